@@ -0,0 +1,125 @@
+//! Python bindings for reading data dirs created by the scraper.
+//!
+//! Build with `maturin develop` inside this directory, then:
+//! ```python
+//! import maven_scraper
+//! for repo in maven_scraper.read_repos("./data/sample10_000"):
+//!     poms = maven_scraper.load_poms("./data/sample10_000", repo.name)
+//! ```
+use maven_scraper_core::data::Data;
+use maven_scraper_core::{CsvRepo, Repo as RpRepo};
+use pyo3::exceptions::PyIOError;
+use pyo3::prelude::*;
+use std::fs;
+use std::path::PathBuf;
+use walkdir::WalkDir;
+
+fn io_error(e: impl std::fmt::Debug) -> PyErr {
+    PyIOError::new_err(format!("{e:?}"))
+}
+
+/// A repository from the github.csv of a data dir
+#[pyclass(get_all)]
+#[derive(Clone)]
+struct Repo {
+    id: String,
+    name: String,
+    has_pom: bool,
+    license: Option<String>,
+    stars: Option<u64>,
+    forks: Option<u64>,
+    created_at: Option<String>,
+    /// In KB
+    disk_usage: Option<u64>,
+    /// Directory name of the repository inside `poms/`
+    path: String,
+}
+
+impl From<CsvRepo> for Repo {
+    fn from(repo: CsvRepo) -> Self {
+        let path = RpRepo::from(repo.clone()).path();
+        Self {
+            id: repo.id,
+            name: repo.name,
+            has_pom: repo.has_pom,
+            license: repo.license,
+            stars: repo.stars,
+            forks: repo.forks,
+            created_at: repo.created_at,
+            disk_usage: repo.disk_usage,
+            path,
+        }
+    }
+}
+
+#[pymethods]
+impl Repo {
+    fn __repr__(&self) -> String {
+        format!("Repo(id={:?}, name={:?})", self.id, self.name)
+    }
+}
+
+/// Reads all repositories of a data dir
+#[pyfunction]
+fn read_repos(data_dir: PathBuf) -> PyResult<Vec<Repo>> {
+    let repos = Data::open(&data_dir).read_repos().map_err(io_error)?;
+    Ok(repos.into_iter().map(Repo::from).collect())
+}
+
+/// Loads all pom.xml files of a repository (`owner/name`) as (relative path, contents) tuples
+#[pyfunction]
+fn load_poms(data_dir: PathBuf, name: String) -> PyResult<Vec<(String, String)>> {
+    let repo = RpRepo {
+        id: String::new(),
+        name,
+    };
+    let dir = Data::open(&data_dir).get_project_dir(&repo);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut poms = Vec::new();
+    for entry in WalkDir::new(&dir).follow_links(true).sort_by_file_name() {
+        let entry = entry.map_err(io_error)?;
+        if entry.file_name() != "pom.xml" {
+            continue;
+        }
+
+        let relative = entry.path().strip_prefix(&dir).unwrap();
+        let contents = fs::read_to_string(entry.path())?;
+        poms.push((relative.to_string_lossy().to_string(), contents));
+    }
+
+    Ok(poms)
+}
+
+/// Converts a serializable value into python objects by round-tripping through json
+fn to_python(py: Python<'_>, value: &impl serde::Serialize) -> PyResult<PyObject> {
+    let json = serde_json::to_string(value).map_err(io_error)?;
+    let loads = py.import("json")?.getattr("loads")?;
+    Ok(loads.call1((json,))?.into())
+}
+
+/// Reads a report from a report file or a data dir, as a dict
+#[pyfunction]
+fn read_report(py: Python<'_>, path: PathBuf) -> PyResult<PyObject> {
+    let report = maven_scraper_core::data::read_report_file(&path).map_err(io_error)?;
+    to_python(py, &report)
+}
+
+/// Reads the per-project analyzer results of a data dir, as a list of dicts
+#[pyfunction]
+fn read_projects(py: Python<'_>, data_dir: PathBuf) -> PyResult<PyObject> {
+    let projects = Data::open(&data_dir).read_projects().map_err(io_error)?;
+    to_python(py, &projects)
+}
+
+#[pymodule]
+fn maven_scraper(_py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<Repo>()?;
+    m.add_function(wrap_pyfunction!(read_repos, m)?)?;
+    m.add_function(wrap_pyfunction!(load_poms, m)?)?;
+    m.add_function(wrap_pyfunction!(read_report, m)?)?;
+    m.add_function(wrap_pyfunction!(read_projects, m)?)?;
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use crate::analyzer::biggest_n;
+use crate::analyzer::Project;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::Path;
+
+/// Locations of the dependabot configuration, relative to the repository root
+pub const DEPENDABOT_FILES: [&str; 2] = [".github/dependabot.yml", ".github/dependabot.yaml"];
+
+/// Locations of the renovate configuration, relative to the repository root
+pub const RENOVATE_FILES: [&str; 8] = [
+    "renovate.json",
+    "renovate.json5",
+    ".github/renovate.json",
+    ".github/renovate.json5",
+    ".gitlab/renovate.json",
+    ".gitlab/renovate.json5",
+    ".renovaterc",
+    ".renovaterc.json",
+];
+
+#[derive(Debug, Deserialize)]
+struct DependabotConfig {
+    #[serde(default)]
+    updates: Vec<DependabotUpdate>,
+    #[serde(default)]
+    registries: HashMap<String, DependabotRegistry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DependabotUpdate {
+    #[serde(rename = "package-ecosystem")]
+    package_ecosystem: String,
+    schedule: Option<DependabotSchedule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DependabotSchedule {
+    interval: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DependabotRegistry {
+    #[serde(rename = "type")]
+    type_: String,
+    url: Option<String>,
+}
+
+/// What a project's dependabot configuration does for maven
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Dependabot {
+    /// Whether updates are enabled for the maven ecosystem
+    pub maven: bool,
+    /// Update intervals of the maven ecosystem entries
+    pub intervals: Vec<String>,
+    /// Urls of the configured maven repositories
+    pub registries: Vec<String>,
+}
+
+pub fn read_dependabot(path: &Path) -> color_eyre::Result<Dependabot> {
+    let config: DependabotConfig = serde_yaml::from_reader(File::open(path)?)?;
+
+    let maven_updates: Vec<_> = config
+        .updates
+        .iter()
+        .filter(|update| update.package_ecosystem == "maven")
+        .collect();
+
+    Ok(Dependabot {
+        maven: !maven_updates.is_empty(),
+        intervals: maven_updates
+            .iter()
+            .filter_map(|update| update.schedule.as_ref()?.interval.clone())
+            .collect(),
+        registries: config
+            .registries
+            .into_values()
+            .filter(|registry| registry.type_ == "maven-repository")
+            .filter_map(|registry| registry.url)
+            .collect(),
+    })
+}
+
+/// What a project's renovate configuration configures for maven
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Renovate {
+    /// Whether the configuration could be read, json5 with comments can't be
+    pub parsed: bool,
+    /// Hosts of maven host rules and registry urls of maven package rules
+    pub registries: Vec<String>,
+}
+
+fn is_maven(value: Option<&Value>) -> bool {
+    match value {
+        Some(Value::String(s)) => s == "maven",
+        Some(Value::Array(values)) => values.iter().any(|v| v.as_str() == Some("maven")),
+        _ => false,
+    }
+}
+
+fn strings(value: Option<&Value>) -> impl Iterator<Item = String> + '_ {
+    let values = match value {
+        Some(Value::Array(values)) => values.as_slice(),
+        Some(value) => std::slice::from_ref(value),
+        None => &[],
+    };
+    values.iter().filter_map(|v| v.as_str().map(str::to_string))
+}
+
+pub fn read_renovate(path: &Path) -> Renovate {
+    let config = fs::read(path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok());
+    let Some(config) = config else {
+        return Renovate::default();
+    };
+
+    let mut registries = Vec::new();
+    for rule in config["hostRules"].as_array().into_iter().flatten() {
+        if is_maven(rule.get("hostType")) {
+            for key in ["matchHost", "hostName", "baseUrl"] {
+                registries.extend(strings(rule.get(key)));
+            }
+        }
+    }
+    for rule in config["packageRules"].as_array().into_iter().flatten() {
+        if is_maven(rule.get("matchDatasources")) || is_maven(rule.get("matchManagers")) {
+            registries.extend(strings(rule.get("registryUrls")));
+        }
+    }
+    registries.extend(strings(config["maven"].get("registryUrls")));
+
+    Renovate {
+        parsed: true,
+        registries,
+    }
+}
+
+/// Dependency update automation used by the projects
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Automation {
+    /// Projects with a dependabot or renovate configuration
+    pub automated: usize,
+    /// Projects with a dependabot configuration
+    pub dependabot: usize,
+    /// Projects with dependabot updates enabled for maven
+    pub dependabot_maven: usize,
+    /// Amount of maven update entries per interval
+    pub dependabot_intervals: DashMap<String, usize>,
+    /// Amount of projects per maven repository configured as dependabot registry
+    pub dependabot_registries: DashMap<String, usize>,
+    /// Projects with a renovate configuration
+    pub renovate: usize,
+    /// Projects with a renovate configuration that couldn't be read, like json5 with comments
+    pub renovate_unparsed: usize,
+    /// Amount of projects per maven registry (host) configured for renovate
+    pub renovate_registries: DashMap<String, usize>,
+}
+
+impl Automation {
+    pub fn add(&mut self, project: &Project) {
+        if project.dependabot.is_some() || project.renovate.is_some() {
+            self.automated += 1;
+        }
+        if let Some(renovate) = &project.renovate {
+            self.renovate += 1;
+            self.renovate_unparsed += usize::from(!renovate.parsed);
+            for registry in &renovate.registries {
+                *self
+                    .renovate_registries
+                    .entry(registry.clone())
+                    .or_insert(0) += 1;
+            }
+        }
+        if let Some(dependabot) = &project.dependabot {
+            self.dependabot += 1;
+            self.dependabot_maven += usize::from(dependabot.maven);
+            for interval in &dependabot.intervals {
+                *self
+                    .dependabot_intervals
+                    .entry(interval.clone())
+                    .or_insert(0) += 1;
+            }
+            for registry in &dependabot.registries {
+                *self
+                    .dependabot_registries
+                    .entry(registry.clone())
+                    .or_insert(0) += 1;
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: Automation) {
+        self.automated += other.automated;
+        self.renovate += other.renovate;
+        self.renovate_unparsed += other.renovate_unparsed;
+        for (registry, count) in other.renovate_registries {
+            *self.renovate_registries.entry(registry).or_insert(0) += count;
+        }
+        self.dependabot += other.dependabot;
+        self.dependabot_maven += other.dependabot_maven;
+        for (interval, count) in other.dependabot_intervals {
+            *self.dependabot_intervals.entry(interval).or_insert(0) += count;
+        }
+        for (registry, count) in other.dependabot_registries {
+            *self.dependabot_registries.entry(registry).or_insert(0) += count;
+        }
+    }
+
+    pub fn print(&self) {
+        println!(
+            "Amount of repos with automated dependency updates: {}",
+            self.automated
+        );
+        println!(
+            "Amount of repos with dependabot: {}, for maven: {}",
+            self.dependabot, self.dependabot_maven
+        );
+        if !self.dependabot_intervals.is_empty() {
+            let intervals = biggest_n(self.dependabot_intervals.clone(), 10);
+            println!("Dependabot maven update intervals: {intervals:#?}");
+        }
+        if !self.dependabot_registries.is_empty() {
+            let registries = biggest_n(self.dependabot_registries.clone(), 25);
+            println!("Top 25 dependabot maven registries: {registries:#?}");
+        }
+        println!(
+            "Amount of repos with renovate: {}, of which unreadable: {}",
+            self.renovate, self.renovate_unparsed
+        );
+        if !self.renovate_registries.is_empty() {
+            let registries = biggest_n(self.renovate_registries.clone(), 25);
+            println!("Top 25 renovate maven registries: {registries:#?}");
+        }
+    }
+}
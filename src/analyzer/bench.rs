@@ -0,0 +1,278 @@
+use crate::analyzer::{fast, Error, Maven, Pom};
+use crate::data::Data;
+use rayon::prelude::*;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+/// Size of the file written to measure the disk write rate
+const WRITE_SIZE: usize = 256 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Maximum amount of poms to parse
+    pub poms: usize,
+    /// Amount of projects to build an effective pom for, 0 skips it
+    pub effective: usize,
+    pub maven: Maven,
+}
+
+#[derive(Debug, Default)]
+pub struct Throughput {
+    pub items: usize,
+    pub bytes: u64,
+    pub elapsed: Duration,
+}
+
+impl Throughput {
+    fn per_second(&self) -> f64 {
+        self.items as f64 / self.elapsed.as_secs_f64()
+    }
+
+    fn mib_per_second(&self) -> f64 {
+        self.bytes as f64 / (1024.0 * 1024.0) / self.elapsed.as_secs_f64()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BenchResult {
+    pub parse: Throughput,
+    pub parse_parallel: Throughput,
+    pub parse_errors: usize,
+    pub fast_path: Throughput,
+    /// Poms the fast path couldn't handle
+    pub fast_path_fallbacks: usize,
+    /// Poms for which the fast path gave a different result than the parser
+    pub fast_path_mismatches: usize,
+    /// Time per successful effective pom build
+    pub effective: Vec<Duration>,
+    pub effective_failed: usize,
+    pub csv_read: Throughput,
+    pub disk_write: Throughput,
+}
+
+impl BenchResult {
+    pub fn print(&self) {
+        println!(
+            "Pom parsing (1 thread): {} poms in {:.2?}, {:.0} poms/s, {:.1} MiB/s ({} failed)",
+            self.parse.items,
+            self.parse.elapsed,
+            self.parse.per_second(),
+            self.parse.mib_per_second(),
+            self.parse_errors
+        );
+        println!(
+            "Pom parsing ({} threads): {:.0} poms/s, {:.1} MiB/s",
+            rayon::current_num_threads(),
+            self.parse_parallel.per_second(),
+            self.parse_parallel.mib_per_second()
+        );
+        println!(
+            "Pom fast path (1 thread): {:.0} poms/s, {:.1} MiB/s ({} fell back, {} mismatched the parser)",
+            self.fast_path.per_second(),
+            self.fast_path.mib_per_second(),
+            self.fast_path_fallbacks,
+            self.fast_path_mismatches
+        );
+
+        if !self.effective.is_empty() || self.effective_failed > 0 {
+            let total: Duration = self.effective.iter().sum();
+            let mean = total.checked_div(self.effective.len() as u32);
+            println!(
+                "Effective pom: {} built, mean {:.2?}, max {:.2?} ({} failed)",
+                self.effective.len(),
+                mean.unwrap_or_default(),
+                self.effective.iter().max().copied().unwrap_or_default(),
+                self.effective_failed
+            );
+        }
+
+        println!(
+            "Csv reading: {} repos in {:.2?}, {:.0} repos/s, {:.1} MiB/s",
+            self.csv_read.items,
+            self.csv_read.elapsed,
+            self.csv_read.per_second(),
+            self.csv_read.mib_per_second()
+        );
+        println!(
+            "Disk writing (temporary directory): {:.1} MiB/s",
+            self.disk_write.mib_per_second()
+        );
+    }
+}
+
+fn pom_paths(dir: &Path, max: usize) -> Vec<PathBuf> {
+    WalkDir::new(dir)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == "pom.xml")
+        .map(|e| e.into_path())
+        .take(max)
+        .collect()
+}
+
+fn bench_parse(poms: &[Vec<u8>]) -> (Throughput, Throughput, usize) {
+    let bytes = poms.iter().map(|p| p.len() as u64).sum();
+
+    let start = Instant::now();
+    let errors = poms.iter().filter(|p| Pom::parse(p).is_err()).count();
+    let sequential = Throughput {
+        items: poms.len(),
+        bytes,
+        elapsed: start.elapsed(),
+    };
+
+    let start = Instant::now();
+    poms.par_iter().for_each(|p| {
+        let _ = Pom::parse(p);
+    });
+    let parallel = Throughput {
+        items: poms.len(),
+        bytes,
+        elapsed: start.elapsed(),
+    };
+
+    (sequential, parallel, errors)
+}
+
+fn bench_fast_path(poms: &[Vec<u8>]) -> (Throughput, usize, usize) {
+    let start = Instant::now();
+    let scanned: Vec<_> = poms.iter().map(|p| fast::scan(p)).collect();
+    let throughput = Throughput {
+        items: poms.len(),
+        bytes: poms.iter().map(|p| p.len() as u64).sum(),
+        elapsed: start.elapsed(),
+    };
+
+    let fallbacks = scanned.iter().filter(|p| p.is_none()).count();
+    let mismatches = poms
+        .par_iter()
+        .zip(scanned)
+        .filter(|(bytes, scanned)| match scanned {
+            Some(pom) => Pom::parse(bytes).ok().as_ref() != Some(pom),
+            None => false,
+        })
+        .count();
+
+    (throughput, fallbacks, mismatches)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), Error> {
+    for entry in WalkDir::new(from).follow_links(true) {
+        let entry = entry.map_err(io::Error::from)?;
+        let target = to.join(entry.path().strip_prefix(from).unwrap());
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds effective poms in copies of the projects, so the data dir stays untouched
+fn bench_effective(data: &Data, maven: &Maven, n: usize) -> Result<(Vec<Duration>, usize), Error> {
+    let mut projects: Vec<_> = data
+        .pom_dir()
+        .read_dir()?
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|p| p.join("pom.xml").exists())
+        .collect();
+    projects.sort();
+
+    let tmp = TempDir::new()?;
+    let mut times = Vec::new();
+    let mut failed = 0;
+    for project in projects.into_iter().take(n) {
+        let dir = tmp.path().join(project.file_name().unwrap());
+        copy_dir(&project, &dir)?;
+
+        let start = Instant::now();
+        match maven.effective_pom(&dir) {
+            Ok(_) => times.push(start.elapsed()),
+            Err(e) => {
+                warn!("Building the effective pom for {project:?} failed: {e}");
+                failed += 1;
+            }
+        }
+    }
+
+    Ok((times, failed))
+}
+
+fn bench_csv(data: &Data) -> Result<Throughput, Error> {
+    let bytes = fs::metadata(data.repo_store())?.len();
+    let start = Instant::now();
+    let repos = data.read_repos()?;
+    Ok(Throughput {
+        items: repos.len(),
+        bytes,
+        elapsed: start.elapsed(),
+    })
+}
+
+/// Writes into a temporary directory, which is removed again even if writing fails
+fn bench_write() -> Result<Throughput, Error> {
+    let tmp = TempDir::new()?;
+    let path = tmp.path().join("bench");
+    let chunk = vec![0x5a; 1024 * 1024];
+
+    let start = Instant::now();
+    let mut f = File::create(&path)?;
+    for _ in 0..WRITE_SIZE / chunk.len() {
+        f.write_all(&chunk)?;
+    }
+    f.sync_all()?;
+    let elapsed = start.elapsed();
+
+    Ok(Throughput {
+        items: 1,
+        bytes: WRITE_SIZE as u64,
+        elapsed,
+    })
+}
+
+/// Measures the throughput of the analysis steps on the given data dir
+///
+/// Warning: this method blocks
+pub fn bench(data: &Data, options: &Options) -> Result<BenchResult, Error> {
+    info!("Reading up to {} poms", options.poms);
+    let poms = pom_paths(data.pom_dir(), options.poms)
+        .into_iter()
+        .map(fs::read)
+        .collect::<Result<Vec<_>, _>>()?;
+    let (parse, parse_parallel, parse_errors) = bench_parse(&poms);
+    let (fast_path, fast_path_fallbacks, fast_path_mismatches) = bench_fast_path(&poms);
+
+    let (effective, effective_failed) = if options.effective > 0 {
+        info!("Building {} effective poms", options.effective);
+        bench_effective(data, &options.maven, options.effective)?
+    } else {
+        Default::default()
+    };
+
+    info!("Reading csv");
+    let csv_read = bench_csv(data)?;
+
+    info!("Writing {} MiB", WRITE_SIZE / 1024 / 1024);
+    let disk_write = bench_write()?;
+
+    Ok(BenchResult {
+        parse,
+        parse_parallel,
+        parse_errors,
+        fast_path,
+        fast_path_fallbacks,
+        fast_path_mismatches,
+        effective,
+        effective_failed,
+        csv_read,
+        disk_write,
+    })
+}
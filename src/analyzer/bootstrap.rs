@@ -0,0 +1,133 @@
+use crate::analyzer::Project;
+use crate::SEED;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Confidence interval of a single statistic obtained by bootstrapping
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Interval {
+    pub name: String,
+    pub estimate: f64,
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// 95% bootstrap confidence intervals for the headline statistics of a report
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Bootstrap {
+    pub iterations: usize,
+    /// Share of projects using external repositories
+    pub has_external: Interval,
+    /// Share of projects declaring distribution repositories
+    pub has_distro: Interval,
+    /// Amount of projects using each of the most popular external repositories
+    pub top_repos: Vec<Interval>,
+}
+
+/// Statistics computed on a single resample
+struct Sample {
+    has_external: usize,
+    has_distro: usize,
+    top_repos: Vec<usize>,
+}
+
+fn sample_stats(projects: &[&Project], top: &[String]) -> Sample {
+    Sample {
+        has_external: projects.iter().filter(|p| !p.repos.is_empty()).count(),
+        has_distro: projects.iter().filter(|p| !p.dist_repos.is_empty()).count(),
+        top_repos: top
+            .iter()
+            .map(|repo| projects.iter().filter(|p| p.repos.contains(repo)).count())
+            .collect(),
+    }
+}
+
+/// Percentile based interval (2.5%, 97.5%) over the resampled values
+fn interval(name: String, estimate: f64, mut values: Vec<f64>) -> Interval {
+    values.sort_by(f64::total_cmp);
+    let at = |q: f64| values[((values.len() - 1) as f64 * q).round() as usize];
+
+    Interval {
+        name,
+        estimate,
+        lower: at(0.025),
+        upper: at(0.975),
+    }
+}
+
+/// Resamples the projects (with replacement) `iterations` times.
+///
+/// Every iteration uses its own ChaCha stream of the fixed seed, so results are reproducible
+/// regardless of how rayon schedules the iterations.
+pub fn bootstrap(projects: &[Project], top: &[String], iterations: usize) -> Option<Bootstrap> {
+    if projects.is_empty() || iterations == 0 {
+        return None;
+    }
+
+    let samples: Vec<Sample> = (0..iterations)
+        .into_par_iter()
+        .map(|i| {
+            let mut rng = ChaCha20Rng::from_seed(SEED);
+            rng.set_stream(i as u64);
+            let resample: Vec<&Project> = (0..projects.len())
+                .map(|_| &projects[rng.gen_range(0..projects.len())])
+                .collect();
+            sample_stats(&resample, top)
+        })
+        .collect();
+
+    let all: Vec<&Project> = projects.iter().collect();
+    let estimate = sample_stats(&all, top);
+    let n = projects.len() as f64;
+
+    Some(Bootstrap {
+        iterations,
+        has_external: interval(
+            "has_external".to_string(),
+            estimate.has_external as f64 / n,
+            samples.iter().map(|s| s.has_external as f64 / n).collect(),
+        ),
+        has_distro: interval(
+            "has_distro".to_string(),
+            estimate.has_distro as f64 / n,
+            samples.iter().map(|s| s.has_distro as f64 / n).collect(),
+        ),
+        top_repos: top
+            .iter()
+            .enumerate()
+            .map(|(i, repo)| {
+                interval(
+                    repo.clone(),
+                    estimate.top_repos[i] as f64,
+                    samples.iter().map(|s| s.top_repos[i] as f64).collect(),
+                )
+            })
+            .collect(),
+    })
+}
+
+impl Bootstrap {
+    pub fn print(&self) {
+        println!(
+            "95% confidence intervals from {} bootstrap iterations:",
+            self.iterations
+        );
+        for share in [&self.has_external, &self.has_distro] {
+            println!(
+                "  {}: {:.3}% [{:.3}%, {:.3}%]",
+                share.name,
+                share.estimate * 100.0,
+                share.lower * 100.0,
+                share.upper * 100.0
+            );
+        }
+        for repo in &self.top_repos {
+            println!(
+                "  {}: {} [{}, {}]",
+                repo.name, repo.estimate, repo.lower, repo.upper
+            );
+        }
+    }
+}
@@ -0,0 +1,28 @@
+use crate::analyzer::xml::{self, Element};
+use std::path::Path;
+
+/// The `groupId:artifactId` of the `<extension>`s in a `.mvn/extensions.xml`, the core extensions
+/// loaded by maven before reading the poms
+pub fn read_extensions(path: &Path) -> Result<Vec<String>, xml::Error> {
+    let extensions = Element::read(path)?;
+    Ok(extensions
+        .children_named("extension")
+        .filter_map(|extension| {
+            Some(format!(
+                "{}:{}",
+                extension.text_of("groupId")?,
+                extension.text_of("artifactId")?
+            ))
+        })
+        .collect())
+}
+
+/// Whether a file in a project is a `.mvn/extensions.xml`
+pub fn is_extensions_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == "extensions.xml")
+        && path
+            .parent()
+            .and_then(Path::file_name)
+            .is_some_and(|dir| dir == ".mvn")
+}
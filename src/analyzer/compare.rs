@@ -0,0 +1,157 @@
+use crate::analyzer::{biggest_n, Report};
+use serde::Serialize;
+use tracing::warn;
+
+/// Z-score above which a sample share is flagged as deviating from the population (95% level)
+const Z_CRITICAL: f64 = 1.96;
+
+/// A single proportion metric measured in both the sample and the population
+#[derive(Debug, Serialize)]
+pub struct MetricComparison {
+    pub metric: String,
+    pub sample: f64,
+    pub population: f64,
+    /// Difference in percentage points (sample - population)
+    pub difference: f64,
+    /// How many standard errors the sample share is away from the population share
+    pub z_score: f64,
+}
+
+impl MetricComparison {
+    fn new(
+        metric: String,
+        sample_count: usize,
+        sample_total: usize,
+        pop_count: usize,
+        pop_total: usize,
+    ) -> Self {
+        let sample = share(sample_count, sample_total);
+        let population = share(pop_count, pop_total);
+        let std_err = (population * (1.0 - population) / sample_total.max(1) as f64).sqrt();
+        let z_score = if std_err > 0.0 {
+            (sample - population) / std_err
+        } else {
+            0.0
+        };
+
+        Self {
+            metric,
+            sample,
+            population,
+            difference: (sample - population) * 100.0,
+            z_score,
+        }
+    }
+
+    pub fn is_biased(&self) -> bool {
+        self.z_score.abs() > Z_CRITICAL
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Comparison {
+    pub sample_total: usize,
+    pub population_total: usize,
+    pub metrics: Vec<MetricComparison>,
+}
+
+fn share(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+/// Compares a report generated from a subset against the report of the full dataset.
+///
+/// The top `n` hosts are taken from the population so hosts missing from the sample show up too.
+pub fn compare(sample: &Report, population: &Report, n: usize) -> Comparison {
+    let (s_total, p_total) = (sample.total, population.total);
+
+    let mut metrics = vec![
+        MetricComparison::new(
+            "has_external_repos".to_string(),
+            sample.has_external_repos,
+            s_total,
+            population.has_external_repos,
+            p_total,
+        ),
+        MetricComparison::new(
+            "has_distro_repos".to_string(),
+            sample.has_distro_repos.len(),
+            s_total,
+            population.has_distro_repos.len(),
+            p_total,
+        ),
+    ];
+
+    // Older reports only count urls, summed per host they count projects with several urls on a
+    // host more than once
+    let has_hosts =
+        |report: &Report| report.has_external_repos == 0 || !report.external_hosts.is_empty();
+    if !has_hosts(sample) || !has_hosts(population) {
+        warn!("Not comparing hosts, analyze the data dirs again to count them");
+        return Comparison {
+            sample_total: s_total,
+            population_total: p_total,
+            metrics,
+        };
+    }
+
+    for (kind, sample_hosts, pop_hosts) in [
+        (
+            "external",
+            &sample.external_hosts,
+            &population.external_hosts,
+        ),
+        ("distro", &sample.distro_hosts, &population.distro_hosts),
+    ] {
+        for (host, pop_count) in biggest_n(pop_hosts.clone(), n) {
+            let sample_count = sample_hosts.get(&host).map(|el| *el.value()).unwrap_or(0);
+            metrics.push(MetricComparison::new(
+                format!("{kind} host {host}"),
+                sample_count,
+                s_total,
+                pop_count,
+                p_total,
+            ));
+        }
+    }
+
+    Comparison {
+        sample_total: s_total,
+        population_total: p_total,
+        metrics,
+    }
+}
+
+impl Comparison {
+    pub fn print(&self) {
+        println!(
+            "Sample of {} repos compared against population of {} repos",
+            self.sample_total, self.population_total
+        );
+        println!(
+            "{:<50} {:>9} {:>9} {:>9} {:>8}",
+            "metric", "sample", "pop.", "diff pp", "z"
+        );
+        for m in &self.metrics {
+            println!(
+                "{:<50} {:>8.3}% {:>8.3}% {:>+9.3} {:>8.2}{}",
+                m.metric,
+                m.sample * 100.0,
+                m.population * 100.0,
+                m.difference,
+                m.z_score,
+                if m.is_biased() { " *" } else { "" }
+            );
+        }
+
+        let biased = self.metrics.iter().filter(|m| m.is_biased()).count();
+        println!(
+            "{biased} of {} metrics deviate significantly (|z| > {Z_CRITICAL}, marked with *)",
+            self.metrics.len()
+        );
+    }
+}
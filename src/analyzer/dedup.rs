@@ -0,0 +1,189 @@
+//! Finds projects with the same poms: manual copies and mirrors of a repo that aren't marked as
+//! forks, which would count the repositories they use more than once.
+//!
+//! Projects are hashed over the paths and contents of their poms, with lines trimmed and empty
+//! lines dropped so copies with other line endings or indentation still match. The hashes are
+//! stored in `content-hashes.jsonl` and the groups of projects sharing one in `dedup-report.json`,
+//! per forge like the repos (`gitlab-dedup-report.json`), since only projects of the same forge
+//! are compared.
+
+use crate::analyzer::Error;
+use crate::data::Data;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use tokio::task::spawn_blocking;
+use tracing::warn;
+use walkdir::WalkDir;
+
+/// The hash of the poms of a project
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentHash {
+    /// Name of the project dir
+    pub project: String,
+    pub hash: String,
+}
+
+/// Projects with the same poms
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Cluster {
+    pub hash: String,
+    /// Sorted, `analyze --exclude-duplicates` keeps the first
+    pub projects: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DedupReport {
+    /// Projects with poms that were hashed
+    pub projects: usize,
+    /// Clusters of more than one project, largest first
+    pub clusters: Vec<Cluster>,
+}
+
+impl DedupReport {
+    pub fn from_hashes(hashes: &[ContentHash]) -> Self {
+        let mut by_hash: HashMap<&str, Vec<String>> = HashMap::new();
+        for hash in hashes {
+            by_hash
+                .entry(hash.hash.as_str())
+                .or_default()
+                .push(hash.project.clone());
+        }
+
+        let mut clusters: Vec<Cluster> = by_hash
+            .into_iter()
+            .filter(|(_, projects)| projects.len() > 1)
+            .map(|(hash, mut projects)| {
+                projects.sort();
+                Cluster {
+                    hash: hash.to_string(),
+                    projects,
+                }
+            })
+            .collect();
+        clusters.sort_by(|a, b| {
+            b.projects
+                .len()
+                .cmp(&a.projects.len())
+                .then_with(|| a.projects.cmp(&b.projects))
+        });
+
+        DedupReport {
+            projects: hashes.len(),
+            clusters,
+        }
+    }
+
+    /// Per duplicate project, the project of its cluster that is kept
+    pub fn duplicates(&self) -> HashMap<&str, &str> {
+        self.clusters
+            .iter()
+            .flat_map(|cluster| {
+                let kept = cluster.projects[0].as_str();
+                cluster.projects[1..]
+                    .iter()
+                    .map(move |project| (project.as_str(), kept))
+            })
+            .collect()
+    }
+
+    pub fn print(&self) {
+        let duplicates: usize = self
+            .clusters
+            .iter()
+            .map(|cluster| cluster.projects.len() - 1)
+            .sum();
+        println!(
+            "Hashed {} projects: {} clusters of projects with the same poms, {duplicates} duplicates",
+            self.projects,
+            self.clusters.len()
+        );
+        for cluster in self.clusters.iter().take(10) {
+            println!(
+                "  {} projects: {}",
+                cluster.projects.len(),
+                cluster.projects.join(", ")
+            );
+        }
+    }
+}
+
+/// Trims the lines and drops the empty ones
+fn normalize(content: &[u8]) -> String {
+    String::from_utf8_lossy(content)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The hash of the poms of the project in `dir`, `None` if it has none
+pub fn hash_project(dir: &Path) -> io::Result<Option<String>> {
+    let poms: Vec<_> = WalkDir::new(dir)
+        .follow_links(true)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == "pom.xml")
+        .map(|e| e.into_path())
+        .collect();
+    if poms.is_empty() {
+        return Ok(None);
+    }
+
+    let mut hasher = Sha256::new();
+    for pom in poms {
+        let relative = pom.strip_prefix(dir).unwrap_or(&pom);
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(normalize(&fs::read(&pom)?).as_bytes());
+        hasher.update([0]);
+    }
+    Ok(Some(format!("{:x}", hasher.finalize())))
+}
+
+/// Hashes every project and writes `content-hashes.jsonl` and `dedup-report.json`
+pub async fn dedup_all(data: &Data) -> Result<DedupReport, Error> {
+    let projects = data.get_project_dirs().await?;
+    let data = data.clone();
+    spawn_blocking(move || -> Result<DedupReport, Error> {
+        let mut hashes: Vec<ContentHash> = projects
+            .par_iter()
+            .filter_map(|dir| match hash_project(dir) {
+                Ok(hash) => Some(ContentHash {
+                    project: dir.file_name()?.to_string_lossy().to_string(),
+                    hash: hash?,
+                }),
+                Err(e) => {
+                    warn!("Failed hashing {dir:?}: {e}");
+                    None
+                }
+            })
+            .collect();
+        hashes.sort_by(|a, b| a.project.cmp(&b.project));
+
+        let report = DedupReport::from_hashes(&hashes);
+        data.write_content_hashes(&hashes)?;
+        data.write_dedup_report(&report)?;
+        Ok(report)
+    })
+    .await
+    .unwrap()
+}
+
+/// Drops the duplicates in a dedup report from the project dirs, returning how many were dropped
+pub fn exclude(projects: &mut Vec<PathBuf>, report: &DedupReport) -> usize {
+    let duplicates: HashSet<&str> = report.duplicates().into_keys().collect();
+    let before = projects.len();
+    projects.retain(|dir| {
+        dir.file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| !duplicates.contains(name))
+    });
+    before - projects.len()
+}
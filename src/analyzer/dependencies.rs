@@ -0,0 +1,148 @@
+//! Usage counts of the dependencies and plugins declared in the poms, for dependency-popularity
+//! studies.
+
+use crate::analyzer::Project;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Amount of artifacts printed per list
+const TOP: usize = 25;
+/// Amount of versions printed per artifact
+const TOP_VERSIONS: usize = 3;
+
+/// Per `groupId:artifactId`, the versions a project declares it with. Versions are kept as
+/// written, so they can be property references like `${spring.version}`.
+pub type Artifacts = BTreeMap<String, BTreeSet<String>>;
+
+/// How many projects use an artifact, and with which versions
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ArtifactUsage {
+    pub projects: usize,
+    /// Amount of projects per declared version, projects not declaring one aren't counted
+    pub versions: BTreeMap<String, usize>,
+}
+
+impl ArtifactUsage {
+    fn merge(&mut self, other: ArtifactUsage) {
+        self.projects += other.projects;
+        for (version, count) in other.versions {
+            *self.versions.entry(version).or_insert(0) += count;
+        }
+    }
+
+    /// The most used versions, most first
+    pub fn top_versions(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut versions: Vec<(&str, usize)> = self
+            .versions
+            .iter()
+            .map(|(version, &count)| (version.as_str(), count))
+            .collect();
+        versions.sort_by(|(a_version, a), (b_version, b)| {
+            b.cmp(a).then_with(|| a_version.cmp(b_version))
+        });
+        versions.truncate(n);
+        versions
+    }
+}
+
+/// Usage per artifact of one kind of declaration
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(transparent)]
+pub struct Usage(pub HashMap<String, ArtifactUsage>);
+
+impl Usage {
+    fn add(&mut self, artifacts: &Artifacts) {
+        for (artifact, versions) in artifacts {
+            let usage = self.0.entry(artifact.clone()).or_default();
+            usage.projects += 1;
+            for version in versions {
+                *usage.versions.entry(version.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn merge(&mut self, other: Usage) {
+        for (artifact, usage) in other.0 {
+            self.0.entry(artifact).or_default().merge(usage);
+        }
+    }
+
+    /// The artifacts used by the most projects, most first
+    pub fn top(&self, n: usize) -> Vec<(&str, &ArtifactUsage)> {
+        let mut top: Vec<(&str, &ArtifactUsage)> = self
+            .0
+            .iter()
+            .map(|(artifact, usage)| (artifact.as_str(), usage))
+            .collect();
+        top.sort_by(|(a_artifact, a), (b_artifact, b)| {
+            b.projects
+                .cmp(&a.projects)
+                .then_with(|| a_artifact.cmp(b_artifact))
+        });
+        top.truncate(n);
+        top
+    }
+
+    fn print(&self, what: &str) {
+        if self.0.is_empty() {
+            return;
+        }
+
+        println!(
+            "Found {} distinct {what}, top {TOP} by projects using them:",
+            self.0.len()
+        );
+        for (artifact, usage) in self.top(TOP) {
+            let versions: Vec<String> = usage
+                .top_versions(TOP_VERSIONS)
+                .into_iter()
+                .map(|(version, count)| format!("{version} ({count})"))
+                .collect();
+            let most_used = if versions.is_empty() {
+                String::new()
+            } else {
+                format!(", most used: {}", versions.join(", "))
+            };
+            println!(
+                "  {artifact}: {} projects, {} versions{most_used}",
+                usage.projects,
+                usage.versions.len()
+            );
+        }
+    }
+}
+
+/// Dependency and plugin usage of the projects
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct DependencyUsage {
+    pub dependencies: Usage,
+    /// Dependencies in `<dependencyManagement>`, including imported BOMs
+    pub managed: Usage,
+    pub plugins: Usage,
+    /// Plugins in `<pluginManagement>`
+    pub managed_plugins: Usage,
+}
+
+impl DependencyUsage {
+    pub fn add(&mut self, project: &Project) {
+        self.dependencies.add(&project.dependencies);
+        self.managed.add(&project.managed_dependencies);
+        self.plugins.add(&project.plugins);
+        self.managed_plugins.add(&project.managed_plugins);
+    }
+
+    pub fn merge(&mut self, other: DependencyUsage) {
+        self.dependencies.merge(other.dependencies);
+        self.managed.merge(other.managed);
+        self.plugins.merge(other.plugins);
+        self.managed_plugins.merge(other.managed_plugins);
+    }
+
+    pub fn print(&self) {
+        self.dependencies.print("dependencies");
+        self.managed.print("managed dependencies");
+        self.plugins.print("plugins");
+        self.managed_plugins.print("managed plugins");
+    }
+}
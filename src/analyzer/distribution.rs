@@ -0,0 +1,170 @@
+//! How projects distribute their binaries: maven repositories, GitHub releases, GitHub Packages
+//! and publishing workflows (see [workflow](crate::analyzer::workflow)).
+
+use crate::analyzer::Project;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+pub const GITHUB_PACKAGES_HOST: &str = "maven.pkg.github.com";
+
+/// Projects cross-tabulated by their distribution channels
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DistributionChannels {
+    /// Indexed by has distributionManagement, has GitHub releases and has a publish workflow
+    pub counts: [[[usize; 2]; 2]; 2],
+    /// Projects whose releases haven't been fetched, not included in `counts`
+    pub unknown_releases: usize,
+    /// Projects with jar or pom assets in their releases, indexed by has distributionManagement
+    #[serde(default)]
+    pub artifact_releases: [usize; 2],
+}
+
+impl DistributionChannels {
+    pub fn add(&mut self, project: &Project) {
+        match project.has_releases {
+            Some(releases) => {
+                self.counts[usize::from(!project.dist_repos.is_empty())][usize::from(releases)]
+                    [usize::from(project.has_publish_workflow)] += 1
+            }
+            None => self.unknown_releases += 1,
+        }
+        if project.release_artifacts > 0 {
+            self.artifact_releases[usize::from(!project.dist_repos.is_empty())] += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: DistributionChannels) {
+        for (dist, releases, workflow) in Self::cells() {
+            self.counts[dist][releases][workflow] += other.counts[dist][releases][workflow];
+        }
+        self.unknown_releases += other.unknown_releases;
+        for dist in 0..2 {
+            self.artifact_releases[dist] += other.artifact_releases[dist];
+        }
+    }
+
+    fn cells() -> impl Iterator<Item = (usize, usize, usize)> {
+        (0..8).map(|i| (i >> 2, (i >> 1) & 1, i & 1))
+    }
+
+    /// Projects publishing GitHub releases without a maven repository to deploy to
+    pub fn github_only(&self) -> usize {
+        self.counts[0][1].iter().sum()
+    }
+
+    pub fn print(&self) {
+        let known: usize = self.counts.iter().flatten().flatten().sum();
+        if known == 0 {
+            return;
+        }
+
+        let yes_no = |b| if b == 1 { "yes" } else { "no" };
+        println!("Distribution channels ({known} repos with fetched releases):");
+        println!("  distributionManagement  releases  publish workflow  repos");
+        for (dist, releases, workflow) in Self::cells() {
+            let count = self.counts[dist][releases][workflow];
+            println!(
+                "  {:>22}  {:>8}  {:>16}  {count} ({:.2}%)",
+                yes_no(dist),
+                yes_no(releases),
+                yes_no(workflow),
+                count as f64 / known as f64 * 100.0
+            );
+        }
+        println!(
+            "Repos distributing exclusively via GitHub releases: {}",
+            self.github_only()
+        );
+        println!(
+            "Repos with jar or pom release assets: {} with distributionManagement, {} without",
+            self.artifact_releases[1], self.artifact_releases[0]
+        );
+        if self.unknown_releases > 0 {
+            println!(
+                "Repos without fetched releases (see fetch-releases): {}",
+                self.unknown_releases
+            );
+        }
+    }
+}
+
+/// Whether a repo publishes maven packages to GitHub Packages
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum GithubPackages {
+    /// Neither the poms nor the workflows configure GitHub Packages
+    #[default]
+    NotConfigured,
+    /// Configured, but the packages of the owner weren't fetched
+    Unverified,
+    /// Configured, but the repo has no published maven packages
+    ConfiguredUnused,
+    /// Configured and the repo has published maven packages
+    Used,
+    /// Published maven packages without GitHub Packages in the poms or workflows
+    PublishedUnconfigured,
+}
+
+impl GithubPackages {
+    /// The verdict for a project, `packages` being the repos its owner published packages from
+    pub fn verdict(project: &Project, packages: Option<&HashSet<String>>) -> Self {
+        let configured = configured_in_pom(project) || project.github_packages_workflow;
+        let published = packages.map(|repos| repos.contains(&project.name));
+        match (configured, published) {
+            (true, None) => GithubPackages::Unverified,
+            (true, Some(false)) => GithubPackages::ConfiguredUnused,
+            (true, Some(true)) => GithubPackages::Used,
+            (false, Some(true)) => GithubPackages::PublishedUnconfigured,
+            (false, _) => GithubPackages::NotConfigured,
+        }
+    }
+}
+
+/// Whether the distributionManagement of any pom deploys to GitHub Packages
+pub fn configured_in_pom(project: &Project) -> bool {
+    project
+        .dist_repos
+        .iter()
+        .any(|repo| repo.contains(GITHUB_PACKAGES_HOST))
+}
+
+/// GitHub Packages usage of the projects
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct GithubPackagesUsage {
+    /// Projects deploying to GitHub Packages in their distributionManagement
+    pub in_pom: usize,
+    /// Projects configuring GitHub Packages in a workflow
+    pub in_workflow: usize,
+    pub verdicts: BTreeMap<GithubPackages, usize>,
+}
+
+impl GithubPackagesUsage {
+    pub fn add(&mut self, project: &Project) {
+        self.in_pom += usize::from(configured_in_pom(project));
+        self.in_workflow += usize::from(project.github_packages_workflow);
+        *self.verdicts.entry(project.github_packages).or_insert(0) += 1;
+    }
+
+    pub fn merge(&mut self, other: GithubPackagesUsage) {
+        self.in_pom += other.in_pom;
+        self.in_workflow += other.in_workflow;
+        for (verdict, count) in other.verdicts {
+            *self.verdicts.entry(verdict).or_insert(0) += count;
+        }
+    }
+
+    pub fn print(&self) {
+        println!(
+            "GitHub Packages configured in poms: {}, in workflows: {}",
+            self.in_pom, self.in_workflow
+        );
+        for (verdict, count) in &self.verdicts {
+            if *verdict != GithubPackages::NotConfigured {
+                println!("  {verdict:?}: {count}");
+            }
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Builds the effective poms of all projects up front, so `analyze` only has to read them.
+
+use crate::analyzer::{record_maven_error, Error, Maven, MavenError, EFFECTIVE_FILE_NAME};
+use crate::data::Data;
+use indicatif::ProgressBar;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::task::{spawn_blocking, JoinSet};
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Amount of projects to build at the same time
+    pub jobs: usize,
+    /// Maximum time a single maven invocation may take
+    pub timeout: Duration,
+    /// Build projects again that failed in an earlier run
+    pub retry_failed: bool,
+    pub maven: Maven,
+}
+
+/// Outcome of building the effective poms of one project, stored in `effective.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EffectiveStatus {
+    /// Name of the project dir
+    pub project: String,
+    /// Poms that have an effective pom, including ones built in earlier runs
+    pub built: usize,
+    /// Poms of `built` that were built without maven
+    #[serde(default)]
+    pub native: usize,
+    pub failed: usize,
+    pub timed_out: usize,
+    pub seconds: f64,
+}
+
+impl EffectiveStatus {
+    pub fn success(&self) -> bool {
+        self.failed == 0 && self.timed_out == 0
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Projects skipped because of an earlier run
+    pub skipped: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub poms_built: usize,
+    pub poms_native: usize,
+    pub poms_failed: usize,
+    pub poms_timed_out: usize,
+}
+
+impl Summary {
+    pub fn print(&self) {
+        println!(
+            "Built effective poms for {} projects, {} failed, {} skipped from earlier runs",
+            self.succeeded, self.failed, self.skipped
+        );
+        println!(
+            "{} poms built ({} without maven), {} failed, {} timed out",
+            self.poms_built, self.poms_native, self.poms_failed, self.poms_timed_out
+        );
+    }
+}
+
+async fn build_pom(dir: &Path, maven: &Maven, timeout: Duration) -> Result<(), MavenError> {
+    let mut cmd = tokio::process::Command::from(maven.effective_pom_command(dir));
+    cmd.kill_on_drop(true);
+
+    let error = match tokio::time::timeout(timeout, cmd.output()).await {
+        Err(_) => MavenError::TimedOut,
+        Ok(Ok(output)) if output.status.success() && dir.join(EFFECTIVE_FILE_NAME).exists() => {
+            return Ok(())
+        }
+        Ok(Ok(output)) => MavenError::failed(output),
+        Ok(Err(e)) => MavenError::Spawn(e),
+    };
+
+    // A killed maven can leave a partial file behind, which would count as built on the next run
+    let _ = tokio::fs::remove_file(dir.join(EFFECTIVE_FILE_NAME)).await;
+
+    Err(error)
+}
+
+async fn build_project(
+    data: Data,
+    project: PathBuf,
+    maven: Maven,
+    timeout: Duration,
+) -> EffectiveStatus {
+    let start = Instant::now();
+
+    let walk_dir = project.clone();
+    let pom_dirs = spawn_blocking(move || {
+        WalkDir::new(walk_dir)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name() == "pom.xml")
+            .filter_map(|e| e.path().parent().map(Path::to_path_buf))
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap();
+
+    let mut status = EffectiveStatus {
+        project: project.file_name().unwrap().to_string_lossy().to_string(),
+        built: 0,
+        native: 0,
+        failed: 0,
+        timed_out: 0,
+        seconds: 0.0,
+    };
+    for dir in pom_dirs {
+        if dir.join(EFFECTIVE_FILE_NAME).exists() {
+            status.built += 1;
+            continue;
+        }
+
+        let (native_maven, native_dir, native_project) =
+            (maven.clone(), dir.clone(), project.clone());
+        let native =
+            spawn_blocking(move || native_maven.native_effective_pom(&native_dir, &native_project))
+                .await
+                .unwrap();
+        if native.is_some() {
+            status.built += 1;
+            status.native += 1;
+            continue;
+        }
+
+        match build_pom(&dir, &maven, timeout).await {
+            Ok(()) => status.built += 1,
+            Err(e) => {
+                match e {
+                    MavenError::TimedOut => {
+                        warn!("Maven timed out in {dir:?}");
+                        status.timed_out += 1
+                    }
+                    _ => status.failed += 1,
+                }
+
+                let (data, project) = (data.clone(), project.clone());
+                spawn_blocking(move || record_maven_error(&data, &project, &e))
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+    status.seconds = start.elapsed().as_secs_f64();
+
+    status
+}
+
+/// Builds the effective poms of every project, recording the outcome per project so an
+/// interrupted run continues where it stopped.
+pub async fn build_all(data: &Data, options: Options) -> Result<Summary, Error> {
+    let done = {
+        let data = data.clone();
+        spawn_blocking(move || data.read_effective_status())
+            .await
+            .unwrap()?
+    };
+
+    let mut summary = Summary::default();
+    let mut projects = data.get_project_dirs().await?;
+    projects.sort();
+    projects.retain(|project| {
+        let name = project.file_name().unwrap().to_string_lossy();
+        let skip = done
+            .get(name.as_ref())
+            .is_some_and(|status| status.success() || !options.retry_failed);
+        if skip {
+            summary.skipped += 1;
+        }
+        !skip
+    });
+    info!(
+        "Building effective poms for {} projects ({} skipped)",
+        projects.len(),
+        summary.skipped
+    );
+
+    let progress = ProgressBar::new(projects.len() as u64);
+    let mut projects = projects.into_iter();
+    let mut running = JoinSet::new();
+    loop {
+        while running.len() < options.jobs.max(1) {
+            match projects.next() {
+                Some(project) => running.spawn(build_project(
+                    data.clone(),
+                    project,
+                    options.maven.clone(),
+                    options.timeout,
+                )),
+                None => break,
+            };
+        }
+
+        let Some(status) = running.join_next().await else {
+            break;
+        };
+        let status = status.unwrap();
+        data.record_effective_status(&status).await?;
+
+        if status.success() {
+            summary.succeeded += 1;
+        } else {
+            summary.failed += 1;
+        }
+        summary.poms_built += status.built;
+        summary.poms_native += status.native;
+        summary.poms_failed += status.failed;
+        summary.poms_timed_out += status.timed_out;
+        progress.inc(1);
+    }
+    progress.finish();
+
+    Ok(summary)
+}
@@ -0,0 +1,436 @@
+//! Extracts the repositories, plugins, dependencies, modules, parent and Java version properties
+//! from a pom by scanning for the few elements [Pom] consists of, without building the tree of the whole
+//! document like [Pom::parse].
+//!
+//! Anything the scanner isn't sure about (doctypes, cdata, namespaced or malformed elements,
+//! incomplete repositories, ...) makes it give up, the caller then falls back to the full parser.
+
+use crate::analyzer::{
+    Build, Dependencies, Dependency, DependencyManagement, Plugin, PluginManagement, Plugins, Pom,
+    Properties, Repositories, Repository,
+};
+
+/// The `<properties>` of [Properties], other properties are skipped
+const PROPERTIES: [&[u8]; 4] = [
+    b"maven.compiler.release",
+    b"maven.compiler.target",
+    b"maven.compiler.source",
+    b"java.version",
+];
+
+fn property<'a>(pom: &'a mut Pom, name: &[u8]) -> Option<&'a mut Option<String>> {
+    let properties = pom.properties.as_mut()?;
+    Some(match name {
+        b"maven.compiler.release" => &mut properties.release,
+        b"maven.compiler.target" => &mut properties.target,
+        b"maven.compiler.source" => &mut properties.source,
+        b"java.version" => &mut properties.java_version,
+        _ => return None,
+    })
+}
+
+/// Whether `name` at `path` is one of the [PROPERTIES]
+fn is_property(path: &[&[u8]], name: &[u8]) -> bool {
+    matches!(path, [b"properties"]) && PROPERTIES.contains(&name)
+}
+
+/// Whether `name` at `path` is a `<module>`
+fn is_module(path: &[&[u8]], name: &[u8]) -> bool {
+    matches!(path, [b"modules"]) && name == b"module"
+}
+
+/// The index of `name` at `path` in the `groupId:artifactId` of the `<parent>`
+fn parent_field(path: &[&[u8]], name: &[u8]) -> Option<usize> {
+    match (path, name) {
+        ([b"parent"], b"groupId") => Some(0),
+        ([b"parent"], b"artifactId") => Some(1),
+        _ => None,
+    }
+}
+
+/// Whether `name` at `path` is a `<goal>` of an execution of a plugin
+fn is_goal(path: &[&[u8]], name: &[u8]) -> bool {
+    match path.strip_suffix(&[&b"executions"[..], b"execution", b"goals"][..]) {
+        Some(plugin) => {
+            name == b"goal"
+                && List::of_item(plugin)
+                    .is_some_and(|list| matches!(list, List::Plugins | List::ManagedPlugins))
+        }
+        None => false,
+    }
+}
+
+/// The lists of items [Pom] consists of
+#[derive(Clone, Copy, PartialEq)]
+enum List {
+    Repositories,
+    Distribution,
+    Plugins,
+    ManagedPlugins,
+    Dependencies,
+    ManagedDependencies,
+}
+
+impl List {
+    const ALL: [List; 6] = [
+        List::Repositories,
+        List::Distribution,
+        List::Plugins,
+        List::ManagedPlugins,
+        List::Dependencies,
+        List::ManagedDependencies,
+    ];
+
+    /// Path of the list element below the root element
+    fn path(self) -> &'static [&'static [u8]] {
+        match self {
+            List::Repositories => &[b"repositories"],
+            List::Distribution => &[b"distributionManagement"],
+            List::Plugins => &[b"build", b"plugins"],
+            List::ManagedPlugins => &[b"build", b"pluginManagement", b"plugins"],
+            List::Dependencies => &[b"dependencies"],
+            List::ManagedDependencies => &[b"dependencyManagement", b"dependencies"],
+        }
+    }
+
+    fn item(self) -> &'static [u8] {
+        match self {
+            List::Repositories | List::Distribution => b"repository",
+            List::Plugins | List::ManagedPlugins => b"plugin",
+            List::Dependencies | List::ManagedDependencies => b"dependency",
+        }
+    }
+
+    fn fields(self) -> &'static [&'static [u8]] {
+        match self {
+            List::Repositories | List::Distribution => &[b"id", b"url"],
+            List::Plugins | List::ManagedPlugins => &[b"groupId", b"artifactId", b"version"],
+            List::Dependencies | List::ManagedDependencies => {
+                &[b"groupId", b"artifactId", b"version", b"scope"]
+            }
+        }
+    }
+
+    fn dependencies(self, pom: &mut Pom) -> Option<&mut Dependencies> {
+        match self {
+            List::Dependencies => pom.dependencies.as_mut(),
+            List::ManagedDependencies => pom.dependency_management.as_mut()?.dependencies.as_mut(),
+            _ => None,
+        }
+    }
+
+    fn plugins(self, pom: &mut Pom) -> Option<&mut Plugins> {
+        let build = pom.build.as_mut()?;
+        match self {
+            List::Plugins => build.plugins.as_mut(),
+            List::ManagedPlugins => build.plugin_management.as_mut()?.plugins.as_mut(),
+            _ => None,
+        }
+    }
+
+    /// The list whose items are children of the element at `path`
+    fn at(path: &[&[u8]]) -> Option<List> {
+        List::ALL.into_iter().find(|list| list.path() == path)
+    }
+
+    /// The list an item element at `path` belongs to
+    fn of_item(path: &[&[u8]]) -> Option<List> {
+        let (item, list_path) = path.split_last()?;
+        List::at(list_path).filter(|list| list.item() == *item)
+    }
+
+    /// Adds a finished item with the goals of its executions, `None` if it is incomplete
+    fn push(self, pom: &mut Pom, fields: Vec<Option<String>>, goals: Vec<String>) -> Option<()> {
+        // The parser leaves empty elements out, not worth handling
+        if fields.iter().flatten().any(String::is_empty) {
+            return None;
+        }
+
+        let mut fields = fields.into_iter();
+        let mut next = || fields.next().flatten();
+        match self {
+            List::Repositories | List::Distribution => {
+                let repository = Repository {
+                    id: next()?,
+                    url: next()?,
+                };
+                let list = if self == List::Repositories {
+                    &mut pom.repositories
+                } else {
+                    &mut pom.distribution_management
+                };
+                list.as_mut()?.repositories.push(repository);
+            }
+            List::Plugins | List::ManagedPlugins => {
+                let plugin = Plugin {
+                    group_id: next(),
+                    artifact_id: next()?,
+                    version: next(),
+                    goals,
+                };
+                self.plugins(pom)?.plugins.push(plugin);
+            }
+            List::Dependencies | List::ManagedDependencies => {
+                let dependency = Dependency {
+                    group_id: next(),
+                    artifact_id: next(),
+                    version: next(),
+                    scope: next(),
+                };
+                self.dependencies(pom)?.dependencies.push(dependency);
+            }
+        }
+        Some(())
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+/// Replaces the predefined and numeric character references, `None` for anything else
+fn unescape(text: &str) -> Option<String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        let end = rest[start..].find(';')? + start;
+        let entity = &rest[start + 1..end];
+        let c = match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => entity.strip_prefix('#')?.parse().ok()?,
+                };
+                char::from_u32(code)?
+            }
+        };
+        out.push(c);
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    Some(out)
+}
+
+/// Whether the innermost open element is a field of a list item, a module or one of the
+/// [PROPERTIES]
+fn in_value(stack: &[&[u8]]) -> bool {
+    match stack.get(1..).and_then(|path| path.split_last()) {
+        Some((field, item_path)) => {
+            is_property(item_path, field)
+                || is_module(item_path, field)
+                || is_goal(item_path, field)
+                || parent_field(item_path, field).is_some()
+                || List::of_item(item_path).is_some_and(|list| list.fields().contains(field))
+        }
+        None => false,
+    }
+}
+
+/// Marks the optional element `name` of [Pom] at `path` as present, `None` if it already was
+fn open_element(pom: &mut Pom, path: &[&[u8]], name: &[u8]) -> Option<()> {
+    let was_present = match (path, name) {
+        ([], b"repositories") => pom.repositories.replace(Repositories::default()),
+        ([], b"distributionManagement") => {
+            pom.distribution_management.replace(Repositories::default())
+        }
+        ([], b"build") => pom
+            .build
+            .replace(Build::default())
+            .map(|_| Default::default()),
+        ([b"build"], b"plugins") => pom
+            .build
+            .as_mut()?
+            .plugins
+            .replace(Plugins::default())
+            .map(|_| Default::default()),
+        ([b"build"], b"pluginManagement") => pom
+            .build
+            .as_mut()?
+            .plugin_management
+            .replace(PluginManagement::default())
+            .map(|_| Default::default()),
+        ([b"build", b"pluginManagement"], b"plugins") => pom
+            .build
+            .as_mut()?
+            .plugin_management
+            .as_mut()?
+            .plugins
+            .replace(Plugins::default())
+            .map(|_| Default::default()),
+        ([], b"properties") => pom
+            .properties
+            .replace(Properties::default())
+            .map(|_| Default::default()),
+        ([], b"dependencies") => pom
+            .dependencies
+            .replace(Dependencies::default())
+            .map(|_| Default::default()),
+        ([], b"dependencyManagement") => pom
+            .dependency_management
+            .replace(DependencyManagement::default())
+            .map(|_| Default::default()),
+        ([b"dependencyManagement"], b"dependencies") => pom
+            .dependency_management
+            .as_mut()?
+            .dependencies
+            .replace(Dependencies::default())
+            .map(|_| Default::default()),
+        _ => return Some(()),
+    };
+    was_present.is_none().then_some(())
+}
+
+/// Returns the [Pom] in `bytes`, or `None` if the full parser has to be used
+pub fn scan(bytes: &[u8]) -> Option<Pom> {
+    let mut pom = Pom::default();
+    let mut stack: Vec<&[u8]> = Vec::new();
+    let mut item: Option<Vec<Option<String>>> = None;
+    let mut goals = Vec::new();
+    let mut parent: [Option<String>; 2] = Default::default();
+    let mut seen_root = false;
+    let mut text_start = 0;
+    let mut pos = 0;
+
+    while let Some(offset) = bytes[pos..].iter().position(|&b| b == b'<') {
+        let start = pos + offset;
+        let text = &bytes[text_start..start];
+        let rest = &bytes[start..];
+
+        if rest.starts_with(b"<!--") {
+            // Comments in a value would split it up, not worth handling
+            if in_value(&stack) && !text.iter().all(u8::is_ascii_whitespace) {
+                return None;
+            }
+            pos = start + 4 + find(&rest[4..], b"-->")? + 3;
+            text_start = pos;
+            continue;
+        }
+        if rest.starts_with(b"<?") {
+            pos = start + find(rest, b"?>")? + 2;
+            text_start = pos;
+            continue;
+        }
+        if rest.starts_with(b"<!") {
+            // Doctypes can define entities and cdata can contain anything
+            return None;
+        }
+
+        // Find the end of the tag, skipping over quoted attribute values
+        let mut quote = None;
+        let end = rest.iter().position(|&b| match quote {
+            Some(q) => {
+                if b == q {
+                    quote = None;
+                }
+                false
+            }
+            None if b == b'"' || b == b'\'' => {
+                quote = Some(b);
+                false
+            }
+            None => b == b'>',
+        })?;
+        let tag = &rest[1..end];
+        pos = start + end + 1;
+        text_start = pos;
+
+        let closing = tag.starts_with(b"/");
+        let self_closing = tag.ends_with(b"/");
+        let name = tag
+            .split(|b| b.is_ascii_whitespace() || *b == b'/')
+            .find(|part| !part.is_empty())?;
+        if name.contains(&b':') {
+            return None;
+        }
+
+        if closing {
+            let value = in_value(&stack);
+            if stack.pop()? != name {
+                return None;
+            }
+            let path = stack.get(1..).unwrap_or_default();
+            if value && (is_module(path, name) || is_goal(path, name)) {
+                let value = unescape(std::str::from_utf8(text).ok()?.trim())?;
+                if value.is_empty() {
+                    return None;
+                }
+                if name == b"module" {
+                    pom.modules.push(value);
+                } else {
+                    goals.push(value);
+                }
+            } else if let Some(index) = parent_field(path, name).filter(|_| value) {
+                let value = unescape(std::str::from_utf8(text).ok()?.trim())?;
+                if value.is_empty() || parent[index].replace(value).is_some() {
+                    return None;
+                }
+            } else if value && is_property(path, name) {
+                let value = unescape(std::str::from_utf8(text).ok()?.trim())?;
+                // The parser leaves empty elements out, not worth handling
+                if value.is_empty() || property(&mut pom, name)?.replace(value).is_some() {
+                    return None;
+                }
+            } else if value {
+                let list = List::of_item(path)?;
+                let index = list.fields().iter().position(|field| *field == name)?;
+                let value = unescape(std::str::from_utf8(text).ok()?.trim())?;
+                if item.as_mut()?[index].replace(value).is_some() {
+                    return None;
+                }
+            } else if let Some(list) = List::at(path).filter(|list| list.item() == name) {
+                list.push(&mut pom, item.take()?, std::mem::take(&mut goals))?;
+            }
+            continue;
+        }
+
+        // Values with child elements aren't simple strings
+        if in_value(&stack) {
+            return None;
+        }
+
+        if let Some(path) = stack.get(1..) {
+            open_element(&mut pom, path, name)?;
+
+            if let Some(list) = List::at(path).filter(|list| list.item() == name) {
+                if self_closing {
+                    return None;
+                }
+                item = Some(vec![None; list.fields().len()]);
+            }
+
+            // Empty values
+            let is_field = List::of_item(path).is_some_and(|list| list.fields().contains(&name))
+                || is_property(path, name)
+                || is_module(path, name)
+                || is_goal(path, name)
+                || parent_field(path, name).is_some();
+            if is_field && self_closing {
+                return None;
+            }
+        }
+
+        if stack.is_empty() {
+            // A second root element, or the `<projects>` of a multi-module effective pom
+            if seen_root || name == b"projects" {
+                return None;
+            }
+            seen_root = true;
+        }
+        if !self_closing {
+            stack.push(name);
+        }
+    }
+
+    if let [Some(group_id), Some(artifact_id)] = parent {
+        pom.parent = Some(format!("{group_id}:{artifact_id}"));
+    }
+    (seen_root && stack.is_empty()).then_some(pom)
+}
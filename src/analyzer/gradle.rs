@@ -0,0 +1,270 @@
+//! Extracts the repositories of Gradle build scripts (Groovy and Kotlin DSL) without evaluating
+//! them. Only urls written as string literals are found, urls built from variables are skipped.
+
+use std::fs;
+use std::path::Path;
+
+/// The Gradle files downloaded and analyzed, by file name
+pub const GRADLE_FILES: &[&str] = &[
+    "build.gradle",
+    "build.gradle.kts",
+    "settings.gradle",
+    "settings.gradle.kts",
+];
+
+/// Urls of the repository shorthands, `mavenLocal()` isn't a remote repository
+const SHORTHANDS: &[(&str, &str)] = &[
+    ("mavenCentral", "https://repo.maven.apache.org/maven2"),
+    ("google", "https://dl.google.com/dl/android/maven2"),
+    ("gradlePluginPortal", "https://plugins.gradle.org/m2"),
+    ("jcenter", "https://jcenter.bintray.com"),
+];
+
+/// Blocks whose repositories are deployed to instead of resolved from
+const PUBLISHING_BLOCKS: &[&str] = &["publishing", "uploadArchives"];
+
+/// The repositories of a Gradle script
+#[derive(Debug, Default, PartialEq)]
+pub struct GradleScript {
+    /// Repositories dependencies and plugins are resolved from, like `<repositories>`
+    pub repositories: Vec<String>,
+    /// Repositories in `publishing {}`, like `<distributionManagement>`
+    pub publishing: Vec<String>,
+}
+
+pub fn is_gradle_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| GRADLE_FILES.contains(&name))
+}
+
+pub fn read_script(path: &Path) -> color_eyre::Result<GradleScript> {
+    let bytes = fs::read(path)?;
+    Ok(parse(&String::from_utf8_lossy(&bytes)))
+}
+
+pub fn parse(script: &str) -> GradleScript {
+    let script = strip_comments(script);
+    let publishing: Vec<(usize, usize)> = PUBLISHING_BLOCKS
+        .iter()
+        .flat_map(|name| blocks(&script, name))
+        .collect();
+
+    let mut result = GradleScript::default();
+    for (start, end) in blocks(&script, "repositories") {
+        let urls = repository_urls(&script[start..end]);
+        if publishing.iter().any(|&(s, e)| s <= start && end <= e) {
+            result.publishing.extend(urls);
+        } else {
+            result.repositories.extend(urls);
+        }
+    }
+    // `uploadArchives { repositories { mavenDeployer { repository(url: "...") } } }`
+    for &(start, end) in &publishing {
+        let block = &script[start..end];
+        for name in ["repository", "snapshotRepository"] {
+            for (s, e) in calls(block, name) {
+                result.publishing.extend(url_argument(&block[s..e]));
+            }
+        }
+    }
+
+    for urls in [&mut result.repositories, &mut result.publishing] {
+        urls.sort_unstable();
+        urls.dedup();
+    }
+    result
+}
+
+fn repository_urls(block: &str) -> Vec<String> {
+    let mut urls = Vec::new();
+    for (name, url) in SHORTHANDS {
+        if !calls(block, name).is_empty() {
+            urls.push(url.to_string());
+        }
+    }
+    // `maven("url")`, `maven(url = "url")` and `maven { url = uri("url") }`
+    for (start, end) in calls(block, "maven")
+        .into_iter()
+        .chain(blocks(block, "maven"))
+    {
+        urls.extend(url_argument(&block[start..end]));
+    }
+    urls
+}
+
+/// The url set in the body of a `maven` block or passed to a call, if it's a literal
+fn url_argument(body: &str) -> Option<String> {
+    match find_word(body, "url").or_else(|| find_word(body, "setUrl")) {
+        Some(at) => literal_value(&body[at..]),
+        None => literal_value(body),
+    }
+}
+
+/// The string literal assigned or passed at the start of `script`, also when wrapped in `uri()`
+fn literal_value(script: &str) -> Option<String> {
+    let mut rest = script.trim_start_matches(|c: char| c.is_whitespace() || "=:(".contains(c));
+    for wrapper in ["uri", "URI", "new URI", "file"] {
+        if let Some(inner) = rest.strip_prefix(wrapper) {
+            if inner.trim_start().starts_with('(') {
+                rest = inner.trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+                break;
+            }
+        }
+    }
+
+    let bytes = rest.as_bytes();
+    if !matches!(bytes.first(), Some(b'"' | b'\'')) {
+        return None;
+    }
+    let end = skip_string(bytes, 0);
+    if bytes.get(end.wrapping_sub(1)) != Some(&bytes[0]) || end < 2 {
+        return None;
+    }
+    let literal = rest[1..end - 1].trim();
+    (!literal.is_empty()).then(|| literal.to_string())
+}
+
+/// Removes `//` and `/* */` comments, keeping string literals (which can contain `//` in urls)
+fn strip_comments(script: &str) -> String {
+    let bytes = script.as_bytes();
+    let mut out = String::with_capacity(script.len());
+    let mut i = 0;
+    let mut last = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => i = skip_string(bytes, i),
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                out.push_str(&script[last..i]);
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+                last = i;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                out.push_str(&script[last..i]);
+                i = script[i + 2..]
+                    .find("*/")
+                    .map_or(bytes.len(), |end| i + end + 4);
+                out.push(' ');
+                last = i;
+            }
+            _ => i += 1,
+        }
+    }
+    out.push_str(&script[last.min(script.len())..]);
+    out
+}
+
+/// The index after a string literal starting at `start`, including triple quoted ones
+fn skip_string(bytes: &[u8], start: usize) -> usize {
+    let quote = bytes[start];
+    let triple = bytes.get(start + 1) == Some(&quote) && bytes.get(start + 2) == Some(&quote);
+    let mut i = start + if triple { 3 } else { 1 };
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'\n' if !triple => return i,
+            c if c == quote => {
+                if !triple {
+                    return i + 1;
+                }
+                if bytes.get(i + 1) == Some(&quote) && bytes.get(i + 2) == Some(&quote) {
+                    return i + 3;
+                }
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+fn is_ident(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_'
+}
+
+/// Positions after each occurrence of `word` as a whole identifier outside of strings
+fn words(script: &str, word: &str) -> Vec<usize> {
+    let bytes = script.as_bytes();
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => i = skip_string(bytes, i),
+            c if is_ident(c) => {
+                let start = i;
+                while i < bytes.len() && is_ident(bytes[i]) {
+                    i += 1;
+                }
+                if &script[start..i] == word {
+                    found.push(i);
+                }
+            }
+            _ => i += 1,
+        }
+    }
+    found
+}
+
+fn find_word(script: &str, word: &str) -> Option<usize> {
+    words(script, word).first().copied()
+}
+
+/// The contents of the brackets opened by `open` at `start`, ignoring brackets in strings
+fn enclosed(script: &str, start: usize, open: u8, close: u8) -> Option<(usize, usize)> {
+    let bytes = script.as_bytes();
+    let mut depth = 0;
+    let mut i = start;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' | b'\'' => {
+                i = skip_string(bytes, i);
+                continue;
+            }
+            c if c == open => depth += 1,
+            c if c == close => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some((start + 1, i));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The first non whitespace position at or after `i`
+fn skip_whitespace(script: &str, i: usize) -> usize {
+    script[i..]
+        .find(|c: char| !c.is_whitespace())
+        .map_or(script.len(), |offset| i + offset)
+}
+
+/// Ranges of the bodies of `name { ... }` blocks
+fn blocks(script: &str, name: &str) -> Vec<(usize, usize)> {
+    words(script, name)
+        .into_iter()
+        .filter_map(|end| {
+            let open = skip_whitespace(script, end);
+            (script.as_bytes().get(open) == Some(&b'{'))
+                .then(|| enclosed(script, open, b'{', b'}'))
+                .flatten()
+        })
+        .collect()
+}
+
+/// Ranges of the arguments of `name(...)` calls
+fn calls(script: &str, name: &str) -> Vec<(usize, usize)> {
+    words(script, name)
+        .into_iter()
+        .filter_map(|end| {
+            let open = skip_whitespace(script, end);
+            (script.as_bytes().get(open) == Some(&b'('))
+                .then(|| enclosed(script, open, b'(', b')'))
+                .flatten()
+        })
+        .collect()
+}
@@ -0,0 +1,171 @@
+use crate::analyzer::{repo_name, Project};
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+use std::io;
+use std::io::Write;
+use url::Url;
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum GraphFormat {
+    /// Graphviz DOT
+    Dot,
+    /// GraphML, readable by networkx and Gephi
+    Graphml,
+    /// Tab separated `source target kind` lines
+    Edges,
+}
+
+/// How a repository host is used by a project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EdgeKind {
+    External,
+    Distribution,
+}
+
+impl EdgeKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            EdgeKind::External => "external",
+            EdgeKind::Distribution => "distribution",
+        }
+    }
+}
+
+/// Bipartite graph of GitHub repositories and the repository hosts they declare
+#[derive(Debug, Default)]
+pub struct Graph {
+    repos: BTreeSet<String>,
+    hosts: BTreeSet<String>,
+    edges: BTreeSet<(String, String, EdgeKind)>,
+}
+
+impl Graph {
+    pub fn from_projects(projects: &[Project]) -> Self {
+        let mut graph = Graph::default();
+
+        for project in projects {
+            let repo = repo_name(&project.name);
+            let declared = project
+                .repos
+                .iter()
+                .map(|url| (url, EdgeKind::External))
+                .chain(
+                    project
+                        .dist_repos
+                        .iter()
+                        .map(|url| (url, EdgeKind::Distribution)),
+                );
+
+            for (url, kind) in declared {
+                let Some(host) = Url::parse(url)
+                    .ok()
+                    .and_then(|url| url.host_str().map(str::to_string))
+                else {
+                    continue;
+                };
+
+                graph.hosts.insert(host.clone());
+                graph.edges.insert((repo.clone(), host, kind));
+            }
+            graph.repos.insert(repo);
+        }
+
+        graph
+    }
+
+    pub fn write(&self, format: GraphFormat, out: &mut impl Write) -> io::Result<()> {
+        match format {
+            GraphFormat::Dot => self.write_dot(out),
+            GraphFormat::Graphml => self.write_graphml(out),
+            GraphFormat::Edges => self.write_edges(out),
+        }
+    }
+
+    fn write_dot(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "digraph repositories {{")?;
+        for repo in &self.repos {
+            let repo = escape_dot(repo);
+            writeln!(out, "  \"repo:{repo}\" [label=\"{repo}\", type=repo];")?;
+        }
+        for host in &self.hosts {
+            let host = escape_dot(host);
+            writeln!(
+                out,
+                "  \"host:{host}\" [label=\"{host}\", type=host, shape=box];"
+            )?;
+        }
+        for (repo, host, kind) in &self.edges {
+            writeln!(
+                out,
+                "  \"repo:{}\" -> \"host:{}\" [kind={}];",
+                escape_dot(repo),
+                escape_dot(host),
+                kind.as_str()
+            )?;
+        }
+        writeln!(out, "}}")
+    }
+
+    fn write_graphml(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#
+        )?;
+        writeln!(
+            out,
+            r#"  <key id="type" for="node" attr.name="type" attr.type="string"/>"#
+        )?;
+        writeln!(
+            out,
+            r#"  <key id="kind" for="edge" attr.name="kind" attr.type="string"/>"#
+        )?;
+        writeln!(out, r#"  <graph id="repositories" edgedefault="directed">"#)?;
+        for repo in &self.repos {
+            writeln!(
+                out,
+                r#"    <node id="repo:{}"><data key="type">repo</data></node>"#,
+                escape(repo)
+            )?;
+        }
+        for host in &self.hosts {
+            writeln!(
+                out,
+                r#"    <node id="host:{}"><data key="type">host</data></node>"#,
+                escape(host)
+            )?;
+        }
+        for (repo, host, kind) in &self.edges {
+            writeln!(
+                out,
+                r#"    <edge source="repo:{}" target="host:{}"><data key="kind">{}</data></edge>"#,
+                escape(repo),
+                escape(host),
+                kind.as_str()
+            )?;
+        }
+        writeln!(out, "  </graph>")?;
+        writeln!(out, "</graphml>")
+    }
+
+    fn write_edges(&self, out: &mut impl Write) -> io::Result<()> {
+        for (repo, host, kind) in &self.edges {
+            writeln!(out, "{repo}\t{host}\t{}", kind.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Escapes a quoted DOT id
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
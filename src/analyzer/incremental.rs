@@ -0,0 +1,152 @@
+//! Cache of the analyzed projects for `analyze --incremental`. Every project is stored with a
+//! fingerprint of its files, projects whose fingerprint didn't change aren't analyzed again.
+
+use crate::analyzer::parents::ParentStatus;
+use crate::analyzer::{Maven, Project};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+/// Bump when [Project] gains fields or is analyzed differently, so older results aren't reused
+const VERSION: u32 = 8;
+
+/// The options that change the analysis of a project, a cache made with others is discarded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Settings {
+    version: u32,
+    build_effective: bool,
+    fast_path: bool,
+    /// How effective poms are built, if they are
+    maven: Option<Maven>,
+}
+
+impl Settings {
+    pub fn new(build_effective: bool, fast_path: bool, maven: &Maven) -> Self {
+        Self {
+            version: VERSION,
+            build_effective,
+            fast_path,
+            maven: build_effective.then(|| maven.clone()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Cached {
+    fingerprint: String,
+    project: Project,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cache {
+    settings: Settings,
+    projects: HashMap<String, Cached>,
+}
+
+impl Cache {
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            settings,
+            projects: HashMap::new(),
+        }
+    }
+
+    /// Reads the cache of an earlier run, starting over if there is none or its settings differ
+    ///
+    /// Warning: this method blocks
+    pub fn load(path: &Path, settings: Settings) -> Self {
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Self::new(settings),
+            Err(e) => {
+                warn!("Not using analysis cache {path:?}: {e}");
+                return Self::new(settings);
+            }
+        };
+        match serde_json::from_reader::<_, Cache>(BufReader::new(file)) {
+            Ok(cache) if cache.settings == settings => {
+                info!("Loaded {} cached projects", cache.projects.len());
+                cache
+            }
+            Ok(_) => {
+                info!("Analysis cache was made with other settings, analyzing all projects");
+                Self::new(settings)
+            }
+            Err(e) => {
+                warn!("Not using invalid analysis cache {path:?}: {e}");
+                Self::new(settings)
+            }
+        }
+    }
+
+    /// The earlier result of a project, if its files didn't change since
+    pub fn get(&self, name: &str, fingerprint: &str) -> Option<&Project> {
+        self.projects
+            .get(name)
+            .filter(|cached| cached.fingerprint == fingerprint)
+            .map(|cached| &cached.project)
+    }
+
+    pub fn insert(&mut self, project: Project, fingerprint: String) {
+        self.projects.insert(
+            project.name.clone(),
+            Cached {
+                fingerprint,
+                project,
+            },
+        );
+    }
+
+    /// Warning: this method blocks
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let tmp = path.with_extension("json.new");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer(&mut writer, self)?;
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        fs::rename(tmp, path)
+    }
+}
+
+/// Hash of the paths, sizes and modification times of all files of a project, and of the outcome
+/// of fetching its parents if given
+///
+/// Warning: this method blocks
+pub fn fingerprint(dir: &Path, parents: Option<&ParentStatus>) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    for entry in WalkDir::new(dir).follow_links(true).sort_by_file_name() {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let meta = entry.metadata()?;
+        let modified = meta
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+
+        let relative = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        hasher.update(relative.to_string_lossy().as_bytes());
+        hasher.update([0]);
+        hasher.update(meta.len().to_le_bytes());
+        hasher.update(modified.to_le_bytes());
+    }
+    if let Some(parents) = parents {
+        hasher.update([0]);
+        hasher.update(serde_json::to_vec(parents)?);
+    }
+
+    Ok(hasher.finalize()[..16]
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect())
+}
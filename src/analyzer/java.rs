@@ -0,0 +1,133 @@
+//! The Java versions projects compile for, from the configuration of the maven-compiler-plugin
+//! or the `maven.compiler.*` properties of their poms.
+
+use crate::analyzer::resolve::Placeholders;
+use crate::analyzer::{Pom, Project};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+pub const COMPILER_PLUGIN: &str = "maven-compiler-plugin";
+
+/// Whether finding the Java version of a pom needs its [Placeholders]: it configures the
+/// compiler plugin, or its properties reference other properties
+pub fn needs_placeholders(pom: &Pom) -> bool {
+    let compiler = pom
+        .plugins()
+        .chain(pom.managed_plugins())
+        .any(|plugin| plugin.artifact_id == COMPILER_PLUGIN);
+    let references = pom.properties.as_ref().is_some_and(|properties| {
+        [
+            &properties.release,
+            &properties.target,
+            &properties.source,
+            &properties.java_version,
+        ]
+        .into_iter()
+        .flatten()
+        .any(|value| value.contains("${"))
+    });
+    compiler || references
+}
+
+/// The Java version a pom compiles for, by [normalize]: the `release`, `target` or `source` of
+/// the compiler plugin, then the `maven.compiler.*` properties, then `java.version`. `None` if
+/// the pom sets none of them, the version then comes from a parent or the plugin's default.
+pub fn declared_version(pom: &Pom, placeholders: Option<&Placeholders>) -> Option<String> {
+    let configured = placeholders.and_then(|placeholders| {
+        ["release", "target", "source"]
+            .into_iter()
+            .find_map(|name| placeholders.plugin_configuration(COMPILER_PLUGIN, name))
+    });
+    let property = || {
+        let properties = pom.properties.as_ref()?;
+        let value = [
+            &properties.release,
+            &properties.target,
+            &properties.source,
+            &properties.java_version,
+        ]
+        .into_iter()
+        .flatten()
+        .find(|value| !value.trim().is_empty())?;
+        Some(match placeholders {
+            Some(placeholders) => placeholders.resolve(value),
+            None => value.clone(),
+        })
+    };
+    configured
+        .or_else(property)
+        .map(|version| normalize(&version))
+}
+
+/// `1.8` and `8` are both `8`. Values that aren't a version, like a reference to a property of
+/// a parent, are kept as written.
+pub fn normalize(version: &str) -> String {
+    let version = version.trim();
+    let major = version.strip_prefix("1.").unwrap_or(version);
+    if !major.is_empty() && major.bytes().all(|b| b.is_ascii_digit()) {
+        major.to_string()
+    } else {
+        version.to_string()
+    }
+}
+
+fn is_version(version: &str) -> bool {
+    !version.is_empty() && version.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Projects by the Java versions their poms compile for
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct JavaVersions {
+    /// Projects per version, a project with modules for several versions counts for each
+    pub versions: BTreeMap<String, usize>,
+    /// Projects declaring versions only as references that couldn't be resolved, like
+    /// `${jdk.version}` of a parent
+    pub unresolved: usize,
+    /// Projects with poms that don't declare a version
+    pub undeclared: usize,
+}
+
+impl JavaVersions {
+    pub fn add(&mut self, project: &Project) {
+        if project.poms == 0 {
+            return;
+        }
+        if project.java_versions.is_empty() {
+            self.undeclared += 1;
+            return;
+        }
+
+        let mut resolved = false;
+        for version in project.java_versions.iter().filter(|v| is_version(v)) {
+            *self.versions.entry(version.clone()).or_insert(0) += 1;
+            resolved = true;
+        }
+        if !resolved {
+            self.unresolved += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: JavaVersions) {
+        for (version, count) in other.versions {
+            *self.versions.entry(version).or_insert(0) += count;
+        }
+        self.unresolved += other.unresolved;
+        self.undeclared += other.undeclared;
+    }
+
+    pub fn print(&self) {
+        if self.versions.is_empty() && self.unresolved == 0 {
+            return;
+        }
+
+        println!(
+            "Java versions compiled for ({} repos with unresolved versions, {} without one):",
+            self.unresolved, self.undeclared
+        );
+        let mut versions: Vec<(&String, &usize)> = self.versions.iter().collect();
+        versions.sort_by_key(|(version, _)| version.parse::<u32>().unwrap_or(u32::MAX));
+        for (version, count) in versions {
+            println!("  Java {version}: {count}");
+        }
+    }
+}
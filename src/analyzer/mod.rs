@@ -1,43 +1,321 @@
+use crate::analyzer::automation::{Automation, Dependabot, Renovate};
+use crate::analyzer::bootstrap::Bootstrap;
+use crate::analyzer::dependencies::{Artifacts, DependencyUsage};
+use crate::analyzer::distribution::{DistributionChannels, GithubPackages, GithubPackagesUsage};
+use crate::analyzer::java::JavaVersions;
+use crate::analyzer::parents::{ParentResolution, ParentStatus};
+use crate::analyzer::poms::PomHistogram;
+use crate::analyzer::tables::OutputFormat;
+use crate::analyzer::workflow::{PublishStep, WorkflowUsage};
+use crate::analyzer::xml::Element;
 use crate::data;
-use crate::data::Data;
-use color_eyre::eyre::{eyre, WrapErr};
+use crate::data::{Data, ErrorRecord};
+use crate::{cap_per_owner, SEED};
+use clap::{Args, ValueEnum};
 use dashmap::DashMap;
+use rand::prelude::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::{BTreeMap, HashMap, HashSet};
-use std::fs::File;
-use std::path::Path;
-use std::process::{Command, Stdio};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Mutex;
-use std::time::Duration;
-use std::{fs, io};
+use std::sync::{Condvar, Mutex};
 use thiserror::Error;
-use tokio::time::Instant;
-use tracing::{error, info, trace};
+use tracing::{debug, error, info, warn};
 use url::Url;
 use walkdir::WalkDir;
 
-#[derive(Debug, Deserialize, PartialEq, Default)]
+pub mod automation;
+pub mod bench;
+pub mod bootstrap;
+pub mod build_files;
+pub mod compare;
+pub mod dedup;
+pub mod dependencies;
+pub mod distribution;
+pub mod effective;
+pub mod fast;
+pub mod gradle;
+pub mod graph;
+pub mod incremental;
+pub mod java;
+pub mod parents;
+pub mod poms;
+pub mod resolve;
+pub mod tables;
+pub mod urls;
+pub mod workflow;
+pub mod xml;
+
+/// The parts of a pom the analysis uses, read by [Pom::parse]
+#[derive(Debug, PartialEq, Default)]
 pub struct Pom {
     pub repositories: Option<Repositories>,
-    #[serde(rename = "distributionManagement")]
     pub distribution_management: Option<Repositories>,
+    pub build: Option<Build>,
+    pub dependencies: Option<Dependencies>,
+    pub dependency_management: Option<DependencyManagement>,
+    pub properties: Option<Properties>,
+    /// The `<modules>` of an aggregator pom, as written
+    pub modules: Vec<String>,
+    /// `groupId:artifactId` of the `<parent>`
+    pub parent: Option<String>,
+}
+
+/// The `<properties>` setting the Java version, the others are skipped
+#[derive(Debug, PartialEq, Default)]
+pub struct Properties {
+    /// `maven.compiler.release`
+    pub release: Option<String>,
+    /// `maven.compiler.target`
+    pub target: Option<String>,
+    /// `maven.compiler.source`
+    pub source: Option<String>,
+    /// `java.version`, Spring Boot's parent sets the compiler versions to it
+    pub java_version: Option<String>,
 }
 
-#[derive(Debug, Deserialize, PartialEq, Default)]
+#[derive(Debug, PartialEq, Default)]
 pub struct Repositories {
-    #[serde(rename = "repository", default)]
     pub repositories: Vec<Repository>,
 }
 
-#[derive(Debug, Deserialize, PartialEq)]
+#[derive(Debug, PartialEq)]
 pub struct Repository {
     pub id: String,
     pub url: String,
 }
 
+#[derive(Debug, PartialEq, Default)]
+pub struct Build {
+    pub plugins: Option<Plugins>,
+    pub plugin_management: Option<PluginManagement>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct PluginManagement {
+    pub plugins: Option<Plugins>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct Plugins {
+    pub plugins: Vec<Plugin>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Plugin {
+    pub group_id: Option<String>,
+    pub artifact_id: String,
+    pub version: Option<String>,
+    /// The goals of all `<executions>`
+    pub goals: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct DependencyManagement {
+    pub dependencies: Option<Dependencies>,
+}
+
+#[derive(Debug, PartialEq, Default)]
+pub struct Dependencies {
+    pub dependencies: Vec<Dependency>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Dependency {
+    pub group_id: Option<String>,
+    pub artifact_id: Option<String>,
+    pub version: Option<String>,
+    pub scope: Option<String>,
+}
+
+/// The children named `name` of all `parents`, as repeated elements are merged
+fn all<'a>(parents: &[&'a Element], name: &str) -> Vec<&'a Element> {
+    parents
+        .iter()
+        .flat_map(|parent| &parent.children)
+        .filter(|child| child.name == name)
+        .collect()
+}
+
+/// The text of the first child named `name` that isn't empty
+fn value(elements: &[&Element], name: &str) -> Option<String> {
+    elements
+        .iter()
+        .filter_map(|element| element.text_of(name))
+        .find(|text| !text.is_empty())
+        .map(str::to_string)
+}
+
+/// The `item`s of `lists` that are complete enough for `of`, `None` without any list
+fn items<T>(lists: &[&Element], item: &str, of: fn(&Element) -> Option<T>) -> Option<Vec<T>> {
+    (!lists.is_empty()).then(|| all(lists, item).into_iter().filter_map(of).collect())
+}
+
+impl Repository {
+    fn of(element: &Element) -> Option<Self> {
+        Some(Repository {
+            id: value(&[element], "id").unwrap_or_default(),
+            url: value(&[element], "url")?,
+        })
+    }
+}
+
+impl Dependency {
+    fn of(element: &Element) -> Option<Self> {
+        Some(Dependency {
+            group_id: value(&[element], "groupId"),
+            artifact_id: value(&[element], "artifactId"),
+            version: value(&[element], "version"),
+            scope: value(&[element], "scope"),
+        })
+    }
+
+    /// `groupId:artifactId`, `None` for invalid dependencies missing either
+    pub fn key(&self) -> Option<String> {
+        Some(format!(
+            "{}:{}",
+            self.group_id.as_deref()?,
+            self.artifact_id.as_deref()?
+        ))
+    }
+}
+
+impl Plugin {
+    fn of(element: &Element) -> Option<Self> {
+        Some(Plugin {
+            group_id: value(&[element], "groupId"),
+            artifact_id: value(&[element], "artifactId")?,
+            version: value(&[element], "version"),
+            goals: all(&all(&all(&[element], "executions"), "execution"), "goals")
+                .into_iter()
+                .flat_map(|goals| goals.children_named("goal"))
+                .filter(|goal| !goal.text.is_empty())
+                .map(|goal| goal.text.clone())
+                .collect(),
+        })
+    }
+
+    /// Whether the plugin is one of the [BUNDLING_PLUGINS] and bundles the artifact of `pom`.
+    /// Spring Boot's plugin only does with its `repackage` goal, which Spring Boot's parent runs
+    /// by default.
+    pub fn bundles(&self, pom: &Pom) -> bool {
+        match self.artifact_id.as_str() {
+            "spring-boot-maven-plugin" => {
+                pom.parent.as_deref() == Some(SPRING_BOOT_PARENT)
+                    || pom
+                        .managed_plugins()
+                        .filter(|managed| managed.artifact_id == self.artifact_id)
+                        .chain([self])
+                        .any(|plugin| plugin.goals.iter().any(|goal| goal == "repackage"))
+            }
+            artifact_id => BUNDLING_PLUGINS.contains(&artifact_id),
+        }
+    }
+
+    /// `groupId:artifactId`, with maven's default group for plugins without one
+    pub fn key(&self) -> String {
+        format!(
+            "{}:{}",
+            self.group_id
+                .as_deref()
+                .unwrap_or("org.apache.maven.plugins"),
+            self.artifact_id
+        )
+    }
+}
+
+/// Plugins bundling dependencies into the built artifact (fat jars)
+pub const BUNDLING_PLUGINS: [&str; 3] = [
+    "maven-shade-plugin",
+    "maven-assembly-plugin",
+    "spring-boot-maven-plugin",
+];
+
+/// Parent of Spring Boot applications, running the `repackage` goal of `spring-boot-maven-plugin`
+const SPRING_BOOT_PARENT: &str = "org.springframework.boot:spring-boot-starter-parent";
+
 impl Pom {
+    /// Parses a pom, tolerating namespaces, CDATA and unknown elements. Lists that are repeated
+    /// (like two `<repositories>`) are merged, of other repeated elements the first is used.
+    /// Items missing what identifies them (a repository without url, a plugin without
+    /// artifactId) are skipped.
+    pub fn parse(bytes: &[u8]) -> Result<Pom, xml::Error> {
+        let root = Element::parse(bytes)?;
+        // The effective pom of a multi-module project has all modules in `<projects>`, starting
+        // with the project itself
+        let project = match root.name.as_str() {
+            "projects" => root.child("project").ok_or(xml::Error::NoRoot)?,
+            _ => &root,
+        };
+        Ok(Pom::of(project))
+    }
+
+    pub fn read(path: &Path) -> Result<Pom, xml::Error> {
+        Pom::parse(&fs::read(path)?)
+    }
+
+    fn of(project: &Element) -> Pom {
+        let project = [project];
+        let build = all(&project, "build");
+        let plugin_management = all(&build, "pluginManagement");
+        let dependency_management = all(&project, "dependencyManagement");
+        let properties = all(&project, "properties");
+        let repositories = |lists: &[&Element]| {
+            items(lists, "repository", Repository::of)
+                .map(|repositories| Repositories { repositories })
+        };
+        let plugins = |lists: &[&Element]| {
+            items(lists, "plugin", Plugin::of).map(|plugins| Plugins { plugins })
+        };
+        let dependencies = |lists: &[&Element]| {
+            items(lists, "dependency", Dependency::of)
+                .map(|dependencies| Dependencies { dependencies })
+        };
+
+        Pom {
+            repositories: repositories(&all(&project, "repositories")),
+            distribution_management: repositories(&all(&project, "distributionManagement")),
+            build: (!build.is_empty()).then(|| Build {
+                plugins: plugins(&all(&build, "plugins")),
+                plugin_management: (!plugin_management.is_empty()).then(|| PluginManagement {
+                    plugins: plugins(&all(&plugin_management, "plugins")),
+                }),
+            }),
+            dependencies: dependencies(&all(&project, "dependencies")),
+            dependency_management: (!dependency_management.is_empty()).then(|| {
+                DependencyManagement {
+                    dependencies: dependencies(&all(&dependency_management, "dependencies")),
+                }
+            }),
+            properties: (!properties.is_empty()).then(|| Properties {
+                release: value(&properties, "maven.compiler.release"),
+                target: value(&properties, "maven.compiler.target"),
+                source: value(&properties, "maven.compiler.source"),
+                java_version: value(&properties, "java.version"),
+            }),
+            modules: all(&all(&project, "modules"), "module")
+                .into_iter()
+                .filter(|module| !module.text.is_empty())
+                .map(|module| module.text.clone())
+                .collect(),
+            parent: project[0].child("parent").and_then(|parent| {
+                Some(format!(
+                    "{}:{}",
+                    value(&[parent], "groupId")?,
+                    value(&[parent], "artifactId")?
+                ))
+            }),
+        }
+    }
+
     pub fn repositories(&self) -> Option<Vec<&str>> {
         self.repositories.as_ref().map(|repos| {
             repos
@@ -48,6 +326,36 @@ impl Pom {
         })
     }
 
+    pub fn plugins(&self) -> impl Iterator<Item = &Plugin> {
+        self.build
+            .iter()
+            .flat_map(|build| build.plugins.iter())
+            .flat_map(|plugins| plugins.plugins.iter())
+    }
+
+    /// The plugins in `<pluginManagement>`
+    pub fn managed_plugins(&self) -> impl Iterator<Item = &Plugin> {
+        self.build
+            .iter()
+            .flat_map(|build| build.plugin_management.iter())
+            .flat_map(|management| management.plugins.iter())
+            .flat_map(|plugins| plugins.plugins.iter())
+    }
+
+    pub fn dependencies(&self) -> impl Iterator<Item = &Dependency> {
+        self.dependencies
+            .iter()
+            .flat_map(|dependencies| dependencies.dependencies.iter())
+    }
+
+    /// The dependencies in `<dependencyManagement>`
+    pub fn managed_dependencies(&self) -> impl Iterator<Item = &Dependency> {
+        self.dependency_management
+            .iter()
+            .flat_map(|management| management.dependencies.iter())
+            .flat_map(|dependencies| dependencies.dependencies.iter())
+    }
+
     pub fn distribution_repositories(&self) -> Option<Vec<&str>> {
         self.distribution_management.as_ref().map(|repos| {
             repos
@@ -66,9 +374,66 @@ pub enum Error {
 
     #[error("IO Error: {0:?}")]
     IO(#[from] io::Error),
+
+    #[error("invalid chunk {0:?}, expected <index>/<total>")]
+    InvalidChunk(String),
+
+    #[error("HTTP Error: {0:?}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("Arrow Error: {0:?}")]
+    Arrow(#[from] arrow::error::ArrowError),
+}
+
+/// A deterministic shard of the project directories, written as `index/total` (e.g. `0/8`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    pub index: usize,
+    pub total: usize,
+}
+
+impl Chunk {
+    /// The chunks `0/total` to `total-1/total`
+    pub fn all(total: usize) -> Vec<Chunk> {
+        (0..total).map(|index| Chunk { index, total }).collect()
+    }
+
+    /// Keeps only the projects belonging to this chunk.
+    /// Projects are sorted first so every machine computes the same shards for the same data dir.
+    pub fn select<T: Ord>(&self, mut projects: Vec<T>) -> Vec<T> {
+        projects.sort_unstable();
+        projects
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, p)| (i % self.total == self.index).then_some(p))
+            .collect()
+    }
+}
+
+impl FromStr for Chunk {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || Error::InvalidChunk(s.to_string());
+        let (index, total) = s.split_once('/').ok_or_else(invalid)?;
+        let index: usize = index.trim().parse().map_err(|_| invalid())?;
+        let total: usize = total.trim().parse().map_err(|_| invalid())?;
+
+        if total == 0 || index >= total {
+            return Err(invalid());
+        }
+
+        Ok(Chunk { index, total })
+    }
 }
 
-fn biggest_n(map: DashMap<String, usize>, n: usize) -> Vec<(String, usize)> {
+impl Display for Chunk {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.total)
+    }
+}
+
+pub fn biggest_n(map: DashMap<String, usize>, n: usize) -> Vec<(String, usize)> {
     let mut top: Vec<(String, usize)> = map.into_iter().collect();
     top.sort_by(|(_, a), (_, b)| a.cmp(b).reverse());
     top.truncate(n);
@@ -76,7 +441,7 @@ fn biggest_n(map: DashMap<String, usize>, n: usize) -> Vec<(String, usize)> {
     top
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Report {
     pub distros: DashMap<String, usize>,
     pub external_repos: DashMap<String, usize>,
@@ -84,6 +449,63 @@ pub struct Report {
     pub has_distro_repos: Vec<String>,
     pub errors: Vec<String>,
     pub total: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bootstrap: Option<Bootstrap>,
+    /// Like `external_repos` but counting each declaring pom, without modules inheriting it
+    #[serde(default)]
+    pub external_repo_declarations: DashMap<String, usize>,
+    /// Like `distros` but counting each declaring pom, without modules inheriting it
+    #[serde(default)]
+    pub distro_declarations: DashMap<String, usize>,
+    #[serde(default)]
+    pub poms_per_repo: PomHistogram,
+    /// Amount of projects using each of the [BUNDLING_PLUGINS]
+    #[serde(default)]
+    pub bundling_plugins: DashMap<String, usize>,
+    #[serde(default)]
+    pub has_toolchains: usize,
+    #[serde(default)]
+    pub has_core_extensions: usize,
+    /// Amount of projects using each core extension
+    #[serde(default)]
+    pub core_extensions: DashMap<String, usize>,
+    #[serde(default)]
+    pub automation: Automation,
+    #[serde(default)]
+    pub distribution_channels: DistributionChannels,
+    #[serde(default)]
+    pub github_packages: GithubPackagesUsage,
+    #[serde(default)]
+    pub dependencies: DependencyUsage,
+    /// Projects with Gradle build scripts, whose repositories are counted like those of poms
+    #[serde(default)]
+    pub gradle: usize,
+    #[serde(default)]
+    pub workflows: WorkflowUsage,
+    #[serde(default)]
+    pub parents: ParentResolution,
+    #[serde(default)]
+    pub java_versions: JavaVersions,
+    /// Analyzed projects with the same poms as another project, see [dedup]
+    #[serde(default)]
+    pub duplicates: usize,
+    /// Duplicates skipped with `--exclude-duplicates`, not included in `total`
+    #[serde(default)]
+    pub excluded_duplicates: usize,
+    /// Amount of projects with external repositories on each host
+    #[serde(default)]
+    pub external_hosts: DashMap<String, usize>,
+    /// Amount of projects with distribution repositories on each host
+    #[serde(default)]
+    pub distro_hosts: DashMap<String, usize>,
+}
+
+/// The distinct hosts of a set of repository urls
+fn hosts(repos: &HashSet<String>) -> HashSet<String> {
+    repos
+        .iter()
+        .filter_map(|repo| Some(Url::parse(repo).ok()?.host_str()?.to_string()))
+        .collect()
 }
 
 pub fn distinct_repos_per_hostname(map: DashMap<String, usize>) {
@@ -112,6 +534,119 @@ pub fn distinct_repos_per_hostname(map: DashMap<String, usize>) {
 }
 
 impl Report {
+    /// Aggregates a report from already analyzed projects
+    pub fn from_projects(projects: &[Project], errors: Vec<String>) -> Self {
+        let mut report = Report {
+            errors,
+            ..Default::default()
+        };
+        for proj in projects {
+            report.add(proj);
+        }
+        report
+    }
+
+    /// Counts an analyzed project into the report
+    pub fn add(&mut self, proj: &Project) {
+        self.total += 1;
+        self.workflows.add(proj);
+        self.parents.add(proj);
+        self.java_versions.add(proj);
+        self.duplicates += usize::from(proj.duplicate_of.is_some());
+        self.dependencies.add(proj);
+        self.gradle += usize::from(proj.gradle_scripts > 0);
+        self.github_packages.add(proj);
+        self.automation.add(proj);
+        self.distribution_channels.add(proj);
+        self.has_toolchains += usize::from(proj.has_toolchains);
+        self.has_core_extensions += usize::from(!proj.core_extensions.is_empty());
+        for extension in proj.core_extensions.iter() {
+            *self.core_extensions.entry(extension.clone()).or_insert(0) += 1;
+        }
+        self.poms_per_repo.add(&proj.name, proj.poms);
+        for plugin in proj.bundling_plugins.iter() {
+            *self.bundling_plugins.entry(plugin.clone()).or_insert(0) += 1;
+        }
+        if !proj.repos.is_empty() {
+            self.has_external_repos += 1;
+        }
+        if !proj.dist_repos.is_empty() {
+            self.has_distro_repos.push(proj.name.clone());
+        }
+        for repo in proj.repos.iter() {
+            *self.external_repos.entry(repo.clone()).or_insert(0) += 1;
+        }
+        for repo in proj.dist_repos.iter() {
+            *self.distros.entry(repo.clone()).or_insert(0) += 1;
+        }
+        for host in hosts(&proj.repos) {
+            *self.external_hosts.entry(host).or_insert(0) += 1;
+        }
+        for host in hosts(&proj.dist_repos) {
+            *self.distro_hosts.entry(host).or_insert(0) += 1;
+        }
+        for (repo, count) in proj.repo_declarations.iter() {
+            *self
+                .external_repo_declarations
+                .entry(repo.clone())
+                .or_insert(0) += count;
+        }
+        for (repo, count) in proj.dist_declarations.iter() {
+            *self.distro_declarations.entry(repo.clone()).or_insert(0) += count;
+        }
+    }
+
+    /// Combines another (partial) report into this one, summing the counts
+    pub fn merge(&mut self, other: Report) {
+        for (repo, count) in other.distros {
+            *self.distros.entry(repo).or_insert(0) += count;
+        }
+        for (repo, count) in other.external_repos {
+            *self.external_repos.entry(repo).or_insert(0) += count;
+        }
+        self.has_external_repos += other.has_external_repos;
+        self.has_distro_repos.extend(other.has_distro_repos);
+        // Reports from overlapping data dirs can contain the same repo twice
+        self.has_distro_repos.sort_unstable();
+        self.has_distro_repos.dedup();
+        for (repo, count) in other.external_repo_declarations {
+            *self.external_repo_declarations.entry(repo).or_insert(0) += count;
+        }
+        for (repo, count) in other.distro_declarations {
+            *self.distro_declarations.entry(repo).or_insert(0) += count;
+        }
+        for (host, count) in other.external_hosts {
+            *self.external_hosts.entry(host).or_insert(0) += count;
+        }
+        for (host, count) in other.distro_hosts {
+            *self.distro_hosts.entry(host).or_insert(0) += count;
+        }
+        self.poms_per_repo.merge(other.poms_per_repo);
+        for (plugin, count) in other.bundling_plugins {
+            *self.bundling_plugins.entry(plugin).or_insert(0) += count;
+        }
+        self.has_toolchains += other.has_toolchains;
+        self.has_core_extensions += other.has_core_extensions;
+        for (extension, count) in other.core_extensions {
+            *self.core_extensions.entry(extension).or_insert(0) += count;
+        }
+        self.automation.merge(other.automation);
+        self.distribution_channels
+            .merge(other.distribution_channels);
+        self.github_packages.merge(other.github_packages);
+        self.dependencies.merge(other.dependencies);
+        self.gradle += other.gradle;
+        self.workflows.merge(other.workflows);
+        self.parents.merge(other.parents);
+        self.java_versions.merge(other.java_versions);
+        self.duplicates += other.duplicates;
+        self.excluded_duplicates += other.excluded_duplicates;
+        self.errors.extend(other.errors);
+        self.total += other.total;
+        // Confidence intervals can't be combined, they have to be recomputed from the projects
+        self.bootstrap = None;
+    }
+
     pub fn print(&self) {
         println!("Found a total of {} repos", self.total);
         println!(
@@ -133,19 +668,67 @@ impl Report {
             "Found {distros_len} distinct distribution repositories, top 25: {top_distros:#?}"
         );
 
+        if !self.external_repo_declarations.is_empty() || !self.distro_declarations.is_empty() {
+            let top_repos = biggest_n(self.external_repo_declarations.clone(), 25);
+            let top_distros = biggest_n(self.distro_declarations.clone(), 25);
+            println!("Counted per declaring pom (excluding inherited), top 25 external repositories: {top_repos:#?}");
+            println!("Counted per declaring pom (excluding inherited), top 25 distribution repositories: {top_distros:#?}");
+        }
+
+        self.poms_per_repo.print();
+        if self.gradle > 0 {
+            println!("Amount of repos with Gradle build scripts: {}", self.gradle);
+        }
+
+        if !self.bundling_plugins.is_empty() {
+            let plugins = biggest_n(self.bundling_plugins.clone(), BUNDLING_PLUGINS.len());
+            println!("Projects bundling dependencies per plugin: {plugins:#?}");
+        }
+
+        println!(
+            "Amount of repos with a toolchains.xml: {}",
+            self.has_toolchains
+        );
+        println!(
+            "Amount of repos with core extensions: {}",
+            self.has_core_extensions
+        );
+        if !self.core_extensions.is_empty() {
+            let top_extensions = biggest_n(self.core_extensions.clone(), 25);
+            println!("Top 25 core extensions: {top_extensions:#?}");
+        }
+
+        self.automation.print();
+        self.distribution_channels.print();
+        self.github_packages.print();
+        self.workflows.print();
+        self.dependencies.print();
+        self.java_versions.print();
+        self.parents.print();
+        if self.duplicates > 0 || self.excluded_duplicates > 0 {
+            println!(
+                "Duplicate projects (same poms as another project): {} analyzed, {} skipped",
+                self.duplicates, self.excluded_duplicates
+            );
+        }
+
+        if let Some(bootstrap) = &self.bootstrap {
+            bootstrap.print();
+        }
+
         println!("{} errors occurred", self.errors.len())
 
         // fs::write("./analyzer_error_log", format!("{:#?}", self.errors)).unwrap();
     }
 }
 
-pub fn most_popular_hostnames(data: Data) -> Result<(), Error> {
-    let report = data.read_report()?;
-    let distro_hostnames = DashMap::new();
-    report.distros.par_iter().for_each(|entry| {
+/// Sums up the counts of repository urls per hostname
+pub fn hostname_counts(repos: &DashMap<String, usize>) -> DashMap<String, usize> {
+    let hostnames = DashMap::new();
+    repos.par_iter().for_each(|entry| {
         if let Ok(url) = Url::parse(entry.key()) {
             if let Some(host) = url.host_str() {
-                distro_hostnames
+                hostnames
                     .entry(host.to_string())
                     .and_modify(|el| *el += entry.value())
                     .or_insert(*entry.value());
@@ -153,17 +736,13 @@ pub fn most_popular_hostnames(data: Data) -> Result<(), Error> {
         }
     });
 
-    let external_repo_hostnames = DashMap::new();
-    report.external_repos.par_iter().for_each(|entry| {
-        if let Ok(url) = Url::parse(entry.key()) {
-            if let Some(host) = url.host_str() {
-                external_repo_hostnames
-                    .entry(host.to_string())
-                    .and_modify(|el| *el += entry.value())
-                    .or_insert(*entry.value());
-            }
-        }
-    });
+    hostnames
+}
+
+pub fn most_popular_hostnames(data: Data) -> Result<(), Error> {
+    let report = data.read_report()?;
+    let distro_hostnames = hostname_counts(&report.distros);
+    let external_repo_hostnames = hostname_counts(&report.external_repos);
 
     let gh_distor = *distro_hostnames
         .get("maven.pkg.github.com")
@@ -188,127 +767,503 @@ pub fn most_popular_hostnames(data: Data) -> Result<(), Error> {
     Ok(())
 }
 
-pub async fn analyze(data: Data, build_effective: bool) -> Result<Report, Error> {
-    let projects = data.get_project_dirs().await?;
+/// Settings of a single analyzer run
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Create effective poms using maven
+    pub build_effective: bool,
+    /// Only analyze a shard of the projects
+    pub chunk: Option<Chunk>,
+    /// Amount of bootstrap resamples for confidence intervals
+    pub bootstrap_iterations: Option<usize>,
+    /// Analyze at most this many repositories per owner
+    pub max_per_owner: Option<usize>,
+    pub output_format: OutputFormat,
+    /// Maximum amount of concurrently running maven processes
+    pub mvn_jobs: Option<usize>,
+    /// Scan poms with [fast::scan] before falling back to the full parser
+    pub fast_path: bool,
+    /// Reuse the results of projects whose files didn't change since the last incremental run
+    pub incremental: bool,
+    /// Skip the duplicates in the dedup report, see [dedup]
+    pub exclude_duplicates: bool,
+    pub maven: Maven,
+}
+
+/// Caps how many maven processes run at the same time, independent of the rayon pool size
+struct MavenLimiter {
+    max: Option<usize>,
+    running: Mutex<usize>,
+    released: Condvar,
+}
+
+struct MavenPermit<'a>(&'a MavenLimiter);
+
+impl MavenLimiter {
+    fn new(max: Option<usize>) -> Self {
+        Self {
+            max,
+            running: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Blocks until a maven process may be started
+    fn acquire(&self) -> MavenPermit<'_> {
+        let mut running = self.running.lock().unwrap();
+        if let Some(max) = self.max {
+            while *running >= max {
+                running = self.released.wait(running).unwrap();
+            }
+        }
+        *running += 1;
+        MavenPermit(self)
+    }
+}
+
+impl Drop for MavenPermit<'_> {
+    fn drop(&mut self) {
+        *self.0.running.lock().unwrap() -= 1;
+        self.0.released.notify_one();
+    }
+}
+
+pub async fn analyze(data: Data, options: Options) -> Result<Report, Error> {
+    let Options {
+        build_effective,
+        chunk,
+        bootstrap_iterations,
+        max_per_owner,
+        output_format,
+        mvn_jobs,
+        fast_path,
+        incremental,
+        exclude_duplicates,
+        maven,
+    } = options;
+    let limiter = MavenLimiter::new(mvn_jobs);
+    let (releases, packages, parent_status, dedup_report) = {
+        let data = data.clone();
+        tokio::task::spawn_blocking(move || {
+            Ok::<_, data::Error>((
+                data.read_releases()?,
+                data.read_packages()?,
+                data.read_parent_status()?,
+                data.read_dedup_report()?,
+            ))
+        })
+        .await
+        .unwrap()?
+    };
+
+    let mut projects = data.get_project_dirs().await?;
+    if let Some(max) = max_per_owner {
+        let before = projects.len();
+        projects.sort_unstable();
+        projects.shuffle(&mut ChaCha20Rng::from_seed(SEED));
+        projects = cap_per_owner(projects, max, |dir| {
+            // Project directories are named like projects, see [Project::owner]
+            dir.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.split('.').next())
+                .unwrap_or_default()
+        });
+        info!(
+            "Capped to {max} repos per owner, skipping {} projects",
+            before - projects.len()
+        );
+    }
+    if let Some(chunk) = chunk {
+        projects = chunk.select(projects);
+        info!("Analyzing chunk {chunk} ({} projects)", projects.len());
+    }
+    let mut excluded_duplicates = 0;
+    if exclude_duplicates {
+        match &dedup_report {
+            Some(report) => {
+                excluded_duplicates = dedup::exclude(&mut projects, report);
+                info!("Skipping {excluded_duplicates} duplicate projects");
+            }
+            None => warn!(
+                "No dedup report to exclude duplicates with, run dedup first (with the same --forge)"
+            ),
+        }
+    }
+    let duplicate_of: HashMap<String, String> = dedup_report
+        .iter()
+        .flat_map(|report| report.duplicates())
+        .map(|(project, kept)| (project.to_string(), kept.to_string()))
+        .collect();
     let (send, recv) = tokio::sync::oneshot::channel();
 
     rayon::spawn(move || {
-        let distros: DashMap<String, usize> = DashMap::new();
-        let repos: DashMap<String, usize> = DashMap::new();
-        let has_external_repo = AtomicUsize::new(0);
-        let has_distro_repo = Mutex::new(Vec::new());
-        let total = AtomicUsize::new(0);
-        let errors = Mutex::new(Vec::new());
-
-        let res: Vec<_> = projects
-            .par_iter()
-            .filter_map(|dir| match process_folder(dir, build_effective) {
-                Ok(project) => Some(project),
-                Err(error) => {
-                    errors.lock().unwrap().push(format!("{error:?}"));
-                    None
-                }
-            })
-            .map(|mut proj| {
-                // Remove repo maven from external repos
-                proj.repos.remove("https://repo.maven.apache.org/maven2");
+        let analyzed = || -> Result<Report, Error> {
+            let settings = incremental::Settings::new(build_effective, fast_path, &maven);
+            let cache_path = data.output_path("analysis-cache", "json", chunk);
+            let cache =
+                incremental.then(|| incremental::Cache::load(&cache_path, settings.clone()));
+            let new_cache = Mutex::new(incremental::Cache::new(settings));
+            let reused = AtomicUsize::new(0);
+            let fingerprint = |dir: &Path| {
+                // Effective poms also depend on the parents fetched for the project
+                let parents = build_effective
+                    .then(|| parent_status.get(dir.file_name()?.to_str()?))
+                    .flatten();
+                incremental::fingerprint(dir, parents)
+                    .map_err(|e| warn!("Failed fingerprinting {dir:?}: {e}"))
+                    .ok()
+            };
 
-                if !proj.repos.is_empty() {
-                    has_external_repo.fetch_add(1, Ordering::SeqCst);
-                }
+            // Projects are written and counted as they are analyzed, only bootstrapping and Arrow
+            // tables need all of them at once. projects.json is written for Arrow too, the other
+            // commands read the projects from it.
+            let report = Mutex::new(Report {
+                excluded_duplicates,
+                ..Default::default()
+            });
+            let writer = Mutex::new(data.project_writer(chunk)?);
+            let keep =
+                bootstrap_iterations.is_some() || matches!(output_format, OutputFormat::Arrow);
+            let kept = Mutex::new(Vec::new());
 
-                if !proj.dist_repos.is_empty() {
-                    has_distro_repo.lock().unwrap().push(proj.name.clone());
-                }
+            projects
+                .par_iter()
+                .filter_map(|dir| {
+                    if let Some(cache) = &cache {
+                        let name = dir.file_name().unwrap().to_string_lossy();
+                        let cached = fingerprint(dir)
+                            .and_then(|fp| Some((cache.get(&name, &fp)?.clone(), fp)));
+                        if let Some((project, fp)) = cached {
+                            reused.fetch_add(1, Ordering::SeqCst);
+                            new_cache.lock().unwrap().insert(project.clone(), fp);
+                            return Some(project);
+                        }
+                    }
 
-                for repo in proj.repos.iter() {
-                    repos
-                        .entry(repo.clone())
-                        .and_modify(|el| *el += 1)
-                        .or_insert(1);
-                }
+                    match process_folder(&data, dir, build_effective, fast_path, &maven, &limiter) {
+                        Ok(project) => {
+                            // Taken after the analysis, which can add effective poms
+                            if let Some(fp) = cache.as_ref().and_then(|_| fingerprint(dir)) {
+                                new_cache.lock().unwrap().insert(project.clone(), fp);
+                            }
+                            Some(project)
+                        }
+                        Err(error) => {
+                            report.lock().unwrap().errors.push(format!("{error:?}"));
+                            None
+                        }
+                    }
+                })
+                .for_each(|mut proj| {
+                    let release = releases.get(&proj.name);
+                    proj.has_releases = release.map(|status| status.has_releases);
+                    proj.release_artifacts = release.map_or(0, |status| status.artifacts.len());
+                    proj.github_packages =
+                        GithubPackages::verdict(&proj, packages.get(proj.owner()));
+                    proj.parents = parent_status.get(&proj.name).cloned();
+                    proj.duplicate_of = duplicate_of.get(&proj.name).cloned();
 
-                for repo in proj.dist_repos.iter() {
-                    distros
-                        .entry(repo.clone())
-                        .and_modify(|el| *el += 1)
-                        .or_insert(1);
-                }
+                    // Remove repo maven from external repos
+                    proj.repos.remove(urls::CENTRAL);
+                    proj.repo_declarations.remove(urls::CENTRAL);
 
-                let total = total.fetch_add(1, Ordering::SeqCst) + 1;
-                if total > 0 && total % 1024 == 0 {
-                    info!("Progress: {total}, writing report");
-                    if let Err(err) = data.write_report(Report {
-                        distros: distros.clone(),
-                        external_repos: repos.clone(),
-                        has_external_repos: has_external_repo.load(Ordering::SeqCst),
-                        has_distro_repos: has_distro_repo.lock().unwrap().clone(),
-                        errors: errors.lock().unwrap().clone(),
-                        total,
-                    }) {
-                        error!("Error writing report occurred {err}")
+                    if let Err(err) = writer.lock().unwrap().write(&proj) {
+                        error!("Error writing project {} occurred {err}", proj.name)
                     }
-                }
 
-                proj
-            })
-            .collect();
-
-        let report = Report {
-            distros,
-            external_repos: repos,
-            has_external_repos: has_external_repo.load(Ordering::SeqCst),
-            has_distro_repos: has_distro_repo.lock().unwrap().clone(),
-            errors: errors.lock().unwrap().clone(),
-            total: total.load(Ordering::SeqCst),
-        };
+                    let mut counted = report.lock().unwrap();
+                    counted.add(&proj);
+                    let total = counted.total;
+                    let progress = total.is_multiple_of(1024).then(|| counted.clone());
+                    drop(counted);
+                    if let Some(progress) = progress {
+                        info!("Progress: {total}, writing report");
+                        if let Err(err) = data.write_report(progress, chunk) {
+                            error!("Error writing report occurred {err}")
+                        }
+                    }
+
+                    if keep {
+                        kept.lock().unwrap().push(proj);
+                    }
+                });
 
-        data.write_report(report.clone()).unwrap();
+            let mut report = report.into_inner().unwrap();
+            let mut res = kept.into_inner().unwrap();
+            // Projects finish in any order, resamples have to be drawn from the same one every run
+            res.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+            report.bootstrap = bootstrap_iterations.and_then(|iterations| {
+                let top: Vec<String> = biggest_n(report.external_repos.clone(), 25)
+                    .into_iter()
+                    .map(|(repo, _)| repo)
+                    .collect();
+                info!("Bootstrapping {iterations} iterations");
+                bootstrap::bootstrap(&res, &top, iterations)
+            });
+
+            data.write_report(report.clone(), chunk)?;
+            if incremental {
+                info!(
+                    "Reused {} of {} projects from the analysis cache",
+                    reused.load(Ordering::SeqCst),
+                    report.total
+                );
+                if let Err(err) = new_cache.into_inner().unwrap().save(&cache_path) {
+                    error!("Error writing analysis cache occurred {err}")
+                }
+            }
 
-        data.write_projects(&res).unwrap();
+            writer.into_inner().unwrap().finish()?;
+            if let OutputFormat::Arrow = output_format {
+                tables::write_arrow(&data, &report, &res, chunk)?;
+            }
 
-        send.send(report).unwrap();
+            Ok(report)
+        };
+        // The receiver is gone if the analysis was cancelled
+        let _ = send.send(analyzed());
     });
 
-    let data = recv.await.unwrap();
+    recv.await.unwrap()
+}
 
-    Ok(data)
+/// Analyzes the projects in `shards` chunks one after another and merges their partial reports
+/// into `report.json`, so only the projects of one chunk are held for bootstrapping, Arrow tables
+/// and the analysis cache. The projects of the chunks are then merged into `projects.json`, chunk
+/// after chunk, while Arrow tables stay in the files of the chunks.
+pub async fn analyze_shards(data: Data, options: Options, shards: usize) -> Result<Report, Error> {
+    let mut merged = Report::default();
+    let chunks = Chunk::all(shards);
+    for &chunk in &chunks {
+        let options = Options {
+            chunk: Some(chunk),
+            ..options.clone()
+        };
+        merged.merge(analyze(data.clone(), options).await?);
+    }
+
+    let report = merged.clone();
+    tokio::task::spawn_blocking(move || {
+        data.write_report(report, None)?;
+        data.merge_projects(&chunks)
+    })
+    .await
+    .unwrap()?;
+    Ok(merged)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Project {
     pub name: String,
+    /// Normalized by [urls::normalize], after resolving `${...}` references against the pom
     pub repos: HashSet<String>,
     pub dist_repos: HashSet<String>,
+    /// `repos` as written in the build files
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub raw_repos: BTreeSet<String>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub raw_dist_repos: BTreeSet<String>,
+    /// Per url, the amount of poms declaring it that don't inherit it from their parent module
+    #[serde(default)]
+    pub repo_declarations: HashMap<String, usize>,
+    #[serde(default)]
+    pub dist_declarations: HashMap<String, usize>,
+    /// Amount of pom files in the project
+    #[serde(default)]
+    pub poms: usize,
+    /// The [BUNDLING_PLUGINS] used by any of the poms
+    #[serde(default)]
+    pub bundling_plugins: HashSet<String>,
+    /// Whether the project contains a toolchains.xml
+    #[serde(default)]
+    pub has_toolchains: bool,
+    /// `groupId:artifactId` of the extensions in .mvn/extensions.xml files
+    #[serde(default)]
+    pub core_extensions: HashSet<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dependabot: Option<Dependabot>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renovate: Option<Renovate>,
+    /// Amount of GitHub workflows in the project
+    #[serde(default)]
+    pub workflows: usize,
+    /// Whether a GitHub workflow deploys or publishes artifacts
+    #[serde(default)]
+    pub has_publish_workflow: bool,
+    /// The kinds of publish steps in any of the workflows
+    #[serde(default)]
+    pub publish_steps: BTreeSet<PublishStep>,
+    /// Server ids configured with `actions/setup-java` in any of the workflows
+    #[serde(default)]
+    pub workflow_server_ids: BTreeSet<String>,
+    /// Whether a GitHub workflow configures GitHub Packages
+    #[serde(default)]
+    pub github_packages_workflow: bool,
+    #[serde(default)]
+    pub github_packages: GithubPackages,
+    /// Whether the repo publishes GitHub releases, `None` if they weren't fetched
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_releases: Option<bool>,
+    /// Amount of jar and pom assets of the GitHub releases
+    #[serde(default)]
+    pub release_artifacts: usize,
+    /// How the parents from outside the project were fetched, `None` if they weren't
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parents: Option<ParentStatus>,
+    /// The project with the same poms that is kept when excluding duplicates, see [dedup]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duplicate_of: Option<String>,
+    /// Dependencies declared by any of the poms
+    #[serde(default)]
+    pub dependencies: Artifacts,
+    /// Dependencies in the `<dependencyManagement>` of any of the poms
+    #[serde(default)]
+    pub managed_dependencies: Artifacts,
+    /// Build plugins used by any of the poms
+    #[serde(default)]
+    pub plugins: Artifacts,
+    /// Plugins in the `<pluginManagement>` of any of the poms
+    #[serde(default)]
+    pub managed_plugins: Artifacts,
+    /// Java versions the poms compile for, see [java::declared_version]
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub java_versions: BTreeSet<String>,
+    /// Per aggregator pom, the directories of the `<modules>` it lists. Both are relative to the
+    /// project, which itself is `.`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub modules: BTreeMap<String, Vec<String>>,
+    /// Amount of Gradle build and settings scripts in the project
+    #[serde(default)]
+    pub gradle_scripts: usize,
+    /// Errors that didn't stop the analysis, like invalid config files or failed effective poms
+    #[serde(default)]
+    pub errors: Vec<String>,
+}
+
+impl Project {
+    /// Project directories are named `owner.repo` by [crate::project_dir], with the GitLab
+    /// namespaces of owners percent-encoded
+    pub fn owner(&self) -> &str {
+        self.name.split('.').next().unwrap_or_default()
+    }
+}
+
+/// The `owner/repo` name of a project named `owner.repo`, see [crate::project_dir]
+pub fn repo_name(project: &str) -> String {
+    match project.split_once('.') {
+        Some((owner, repo)) => {
+            // Every `%` starts an escape, so they can be decoded one after another
+            let owner = owner
+                .replace("%2F", "/")
+                .replace("%2E", ".")
+                .replace("%25", "%");
+            format!("{owner}/{repo}")
+        }
+        None => project.to_string(),
+    }
 }
 
 const EFFECTIVE_FILE_NAME: &str = "effective.xml";
 
-fn process_folder(path: &Path, build_effective: bool) -> color_eyre::Result<Project> {
-    let iter = WalkDir::new(path)
-        .follow_links(true)
-        .into_iter()
-        .filter_map(|e| {
-            e.ok()
-                .and_then(|d| (d.file_name() == "pom.xml").then_some(d.into_path()))
-        });
+fn process_folder(
+    data: &Data,
+    path: &Path,
+    build_effective: bool,
+    fast_path: bool,
+    maven: &Maven,
+    limiter: &MavenLimiter,
+) -> color_eyre::Result<Project> {
+    let mut poms = Vec::new();
+    let mut gradle_scripts = Vec::new();
+    let mut has_toolchains = false;
+    let mut core_extensions = HashSet::new();
+    let mut dependabot = None;
+    let mut renovate = None;
+    let mut workflows = 0;
+    let mut publish_steps = BTreeSet::new();
+    let mut workflow_server_ids = BTreeSet::new();
+    let mut github_packages_workflow = false;
+    let mut errors = Vec::new();
+    for entry in WalkDir::new(path).follow_links(true).into_iter().flatten() {
+        let relative = entry.path().strip_prefix(path).unwrap_or(entry.path());
+        if automation::DEPENDABOT_FILES
+            .iter()
+            .any(|file| relative == Path::new(file))
+        {
+            match automation::read_dependabot(entry.path()) {
+                Ok(config) => dependabot = Some(config),
+                Err(e) => {
+                    warn!("Invalid dependabot config {:?}: {e}", entry.path());
+                    errors.push(format!("Invalid dependabot config {relative:?}: {e}"));
+                }
+            }
+        } else if automation::RENOVATE_FILES
+            .iter()
+            .any(|file| relative == Path::new(file))
+        {
+            renovate = Some(automation::read_renovate(entry.path()));
+        } else if workflow::is_workflow_file(relative) {
+            workflows += 1;
+            match workflow::read_workflow(entry.path()) {
+                Ok(workflow) => {
+                    github_packages_workflow |= workflow.github_packages;
+                    publish_steps.extend(workflow.steps);
+                    workflow_server_ids.extend(workflow.server_ids);
+                }
+                Err(e) => {
+                    warn!("Invalid workflow {:?}: {e}", entry.path());
+                    errors.push(format!("Invalid workflow {relative:?}: {e}"));
+                }
+            }
+        } else if entry.file_name() == "pom.xml" {
+            poms.push(entry.into_path());
+        } else if gradle::is_gradle_file(entry.path()) {
+            gradle_scripts.push(entry.into_path());
+        } else if entry.file_name() == "toolchains.xml" {
+            has_toolchains = true;
+        } else if build_files::is_extensions_file(entry.path()) {
+            match build_files::read_extensions(entry.path()) {
+                Ok(extensions) => core_extensions.extend(extensions),
+                Err(e) => {
+                    warn!("Invalid extensions file {:?}: {e}", entry.path());
+                    errors.push(format!("Invalid extensions file {relative:?}: {e}"));
+                }
+            }
+        }
+    }
 
-    let mut repos = HashSet::new();
-    let mut dist_repos = HashSet::new();
+    // Per directory, the repositories of the pom in it
+    let mut pom_repos = HashMap::new();
+    let mut pom_dist_repos = HashMap::new();
+    let mut raw_repos = BTreeSet::new();
+    let mut raw_dist_repos = BTreeSet::new();
+    let mut bundling_plugins = HashSet::new();
+    let mut dependencies = Artifacts::new();
+    let mut managed_dependencies = Artifacts::new();
+    let mut plugins = Artifacts::new();
+    let mut managed_plugins = Artifacts::new();
+    let mut java_versions = BTreeSet::new();
+    let mut modules = BTreeMap::new();
 
-    for mut pom in iter {
+    for mut pom in poms {
         let data = if build_effective {
             pom.set_file_name("effective.xml");
             if pom.exists() {
-                let f = File::open(pom)?;
-                serde_xml_rs::from_reader(f)?
+                read_pom(&pom, fast_path)?
+            } else if let Some(p) = maven.native_effective_pom(pom.parent().unwrap(), path) {
+                p
             } else {
-                match effective_pom(pom.parent().unwrap()) {
+                let permit = limiter.acquire();
+                let effective = maven.effective_pom(pom.parent().unwrap());
+                drop(permit);
+                match effective {
                     Ok(p) => p,
-                    Err(_) => {
+                    Err(e) => {
+                        record_maven_error(data, path, &e);
+                        let relative = pom.strip_prefix(path).unwrap_or(&pom);
+                        errors.push(format!("Effective pom of {relative:?} failed: {e}"));
                         pom.set_file_name("pom.xml");
-                        let f = File::open(pom)?;
-                        serde_xml_rs::from_reader(f)?
+                        read_pom(&pom, fast_path)?
                     }
                 }
             }
@@ -317,51 +1272,595 @@ fn process_folder(path: &Path, build_effective: bool) -> color_eyre::Result<Proj
             if !pom.exists() {
                 pom.set_file_name("pom.xml");
             }
-            let f = File::open(pom)?;
-            serde_xml_rs::from_reader(f)?
+            read_pom(&pom, fast_path)?
         };
 
-        if let Some(reps) = data.repositories() {
-            for repo in reps {
-                repos.insert(repo.to_string());
+        for plugin in data.plugins() {
+            if plugin.bundles(&data) {
+                bundling_plugins.insert(plugin.artifact_id.clone());
+            }
+            let versions = plugins.entry(plugin.key()).or_default();
+            versions.extend(plugin.version.clone());
+        }
+        for plugin in data.managed_plugins() {
+            let versions = managed_plugins.entry(plugin.key()).or_default();
+            versions.extend(plugin.version.clone());
+        }
+        for (declared, artifacts) in [
+            (data.dependencies().collect::<Vec<_>>(), &mut dependencies),
+            (
+                data.managed_dependencies().collect(),
+                &mut managed_dependencies,
+            ),
+        ] {
+            for dependency in declared {
+                if let Some(key) = dependency.key() {
+                    let versions = artifacts.entry(key).or_default();
+                    versions.extend(dependency.version.clone());
+                }
             }
         }
 
-        if let Some(repos) = data.distribution_repositories() {
-            for repo in repos {
-                dist_repos.insert(repo.to_string());
+        // Only poms with references in their urls or Java version, or with a compiler plugin
+        // configuration, are read again for their properties
+        let mut placeholders = None;
+        let java_version = if java::needs_placeholders(&data) {
+            java::declared_version(&data, read_placeholders(&mut placeholders, &pom))
+        } else {
+            java::declared_version(&data, None)
+        };
+        java_versions.extend(java_version);
+        let mut normalize = |url: &str| {
+            if !url.contains("${") {
+                return urls::normalize(url);
             }
+            match read_placeholders(&mut placeholders, &pom) {
+                Some(placeholders) => urls::normalize(&placeholders.resolve(url)),
+                None => urls::normalize(url),
+            }
+        };
+
+        let dir = pom.parent().unwrap().to_path_buf();
+        if !data.modules.is_empty() {
+            let relative = dir.strip_prefix(path).unwrap_or(&dir);
+            let listed = data
+                .modules
+                .iter()
+                .map(|module| module_dir(relative, module))
+                .collect();
+            modules.insert(project_relative(relative), listed);
         }
+        let raw = data.repositories().unwrap_or_default();
+        let repos: HashSet<String> = raw.iter().map(|&url| normalize(url)).collect();
+        raw_repos.extend(raw.into_iter().map(str::to_string));
+        let raw = data.distribution_repositories().unwrap_or_default();
+        let dist_repos: HashSet<String> = raw.iter().map(|&url| normalize(url)).collect();
+        raw_dist_repos.extend(raw.into_iter().map(str::to_string));
+        pom_repos.insert(dir.clone(), repos);
+        pom_dist_repos.insert(dir, dist_repos);
+    }
+
+    // Scripts are counted with the poms of their dir, a build and settings script in the same dir
+    // are one module
+    let poms_count = pom_repos.len();
+    for script in &gradle_scripts {
+        let parsed = gradle::read_script(script)?;
+        let dir = script.parent().unwrap().to_path_buf();
+        pom_repos
+            .entry(dir.clone())
+            .or_default()
+            .extend(parsed.repositories.iter().map(|url| urls::normalize(url)));
+        pom_dist_repos
+            .entry(dir)
+            .or_default()
+            .extend(parsed.publishing.iter().map(|url| urls::normalize(url)));
+        raw_repos.extend(parsed.repositories);
+        raw_dist_repos.extend(parsed.publishing);
     }
 
     let name = path.file_name().unwrap().to_string_lossy().to_string();
     Ok(Project {
         name,
-        repos,
-        dist_repos,
+        poms: poms_count,
+        gradle_scripts: gradle_scripts.len(),
+        bundling_plugins,
+        has_toolchains,
+        core_extensions,
+        dependabot,
+        renovate,
+        workflows,
+        has_publish_workflow: publish_steps.iter().any(|step| step.publishes()),
+        publish_steps,
+        workflow_server_ids,
+        github_packages_workflow,
+        github_packages: GithubPackages::NotConfigured,
+        has_releases: None,
+        parents: None,
+        duplicate_of: None,
+        release_artifacts: 0,
+        dependencies,
+        managed_dependencies,
+        plugins,
+        managed_plugins,
+        java_versions,
+        modules,
+        repos: pom_repos.values().flatten().cloned().collect(),
+        dist_repos: pom_dist_repos.values().flatten().cloned().collect(),
+        raw_repos,
+        raw_dist_repos,
+        repo_declarations: count_declarations(&pom_repos),
+        dist_declarations: count_declarations(&pom_dist_repos),
+        errors,
     })
 }
 
-fn effective_pom(path: &Path) -> color_eyre::Result<Pom> {
-    let cmd = Command::new("mvn")
-        .args([
+/// The properties of a pom, read the first time they are needed
+fn read_placeholders<'a>(
+    placeholders: &'a mut Option<Option<resolve::Placeholders>>,
+    pom: &Path,
+) -> Option<&'a resolve::Placeholders> {
+    placeholders
+        .get_or_insert_with(|| {
+            resolve::Placeholders::read(pom)
+                .map_err(|e| warn!("Failed reading properties of {pom:?}: {e}"))
+                .ok()
+        })
+        .as_ref()
+}
+
+/// A path relative to the project as written in [Project::modules]
+fn project_relative(path: &Path) -> String {
+    match path.to_string_lossy() {
+        relative if relative.is_empty() => ".".to_string(),
+        relative => relative.into_owned(),
+    }
+}
+
+/// The directory of a `<module>` of the pom in `dir`, modules can also name a pom file
+fn module_dir(dir: &Path, module: &str) -> String {
+    let mut module = resolve::normalize(&dir.join(module.replace('\\', "/")));
+    if module
+        .extension()
+        .is_some_and(|extension| extension == "xml")
+    {
+        module.pop();
+    }
+    project_relative(&module)
+}
+
+/// Counts the repositories of every pom that its parent module doesn't have as well,
+/// taking the closest pom in a directory above a pom as its parent
+fn count_declarations(poms: &HashMap<PathBuf, HashSet<String>>) -> HashMap<String, usize> {
+    let mut counts = HashMap::new();
+    for (dir, repos) in poms {
+        let parent = dir.ancestors().skip(1).find_map(|d| poms.get(d));
+        for repo in repos {
+            if !parent.is_some_and(|p| p.contains(repo)) {
+                *counts.entry(repo.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+    counts
+}
+
+fn record_maven_error(data: &Data, project: &Path, error: &MavenError) {
+    let record = ErrorRecord {
+        output: error.output().map(str::to_string),
+        ..ErrorRecord::new(
+            project.file_name().unwrap().to_string_lossy().to_string(),
+            "effective-pom",
+            error.to_string(),
+        )
+    };
+    if let Err(e) = data.record_error(&record) {
+        error!("Failed recording error for {project:?}: {e}");
+    }
+}
+
+/// Parses a pom file, with the [fast] scanner first if enabled
+fn read_pom(path: &Path, fast_path: bool) -> color_eyre::Result<Pom> {
+    if fast_path {
+        let bytes = fs::read(path)?;
+        if let Some(pom) = fast::scan(&bytes) {
+            return Ok(pom);
+        }
+        return Ok(Pom::parse(&bytes)?);
+    }
+
+    Ok(Pom::read(path)?)
+}
+
+// How maven is invoked to build effective poms
+#[derive(Debug, Clone, PartialEq, Eq, Args, Serialize, Deserialize)]
+pub struct Maven {
+    /// Maven executable, e.g. `mvnd`, a specific maven install or a wrapper running it in a container
+    #[arg(long = "mvn", env = "MVN", default_value = "mvn", global = true)]
+    pub executable: PathBuf,
+
+    /// Extra argument passed to maven before the goal, can be repeated (e.g. `--mvn-arg=-q`)
+    #[arg(
+        long = "mvn-arg",
+        value_name = "ARG",
+        allow_hyphen_values = true,
+        global = true
+    )]
+    pub args: Vec<String>,
+
+    /// Local repository maven resolves into, instead of ~/.m2 [default: <data>/m2]
+    #[arg(long = "mvn-local-repo", value_name = "PATH", global = true)]
+    pub local_repo: Option<PathBuf>,
+
+    /// Run maven offline, only resolving from the local repository
+    #[arg(long = "mvn-offline", global = true)]
+    pub offline: bool,
+
+    /// How effective poms are built
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub resolver: Resolver,
+}
+
+/// How effective poms are built
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Resolver {
+    /// In-process, falling back to maven for poms with a parent outside the project
+    #[default]
+    Native,
+    /// Always run maven
+    Maven,
+}
+
+impl Default for Maven {
+    fn default() -> Self {
+        Self {
+            executable: PathBuf::from("mvn"),
+            args: Vec::new(),
+            local_repo: None,
+            offline: false,
+            resolver: Resolver::Native,
+        }
+    }
+}
+
+/// Amount of output kept from a failed maven invocation
+const MAVEN_OUTPUT_TAIL: usize = 16 * 1024;
+
+#[derive(Debug, Error)]
+pub enum MavenError {
+    #[error("Failed running maven: {0}")]
+    Spawn(io::Error),
+
+    #[error("Maven failed with {status}")]
+    Failed { status: ExitStatus, output: String },
+
+    #[error("Maven timed out")]
+    TimedOut,
+
+    #[error("Invalid effective pom: {0}")]
+    Parse(#[from] xml::Error),
+
+    #[error("IO Error: {0:?}")]
+    IO(#[from] io::Error),
+}
+
+impl MavenError {
+    /// The end of maven's output, if it ran to completion
+    pub fn output(&self) -> Option<&str> {
+        match self {
+            MavenError::Failed { output, .. } => Some(output),
+            _ => None,
+        }
+    }
+
+    fn failed(output: Output) -> Self {
+        // Maven logs most errors to stdout
+        let mut combined = output.stdout;
+        combined.extend_from_slice(&output.stderr);
+        let tail = &combined[combined.len().saturating_sub(MAVEN_OUTPUT_TAIL)..];
+
+        MavenError::Failed {
+            status: output.status,
+            output: String::from_utf8_lossy(tail).into_owned(),
+        }
+    }
+}
+
+impl Maven {
+    /// The maven invocation writing the effective pom of the pom in `path` to [EFFECTIVE_FILE_NAME]
+    fn effective_pom_command(&self, path: &Path) -> Command {
+        let mut cmd = Command::new(&self.executable);
+        cmd.args(&self.args);
+        if let Some(repo) = &self.local_repo {
+            cmd.arg(format!("-Dmaven.repo.local={}", repo.display()));
+        }
+        if self.offline {
+            cmd.arg("--offline");
+        }
+        cmd.args([
             "-T1", // One thread as we don't want maven to interfere with our own multithreading
             "help:effective-pom",
             &format!("-Doutput={EFFECTIVE_FILE_NAME}"),
         ])
         .current_dir(path)
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .status()
-        .wrap_err("Failed running maven")?;
-
-    if cmd.success() {
-        let f = File::open(path.join(EFFECTIVE_FILE_NAME))?;
-        let pom = serde_xml_rs::from_reader(f)?;
-        info!("Created effective pom for {path:?}");
-
-        Ok(pom)
-    } else {
-        Err(eyre!("Maven command failed"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+        cmd
+    }
+
+    /// Builds the effective pom of the pom in `dir` in-process, if enabled and possible.
+    /// `None` if maven has to be used instead.
+    fn native_effective_pom(&self, dir: &Path, project: &Path) -> Option<Pom> {
+        if self.resolver != Resolver::Native {
+            return None;
+        }
+
+        match resolve::write_effective_pom(dir, project, self.local_repo.as_deref()) {
+            Ok(pom) => Some(pom),
+            Err(e) => {
+                debug!("Falling back to maven for {dir:?}: {e}");
+                None
+            }
+        }
+    }
+
+    fn effective_pom(&self, path: &Path) -> Result<Pom, MavenError> {
+        let output = self
+            .effective_pom_command(path)
+            .output()
+            .map_err(MavenError::Spawn)?;
+
+        if output.status.success() {
+            let pom = Pom::read(&path.join(EFFECTIVE_FILE_NAME))?;
+            info!("Created effective pom for {path:?}");
+
+            Ok(pom)
+        } else {
+            Err(MavenError::failed(output))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a pom with [Pom::parse], checking that [fast::scan] agrees when it handles the pom
+    fn parse(pom: &str) -> Pom {
+        let parsed = Pom::parse(pom.as_bytes()).unwrap();
+        if let Some(scanned) = fast::scan(pom.as_bytes()) {
+            assert_eq!(scanned, parsed);
+        }
+        parsed
+    }
+
+    fn repository(id: &str, url: &str) -> Repository {
+        Repository {
+            id: id.to_string(),
+            url: url.to_string(),
+        }
+    }
+
+    const PLAIN: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<project xmlns="http://maven.apache.org/POM/4.0.0">
+  <modelVersion>4.0.0</modelVersion>
+  <parent>
+    <groupId>org.springframework.boot</groupId>
+    <artifactId>spring-boot-starter-parent</artifactId>
+    <version>3.2.0</version>
+  </parent>
+  <properties>
+    <java.version>17</java.version>
+  </properties>
+  <repositories>
+    <repository>
+      <id>jitpack</id>
+      <url>https://jitpack.io</url>
+    </repository>
+  </repositories>
+  <build>
+    <plugins>
+      <plugin>
+        <groupId>org.springframework.boot</groupId>
+        <artifactId>spring-boot-maven-plugin</artifactId>
+      </plugin>
+    </plugins>
+  </build>
+</project>"#;
+
+    #[test]
+    fn parses_plain_poms() {
+        let pom = parse(PLAIN);
+        assert!(fast::scan(PLAIN.as_bytes()).is_some());
+        assert_eq!(
+            pom.parent.as_deref(),
+            Some("org.springframework.boot:spring-boot-starter-parent")
+        );
+        assert_eq!(pom.repositories(), Some(vec!["https://jitpack.io"]));
+        assert_eq!(pom.distribution_repositories(), None);
+        assert_eq!(pom.properties.unwrap().java_version.as_deref(), Some("17"));
+        assert_eq!(
+            pom.build.unwrap().plugins.unwrap().plugins,
+            vec![Plugin {
+                group_id: Some("org.springframework.boot".to_string()),
+                artifact_id: "spring-boot-maven-plugin".to_string(),
+                version: None,
+                goals: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn parses_namespaced_roots() {
+        let pom = parse(
+            r#"<pom:project xmlns:pom="http://maven.apache.org/POM/4.0.0">
+  <pom:distributionManagement>
+    <pom:repository>
+      <pom:id>releases</pom:id>
+      <pom:url>https://repo.example.com/releases</pom:url>
+    </pom:repository>
+    <pom:snapshotRepository>
+      <pom:id>snapshots</pom:id>
+      <pom:url>https://repo.example.com/snapshots</pom:url>
+    </pom:snapshotRepository>
+  </pom:distributionManagement>
+</pom:project>"#,
+        );
+        assert_eq!(
+            pom.distribution_management,
+            Some(Repositories {
+                repositories: vec![repository("releases", "https://repo.example.com/releases")]
+            })
+        );
+    }
+
+    #[test]
+    fn parses_cdata() {
+        let pom = parse(
+            r#"<project>
+  <repositories>
+    <repository>
+      <id><![CDATA[ central ]]></id>
+      <url><![CDATA[https://repo.example.com/maven2?a=1&b=2]]></url>
+    </repository>
+  </repositories>
+</project>"#,
+        );
+        assert_eq!(
+            pom.repositories.unwrap().repositories,
+            vec![repository(
+                "central",
+                "https://repo.example.com/maven2?a=1&b=2"
+            )]
+        );
+    }
+
+    #[test]
+    fn merges_repeated_repositories() {
+        let pom = parse(
+            r#"<project>
+  <repositories>
+    <repository><id>a</id><url>https://a.example.com</url></repository>
+  </repositories>
+  <properties>
+    <maven.compiler.release>21</maven.compiler.release>
+  </properties>
+  <repositories>
+    <repository><id>b</id><url>https://b.example.com</url></repository>
+    <repository><id>no-url</id></repository>
+  </repositories>
+  <properties>
+    <maven.compiler.release>11</maven.compiler.release>
+  </properties>
+</project>"#,
+        );
+        assert_eq!(
+            pom.repositories(),
+            Some(vec!["https://a.example.com", "https://b.example.com"])
+        );
+        // Of other repeated elements the first is used
+        assert_eq!(pom.properties.unwrap().release.as_deref(), Some("21"));
+    }
+
+    #[test]
+    fn parses_the_first_project_of_effective_poms() {
+        let pom = parse(
+            r#"<projects>
+  <project>
+    <artifactId>parent</artifactId>
+    <modules>
+      <module>core</module>
+    </modules>
+    <repositories>
+      <repository><id>parent</id><url>https://parent.example.com</url></repository>
+    </repositories>
+  </project>
+  <project>
+    <artifactId>core</artifactId>
+    <repositories>
+      <repository><id>core</id><url>https://core.example.com</url></repository>
+    </repositories>
+  </project>
+</projects>"#,
+        );
+        assert_eq!(pom.repositories(), Some(vec!["https://parent.example.com"]));
+        assert_eq!(pom.modules, vec!["core"]);
+    }
+
+    #[test]
+    fn keeps_doctype_entities_as_written() {
+        let pom = parse(
+            r#"<?xml version="1.0"?>
+<!DOCTYPE project [
+  <!ENTITY host "repo.example.com">
+]>
+<project>
+  <repositories>
+    <repository>
+      <id>entity</id>
+      <url>https://&host;/maven2</url>
+    </repository>
+    <repository>
+      <id>escaped</id>
+      <url>https://repo.example.com/?a=1&amp;b=2</url>
+    </repository>
+  </repositories>
+</project>"#,
+        );
+        assert_eq!(
+            pom.repositories(),
+            Some(vec![
+                "https://&host;/maven2",
+                "https://repo.example.com/?a=1&b=2"
+            ])
+        );
+    }
+
+    #[test]
+    fn lists_modules() {
+        let pom = parse(
+            r#"<project>
+  <modules>
+    <module>core</module>
+    <module> </module>
+    <module>plugins/cli</module>
+  </modules>
+  <profiles>
+    <profile>
+      <modules>
+        <module>docs</module>
+      </modules>
+    </profile>
+  </profiles>
+</project>"#,
+        );
+        // Modules of profiles are only built with the profile
+        assert_eq!(pom.modules, vec!["core", "plugins/cli"]);
+    }
+
+    #[test]
+    fn names_project_dirs_unambiguously() {
+        for name in [
+            "alice/app",
+            "alice/app.kt",
+            "group/sub/project",
+            "group.sub/project",
+            "group/sub.project",
+            "a.b/c",
+            "a/b.c",
+            "100%/x",
+        ] {
+            assert_eq!(repo_name(&crate::project_dir(name)), name);
+        }
+        // GitHub's project dirs stay as they were
+        assert_eq!(crate::project_dir("alice/app.kt"), "alice.app.kt");
+        assert_ne!(crate::project_dir("a.b/c"), crate::project_dir("a/b.c"));
+    }
+
+    #[test]
+    fn rejects_invalid_xml() {
+        assert!(Pom::parse(b"<project><repositories></project>").is_err());
+        assert!(Pom::parse(b"").is_err());
+        assert!(Pom::parse(b"<projects></projects>").is_err());
     }
 }
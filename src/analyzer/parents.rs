@@ -0,0 +1,337 @@
+//! Downloads the parent poms projects reference from outside the project into the local maven
+//! repository, so their effective poms can be built offline (`--mvn-offline`, or in-process by
+//! [resolve](crate::analyzer::resolve)).
+//!
+//! Parents are looked up in Maven Central, then in the repositories declared by the poms
+//! referencing them. The parents of downloaded parents are downloaded too, up to the root of the
+//! chain.
+
+use crate::analyzer::resolve;
+use crate::analyzer::{urls, Error, Project};
+use crate::data::Data;
+use indicatif::ProgressBar;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::task::{spawn_blocking, JoinSet};
+use tracing::{debug, info, warn};
+use walkdir::WalkDir;
+
+static USER_AGENT: &str = "maven_github_scraper (https://github.com/NULLx76/maven_github_scraper)";
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Amount of projects to fetch the parents of at the same time
+    pub jobs: usize,
+    /// Fetch the parents of projects again that had unresolved parents in an earlier run
+    pub retry_failed: bool,
+    /// The local maven repository parents are downloaded into
+    pub local_repo: PathBuf,
+}
+
+/// Outcome of fetching the parents of one project, stored in `parents.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParentStatus {
+    /// Name of the project dir
+    pub project: String,
+    /// Parents from outside the project in the local repository, including their own parents
+    pub resolved: usize,
+    /// Parents of `resolved` downloaded by this run
+    pub downloaded: usize,
+    /// Coordinates of the parents that weren't found in any repository
+    #[serde(default)]
+    pub unresolved: Vec<String>,
+}
+
+impl ParentStatus {
+    pub fn success(&self) -> bool {
+        self.unresolved.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Summary {
+    /// Projects skipped because of an earlier run
+    pub skipped: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub parents_resolved: usize,
+    pub parents_downloaded: usize,
+    pub parents_unresolved: usize,
+}
+
+impl Summary {
+    pub fn print(&self) {
+        println!(
+            "Resolved the parents of {} projects, {} failed, {} skipped from earlier runs",
+            self.succeeded, self.failed, self.skipped
+        );
+        println!(
+            "{} parents resolved ({} downloaded), {} not found",
+            self.parents_resolved, self.parents_downloaded, self.parents_unresolved
+        );
+    }
+}
+
+/// Projects by how their parents from outside the project were resolved, see `fetch-parents`
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ParentResolution {
+    /// Projects whose parents were fetched
+    pub fetched: usize,
+    /// Projects of `fetched` with parents from outside the project
+    pub remote: usize,
+    /// Projects of `remote` with parents that weren't found in any repository
+    pub unresolved: usize,
+    /// Amount of projects per parent that wasn't found
+    pub missing: BTreeMap<String, usize>,
+}
+
+impl ParentResolution {
+    pub fn add(&mut self, project: &Project) {
+        let Some(parents) = &project.parents else {
+            return;
+        };
+        self.fetched += 1;
+        if parents.resolved + parents.unresolved.len() > 0 {
+            self.remote += 1;
+        }
+        if !parents.unresolved.is_empty() {
+            self.unresolved += 1;
+        }
+        for parent in &parents.unresolved {
+            *self.missing.entry(parent.clone()).or_insert(0) += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: ParentResolution) {
+        self.fetched += other.fetched;
+        self.remote += other.remote;
+        self.unresolved += other.unresolved;
+        for (parent, count) in other.missing {
+            *self.missing.entry(parent).or_insert(0) += count;
+        }
+    }
+
+    pub fn print(&self) {
+        if self.fetched == 0 {
+            return;
+        }
+        println!(
+            "Parents ({} repos with fetched parents): {} with parents outside the project, {} of those unresolved",
+            self.fetched, self.remote, self.unresolved
+        );
+        let mut missing: Vec<_> = self.missing.iter().collect();
+        missing.sort_by(|(_, a), (_, b)| b.cmp(a));
+        missing.truncate(25);
+        if !missing.is_empty() {
+            println!("Top 25 parents that weren't found: {missing:#?}");
+        }
+    }
+}
+
+/// Downloads the pom at `pom_path` (see [resolve::Coordinates::pom_path]) into the local
+/// repository from the first repository that has it. `false` if none of them does.
+async fn download(
+    client: &Client,
+    local_repo: &Path,
+    pom_path: &str,
+    repositories: &[String],
+) -> bool {
+    // Concurrent projects can download the same parent, each writes its own temporary file
+    static PART: AtomicUsize = AtomicUsize::new(0);
+
+    let path = local_repo.join(pom_path);
+    if !resolve::normalize(&path).starts_with(resolve::normalize(local_repo)) {
+        warn!("Not downloading {pom_path:?} outside of the local repository");
+        return false;
+    }
+    for repo in repositories {
+        let url = format!("{repo}/{pom_path}");
+        let resp = match client.get(&url).send().await {
+            Ok(resp) => resp,
+            Err(e) => {
+                warn!("Failed fetching {url}: {e}");
+                continue;
+            }
+        };
+        match resp.status() {
+            StatusCode::OK => {}
+            status => {
+                debug!("{url} returned {status}");
+                continue;
+            }
+        }
+        let body = match resp.bytes().await {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("Failed fetching {url}: {e}");
+                continue;
+            }
+        };
+
+        let part = path.with_extension(format!("pom.{}.part", PART.fetch_add(1, Ordering::SeqCst)));
+        let written = async {
+            tokio::fs::create_dir_all(path.parent().unwrap()).await?;
+            tokio::fs::write(&part, &body).await?;
+            tokio::fs::rename(&part, &path).await
+        };
+        if let Err(e) = written.await {
+            warn!("Failed writing {path:?}: {e}");
+            return false;
+        }
+        return true;
+    }
+
+    false
+}
+
+/// The repositories a parent is looked up in: Maven Central, then the declared ones
+fn repositories(declared: &[String]) -> Vec<String> {
+    let mut repositories = vec![urls::CENTRAL.to_string()];
+    for url in declared.iter().map(|url| urls::normalize(url)) {
+        if url.starts_with("https://") && !repositories.contains(&url) {
+            repositories.push(url);
+        }
+    }
+    repositories
+}
+
+async fn fetch_project(client: Client, project: PathBuf, local_repo: PathBuf) -> ParentStatus {
+    let mut status = ParentStatus {
+        project: project.file_name().unwrap().to_string_lossy().to_string(),
+        resolved: 0,
+        downloaded: 0,
+        unresolved: Vec::new(),
+    };
+
+    let walk_dir = project.clone();
+    let mut queue = spawn_blocking(move || {
+        WalkDir::new(&walk_dir)
+            .follow_links(true)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_name() == "pom.xml")
+            .filter_map(
+                |entry| match resolve::remote_parent(entry.path(), Some(&walk_dir)) {
+                    Ok(parent) => parent,
+                    Err(e) => {
+                        debug!("Failed reading the parent of {:?}: {e}", entry.path());
+                        None
+                    }
+                },
+            )
+            .collect::<Vec<_>>()
+    })
+    .await
+    .unwrap();
+
+    let mut seen = HashSet::new();
+    while let Some(parent) = queue.pop() {
+        if !seen.insert(parent.coordinates.clone()) {
+            continue;
+        }
+        let Some(pom_path) = parent.coordinates.pom_path() else {
+            status.unresolved.push(parent.coordinates.to_string());
+            continue;
+        };
+
+        let path = local_repo.join(&pom_path);
+        if !path.is_file() {
+            let repositories = repositories(&parent.repositories);
+            if !download(&client, &local_repo, &pom_path, &repositories).await {
+                status.unresolved.push(parent.coordinates.to_string());
+                continue;
+            }
+            status.downloaded += 1;
+        }
+        status.resolved += 1;
+
+        // The grandparent can come from the repositories of the parent or of the poms below it
+        let grandparent = spawn_blocking(move || resolve::remote_parent(&path, None))
+            .await
+            .unwrap();
+        match grandparent {
+            Ok(Some(mut grandparent)) => {
+                grandparent.repositories.extend(parent.repositories);
+                queue.push(grandparent);
+            }
+            Ok(None) => {}
+            Err(e) => warn!("Failed reading the parent of {}: {e}", parent.coordinates),
+        }
+    }
+    status.unresolved.sort();
+
+    status
+}
+
+/// Downloads the parents of every project, recording the outcome per project so an interrupted
+/// run continues where it stopped.
+pub async fn fetch_all(data: &Data, options: Options) -> Result<Summary, Error> {
+    let done = {
+        let data = data.clone();
+        spawn_blocking(move || data.read_parent_status())
+            .await
+            .unwrap()?
+    };
+    let client = Client::builder()
+        .user_agent(USER_AGENT)
+        .timeout(Duration::from_secs(60))
+        .build()?;
+
+    let mut summary = Summary::default();
+    let mut projects = data.get_project_dirs().await?;
+    projects.sort();
+    projects.retain(|project| {
+        let name = project.file_name().unwrap().to_string_lossy();
+        let skip = done
+            .get(name.as_ref())
+            .is_some_and(|status| status.success() || !options.retry_failed);
+        if skip {
+            summary.skipped += 1;
+        }
+        !skip
+    });
+    info!(
+        "Fetching the parents of {} projects ({} skipped)",
+        projects.len(),
+        summary.skipped
+    );
+
+    let progress = ProgressBar::new(projects.len() as u64);
+    let mut projects = projects.into_iter();
+    let mut running = JoinSet::new();
+    loop {
+        while running.len() < options.jobs.max(1) {
+            match projects.next() {
+                Some(project) => running.spawn(fetch_project(
+                    client.clone(),
+                    project,
+                    options.local_repo.clone(),
+                )),
+                None => break,
+            };
+        }
+
+        let Some(status) = running.join_next().await else {
+            break;
+        };
+        let status = status.unwrap();
+        data.record_parent_status(&status).await?;
+
+        if status.success() {
+            summary.succeeded += 1;
+        } else {
+            summary.failed += 1;
+        }
+        summary.parents_resolved += status.resolved;
+        summary.parents_downloaded += status.downloaded;
+        summary.parents_unresolved += status.unresolved.len();
+        progress.inc(1);
+    }
+    progress.finish();
+
+    Ok(summary)
+}
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Amount of repositories with the most poms kept as outliers
+const OUTLIERS: usize = 25;
+
+/// Distribution of the amount of pom files per repository
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct PomHistogram {
+    /// Amount of repositories per amount of poms
+    pub counts: BTreeMap<usize, usize>,
+    /// The repositories with the most poms, most first
+    pub outliers: Vec<(String, usize)>,
+}
+
+impl PomHistogram {
+    pub fn add(&mut self, name: &str, poms: usize) {
+        *self.counts.entry(poms).or_insert(0) += 1;
+
+        if self.outliers.len() < OUTLIERS || poms > self.outliers.last().unwrap().1 {
+            self.outliers.push((name.to_string(), poms));
+            self.sort_outliers();
+        }
+    }
+
+    pub fn merge(&mut self, other: PomHistogram) {
+        for (poms, repos) in other.counts {
+            *self.counts.entry(poms).or_insert(0) += repos;
+        }
+        self.outliers.extend(other.outliers);
+        self.sort_outliers();
+    }
+
+    fn sort_outliers(&mut self) {
+        self.outliers
+            .sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then_with(|| a_name.cmp(b_name)));
+        self.outliers.truncate(OUTLIERS);
+    }
+
+    pub fn repos(&self) -> usize {
+        self.counts.values().sum()
+    }
+
+    /// Smallest amount of poms that at least `p` (0 to 1) of the repositories have at most
+    pub fn percentile(&self, p: f64) -> usize {
+        let target = (self.repos() as f64 * p).ceil().max(1.0) as usize;
+        let mut seen = 0;
+        for (&poms, &repos) in &self.counts {
+            seen += repos;
+            if seen >= target {
+                return poms;
+            }
+        }
+        0
+    }
+
+    pub fn print(&self) {
+        let repos = self.repos();
+        if repos == 0 {
+            return;
+        }
+
+        let poms: usize = self.counts.iter().map(|(poms, repos)| poms * repos).sum();
+        println!(
+            "Poms per repository: mean {:.2}, median {}, p90 {}, p99 {}, max {}",
+            poms as f64 / repos as f64,
+            self.percentile(0.5),
+            self.percentile(0.9),
+            self.percentile(0.99),
+            self.counts.keys().last().unwrap()
+        );
+
+        // Power of two buckets keep the histogram readable for monorepos with thousands of modules
+        let mut buckets: BTreeMap<usize, usize> = BTreeMap::new();
+        for (&poms, &count) in &self.counts {
+            *buckets.entry(poms.next_power_of_two()).or_insert(0) += count;
+        }
+        let mut lower = 0;
+        for (upper, count) in buckets {
+            let range = if lower == upper {
+                format!("{upper}")
+            } else {
+                format!("{lower}-{upper}")
+            };
+            println!(
+                "  {range:>11}: {count} ({:.2}%)",
+                count as f64 / repos as f64 * 100.0
+            );
+            lower = upper + 1;
+        }
+
+        println!("Repositories with the most poms: {:#?}", self.outliers);
+    }
+}
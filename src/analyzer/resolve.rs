@@ -0,0 +1,789 @@
+//! Builds effective poms in-process for the common cases, instead of running maven (~2s per pom).
+//!
+//! Handles parent poms inside the project, profiles active by default, dependency and plugin
+//! management and `${...}` interpolation of properties and `project.*` values. Parents outside
+//! the project (or not matching the coordinates they are referenced by) are looked up in the local
+//! repository `fetch-parents` downloads them into, if they aren't there the caller falls back to
+//! maven. Unlike maven, BOM imports aren't expanded and system
+//! properties are left uninterpolated.
+
+use crate::analyzer::urls::CENTRAL;
+use crate::analyzer::xml::{self, Element};
+use crate::analyzer::{Pom, EFFECTIVE_FILE_NAME};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// Maximum depth of parents and of properties referencing properties
+const MAX_DEPTH: usize = 32;
+
+/// Elements of a model that are merged with the parent's element by their children
+const MERGED: &[&str] = &[
+    "properties",
+    "distributionManagement",
+    "build",
+    "pluginManagement",
+    "dependencyManagement",
+    "reporting",
+];
+
+/// Elements of the project that children don't inherit
+const NOT_INHERITED: &[&str] = &["artifactId", "modules", "profiles", "parent", "packaging"];
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO Error: {0:?}")]
+    IO(#[from] io::Error),
+
+    #[error("{0}")]
+    Xml(#[from] xml::Error),
+
+    #[error("Parent {0} isn't in the project")]
+    ParentOutside(String),
+
+    #[error("Parents nested too deep, or a cycle")]
+    TooDeep,
+}
+
+impl Element {
+    /// Identifies the items of a list merged with the parent's list, `None` for other elements
+    fn key(&self) -> Option<String> {
+        match self.name.as_str() {
+            "repository" | "pluginRepository" => Some(self.text_of("id")?.to_string()),
+            "dependency" => Some(format!(
+                "{}:{}:{}:{}",
+                self.text_of("groupId")?,
+                self.text_of("artifactId")?,
+                self.text_of("type").unwrap_or("jar"),
+                self.text_of("classifier").unwrap_or_default()
+            )),
+            "plugin" => Some(format!(
+                "{}:{}",
+                self.text_of("groupId")
+                    .unwrap_or("org.apache.maven.plugins"),
+                self.text_of("artifactId")?
+            )),
+            _ => None,
+        }
+    }
+
+    /// Adds what `parent` has and `self` doesn't: elements in [MERGED] are merged recursively,
+    /// list items by their [Element::key], anything else is taken from `self` if present
+    fn inherit(&mut self, parent: &Element) {
+        for inherited in &parent.children {
+            if is_list(&self.name) {
+                let key = inherited.key();
+                let own = self
+                    .children
+                    .iter_mut()
+                    .find(|child| key.is_some() && child.key() == key);
+                match own {
+                    Some(own) => own.inherit_shallow(inherited),
+                    None => self.children.push(inherited.clone()),
+                }
+                continue;
+            }
+
+            match self.child_mut(&inherited.name) {
+                Some(own) if MERGED.contains(&own.name.as_str()) || is_list(&own.name) => {
+                    own.inherit(inherited)
+                }
+                Some(_) => {}
+                None => self.children.push(inherited.clone()),
+            }
+        }
+    }
+
+    /// Adds the direct children `parent` has and `self` doesn't, e.g. the version of a plugin
+    fn inherit_shallow(&mut self, parent: &Element) {
+        for inherited in &parent.children {
+            if self.child(&inherited.name).is_none() {
+                self.children.push(inherited.clone());
+            }
+        }
+    }
+
+    fn interpolate(&mut self, values: &dyn Fn(&str) -> Option<String>) {
+        self.text = interpolate(&self.text, values, 0);
+        for child in &mut self.children {
+            child.interpolate(values);
+        }
+    }
+
+    fn write(&self, out: &mut String, indent: usize) {
+        let pad = "  ".repeat(indent);
+        if self.children.is_empty() {
+            out.push_str(&format!(
+                "{pad}<{0}>{1}</{0}>\n",
+                self.name,
+                escape(&self.text)
+            ));
+            return;
+        }
+
+        out.push_str(&format!("{pad}<{}>\n", self.name));
+        for child in &self.children {
+            child.write(out, indent + 1);
+        }
+        out.push_str(&format!("{pad}</{}>\n", self.name));
+    }
+}
+
+/// Lists whose items are merged by key
+fn is_list(name: &str) -> bool {
+    matches!(
+        name,
+        "repositories" | "pluginRepositories" | "dependencies" | "plugins"
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Replaces the `${...}` references `values` knows, leaving the others as they are like maven
+fn interpolate(text: &str, values: &dyn Fn(&str) -> Option<String>, depth: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        let Some(end) = rest[start..].find('}').map(|end| start + end) else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let reference = &rest[start..=end];
+        match values(&rest[start + 2..end]) {
+            Some(value) if depth < MAX_DEPTH => {
+                out.push_str(&interpolate(&value, values, depth + 1))
+            }
+            _ => out.push_str(reference),
+        }
+        rest = &rest[end + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Applies the profiles that are active by default, which maven does when no other profile is
+/// activated. Activation by jdk, os, files or properties isn't evaluated, so these are inactive.
+fn apply_default_profiles(model: &mut Element) {
+    let Some(profiles) = model.child("profiles").cloned() else {
+        return;
+    };
+
+    for profile in profiles.children {
+        let active = profile
+            .at(&["activation", "activeByDefault"])
+            .is_some_and(|active| active.text.trim() == "true");
+        if !active {
+            continue;
+        }
+
+        let mut contents = Element {
+            name: model.name.clone(),
+            text: String::new(),
+            children: profile
+                .children
+                .into_iter()
+                .filter(|child| child.name != "id" && child.name != "activation")
+                .collect(),
+        };
+        contents.inherit(model);
+        *model = contents;
+    }
+}
+
+/// Fills in the versions (and scopes) of dependencies and plugins from their management sections
+fn apply_management(model: &mut Element) {
+    let managed_dependencies = model.at(&["dependencyManagement", "dependencies"]).cloned();
+    let managed_plugins = model.at(&["build", "pluginManagement", "plugins"]).cloned();
+
+    let apply = |list: Option<&mut Element>, managed: &Option<Element>| {
+        let (Some(list), Some(managed)) = (list, managed) else {
+            return;
+        };
+        for item in &mut list.children {
+            let key = item.key();
+            if let Some(managed) = managed
+                .children
+                .iter()
+                .find(|m| key.is_some() && m.key() == key)
+            {
+                for field in ["version", "scope"] {
+                    if item.child(field).is_none() {
+                        if let Some(value) = managed.child(field) {
+                            item.children.push(value.clone());
+                        }
+                    }
+                }
+            }
+        }
+    };
+
+    apply(model.child_mut("dependencies"), &managed_dependencies);
+    apply(
+        model
+            .child_mut("build")
+            .and_then(|build| build.child_mut("plugins")),
+        &managed_plugins,
+    );
+}
+
+/// Adds the repositories of maven's super pom
+fn add_central(model: &mut Element) {
+    for (list, item) in [
+        ("repositories", "repository"),
+        ("pluginRepositories", "pluginRepository"),
+    ] {
+        if model.child(list).is_none() {
+            model.children.push(Element {
+                name: list.to_string(),
+                ..Default::default()
+            });
+        }
+        let list = model.child_mut(list).unwrap();
+        if !list
+            .children
+            .iter()
+            .any(|repo| repo.text_of("id") == Some("central"))
+        {
+            let field = |name: &str, text: &str| Element {
+                name: name.to_string(),
+                text: text.to_string(),
+                children: Vec::new(),
+            };
+            list.children.push(Element {
+                name: item.to_string(),
+                text: String::new(),
+                children: vec![field("id", "central"), field("url", CENTRAL)],
+            });
+        }
+    }
+}
+
+/// Removes `.` and `..` without touching the filesystem, so symlinked project dirs stay inside
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Coordinates of a pom in a maven repository
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Coordinates {
+    pub group_id: String,
+    pub artifact_id: String,
+    pub version: String,
+}
+
+impl Coordinates {
+    fn of(element: &Element) -> Self {
+        let field = |name| element.text_of(name).unwrap_or_default().to_string();
+        Self {
+            group_id: field("groupId"),
+            artifact_id: field("artifactId"),
+            version: field("version"),
+        }
+    }
+
+    /// Whether all coordinates are given literally, as needed to look them up in a repository
+    pub fn is_complete(&self) -> bool {
+        [&self.group_id, &self.artifact_id, &self.version]
+            .iter()
+            .all(|field| !field.is_empty() && !field.contains("${"))
+    }
+
+    /// Path of the pom in the maven repository layout, `None` if the coordinates could address
+    /// something outside of it (e.g. `..` or a `/`)
+    pub fn pom_path(&self) -> Option<String> {
+        let safe = |field: &str| {
+            !field.starts_with('.')
+                && !field.ends_with('.')
+                && !field.contains("..")
+                && field
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'.' | b'-'))
+        };
+        if !self.is_complete()
+            || ![&self.group_id, &self.artifact_id, &self.version]
+                .iter()
+                .all(|field| safe(field))
+        {
+            return None;
+        }
+        Some(format!(
+            "{}/{}/{}/{}-{}.pom",
+            self.group_id.replace('.', "/"),
+            self.artifact_id,
+            self.version,
+            self.artifact_id,
+            self.version
+        ))
+    }
+}
+
+impl fmt::Display for Coordinates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.group_id, self.artifact_id, self.version)
+    }
+}
+
+/// The pom a `<parent>` refers to by its `relativePath`, if that is in the project
+fn project_parent(pom: &Path, parent: &Element, project: &Path) -> Option<PathBuf> {
+    // An empty relativePath means the parent is only looked up in repositories
+    let relative = parent.text_of("relativePath").unwrap_or("../pom.xml");
+    if relative.is_empty() {
+        return None;
+    }
+    // relativePath names either the parent's pom or its directory
+    let mut path = normalize(&pom.parent()?.join(relative));
+    if path.is_dir() {
+        path.push("pom.xml");
+    }
+    (path.starts_with(project) && path.is_file()).then_some(path)
+}
+
+/// The pom with profiles applied and inherited from its parents, not interpolated yet
+fn inherited_model(
+    pom: &Path,
+    project: &Path,
+    local_repo: Option<&Path>,
+    depth: usize,
+) -> Result<Element, Error> {
+    if depth > MAX_DEPTH {
+        return Err(Error::TooDeep);
+    }
+
+    let mut model = Element::read(pom)?;
+    apply_default_profiles(&mut model);
+
+    let Some(parent) = model.child("parent").cloned() else {
+        return Ok(model);
+    };
+
+    let in_project = match project_parent(pom, &parent, project) {
+        Some(path) => {
+            let parent_model = inherited_model(&path, project, local_repo, depth + 1)?;
+            let matches = |field| {
+                parent.text_of(field).is_none()
+                    || parent.text_of(field) == parent_model.text_of(field)
+            };
+            (matches("groupId") && matches("artifactId") && matches("version"))
+                .then_some(parent_model)
+        }
+        None => None,
+    };
+    let parent_model = match in_project {
+        Some(parent_model) => parent_model,
+        None => {
+            let coordinates = Coordinates::of(&parent);
+            let Some(path) = local_repo
+                .zip(coordinates.pom_path())
+                .map(|(repo, pom_path)| repo.join(pom_path))
+                .filter(|path| path.is_file())
+            else {
+                return Err(Error::ParentOutside(coordinates.to_string()));
+            };
+            inherited_model(&path, project, local_repo, depth + 1)?
+        }
+    };
+
+    let mut inherited = parent_model;
+    inherited
+        .children
+        .retain(|child| !NOT_INHERITED.contains(&child.name.as_str()));
+    model.inherit(&inherited);
+
+    Ok(model)
+}
+
+/// What `${...}` references in a model resolve to: its properties, `basedir` and `project.*` values
+fn model_values<'a>(model: &'a Element, basedir: &'a str) -> impl Fn(&str) -> Option<String> + 'a {
+    let properties: HashMap<String, String> = model
+        .child("properties")
+        .map(|properties| {
+            properties
+                .children
+                .iter()
+                .map(|property| (property.name.clone(), property.text.clone()))
+                .collect()
+        })
+        .unwrap_or_default();
+    move |name: &str| -> Option<String> {
+        if let Some(value) = properties.get(name) {
+            return Some(value.clone());
+        }
+        if name == "basedir" || name == "project.basedir" {
+            return Some(basedir.to_string());
+        }
+        let path = name
+            .strip_prefix("project.")
+            .or_else(|| name.strip_prefix("pom."))?;
+        let path: Vec<&str> = path.split('.').collect();
+        let element = model.at(&path)?;
+        element.children.is_empty().then(|| element.text.clone())
+    }
+}
+
+/// Resolves `${...}` references against a single pom, without its parents
+#[derive(Debug)]
+pub struct Placeholders {
+    model: Element,
+    basedir: String,
+}
+
+impl Placeholders {
+    pub fn read(pom: &Path) -> Result<Self, Error> {
+        Ok(Self {
+            model: Element::read(pom)?,
+            basedir: pom
+                .parent()
+                .map(|dir| dir.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Replaces the references the pom defines, leaving the others as they are
+    pub fn resolve(&self, text: &str) -> String {
+        interpolate(text, &model_values(&self.model, &self.basedir), 0)
+    }
+
+    /// A value of the `<configuration>` of a plugin in `<build><plugins>`, then in
+    /// `<pluginManagement>`, with its references replaced
+    pub fn plugin_configuration(&self, artifact_id: &str, name: &str) -> Option<String> {
+        [
+            &["build", "plugins"][..],
+            &["build", "pluginManagement", "plugins"],
+        ]
+        .into_iter()
+        .filter_map(|path| self.model.at(path))
+        .flat_map(|plugins| plugins.children.iter())
+        .filter(|plugin| plugin.text_of("artifactId") == Some(artifact_id))
+        .filter_map(|plugin| plugin.at(&["configuration", name]))
+        .find(|value| value.children.is_empty() && !value.text.is_empty())
+        .map(|value| self.resolve(&value.text))
+    }
+}
+
+/// A `<parent>` that has to come from a repository
+#[derive(Debug, Clone)]
+pub struct RemoteParent {
+    pub coordinates: Coordinates,
+    /// Urls of the repositories declared by the pom referencing the parent
+    pub repositories: Vec<String>,
+}
+
+/// The parent of a pom if it isn't in `project`, with the pom's own properties interpolated.
+/// Without a project (for poms in a repository) the parent is always remote.
+pub fn remote_parent(pom: &Path, project: Option<&Path>) -> Result<Option<RemoteParent>, Error> {
+    let pom = normalize(pom);
+    let mut model = Element::read(&pom)?;
+    apply_default_profiles(&mut model);
+    let Some(parent) = model.child("parent") else {
+        return Ok(None);
+    };
+
+    if let Some(project) = project {
+        if let Some(path) = project_parent(&pom, parent, &normalize(project)) {
+            if Element::read(&path)?.text_of("artifactId") == parent.text_of("artifactId") {
+                return Ok(None);
+            }
+        }
+    }
+
+    let basedir = pom
+        .parent()
+        .map(|dir| dir.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let values = model_values(&model, &basedir);
+    let coordinates = Coordinates::of(parent);
+    let coordinates = Coordinates {
+        group_id: interpolate(&coordinates.group_id, &values, 0),
+        artifact_id: interpolate(&coordinates.artifact_id, &values, 0),
+        version: interpolate(&coordinates.version, &values, 0),
+    };
+    let repositories = model
+        .child("repositories")
+        .map(|repositories| {
+            repositories
+                .children
+                .iter()
+                .filter_map(|repo| repo.text_of("url"))
+                .map(|url| interpolate(url, &values, 0))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    Ok(Some(RemoteParent {
+        coordinates,
+        repositories,
+    }))
+}
+
+/// Builds the effective pom of the `pom.xml` in `dir`, with `project` the directory of the
+/// project parents have to be in unless they are in `local_repo`
+pub fn effective_pom(
+    dir: &Path,
+    project: &Path,
+    local_repo: Option<&Path>,
+) -> Result<String, Error> {
+    let dir = normalize(dir);
+    let mut model = inherited_model(&dir.join("pom.xml"), &normalize(project), local_repo, 0)?;
+    model.children.retain(|child| child.name != "profiles");
+    apply_management(&mut model);
+    add_central(&mut model);
+
+    let snapshot = model.clone();
+    let basedir = dir.to_string_lossy().to_string();
+    model.interpolate(&model_values(&snapshot, &basedir));
+
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str("<!-- Effective pom built by rp without maven -->\n");
+    model.write(&mut out, 0);
+    Ok(out)
+}
+
+/// Builds the effective pom of the `pom.xml` in `dir` and writes it to [EFFECTIVE_FILE_NAME]
+pub fn write_effective_pom(
+    dir: &Path,
+    project: &Path,
+    local_repo: Option<&Path>,
+) -> Result<Pom, Error> {
+    let effective = effective_pom(dir, project, local_repo)?;
+    let pom = Pom::parse(effective.as_bytes())?;
+    fs::write(dir.join(EFFECTIVE_FILE_NAME), effective)?;
+    Ok(pom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(xml: &str) -> Element {
+        Element::parse(xml.as_bytes()).unwrap()
+    }
+
+    fn texts<'a>(element: &'a Element, path: &[&str], field: &str) -> Vec<&'a str> {
+        element
+            .at(path)
+            .into_iter()
+            .flat_map(|list| &list.children)
+            .filter_map(|item| item.text_of(field))
+            .collect()
+    }
+
+    #[test]
+    fn inherits_lists_by_key() {
+        let mut child = parse(
+            "<project>
+              <repositories>
+                <repository><id>shared</id><url>https://child.example.com</url></repository>
+              </repositories>
+              <dependencies>
+                <dependency><groupId>g</groupId><artifactId>a</artifactId></dependency>
+              </dependencies>
+              <properties><own>1</own><shared>child</shared></properties>
+            </project>",
+        );
+        let parent = parse(
+            "<project>
+              <repositories>
+                <repository><id>shared</id><url>https://parent.example.com</url></repository>
+                <repository><id>parent</id><url>https://parent.example.com/other</url></repository>
+              </repositories>
+              <dependencies>
+                <dependency>
+                  <groupId>g</groupId><artifactId>a</artifactId><version>1.0</version>
+                </dependency>
+                <dependency>
+                  <groupId>g</groupId><artifactId>a</artifactId><classifier>tests</classifier>
+                </dependency>
+              </dependencies>
+              <properties><shared>parent</shared><inherited>2</inherited></properties>
+              <description>parent</description>
+            </project>",
+        );
+        child.inherit(&parent);
+
+        assert_eq!(
+            texts(&child, &["repositories"], "url"),
+            vec![
+                "https://child.example.com",
+                "https://parent.example.com/other"
+            ]
+        );
+        assert_eq!(
+            texts(&child, &["dependencies"], "version"),
+            vec!["1.0"],
+            "the version of the same dependency is inherited"
+        );
+        assert_eq!(texts(&child, &["dependencies"], "artifactId").len(), 2);
+        assert_eq!(child.at(&["properties", "shared"]).unwrap().text, "child");
+        assert_eq!(child.at(&["properties", "inherited"]).unwrap().text, "2");
+        assert_eq!(child.text_of("description"), Some("parent"));
+    }
+
+    #[test]
+    fn applies_profiles_active_by_default() {
+        let mut model = parse(
+            "<project>
+              <repositories>
+                <repository><id>base</id><url>https://base.example.com</url></repository>
+              </repositories>
+              <profiles>
+                <profile>
+                  <id>default</id>
+                  <activation><activeByDefault>true</activeByDefault></activation>
+                  <repositories>
+                    <repository><id>default</id><url>https://default.example.com</url></repository>
+                  </repositories>
+                </profile>
+                <profile>
+                  <id>jdk</id>
+                  <activation><jdk>17</jdk></activation>
+                  <repositories>
+                    <repository><id>jdk</id><url>https://jdk.example.com</url></repository>
+                  </repositories>
+                </profile>
+              </profiles>
+            </project>",
+        );
+        apply_default_profiles(&mut model);
+
+        assert_eq!(
+            texts(&model, &["repositories"], "id"),
+            vec!["default", "base"]
+        );
+        assert!(model.child("activation").is_none());
+    }
+
+    #[test]
+    fn applies_management() {
+        let mut model = parse(
+            "<project>
+              <dependencyManagement><dependencies>
+                <dependency>
+                  <groupId>g</groupId><artifactId>a</artifactId>
+                  <version>1.0</version><scope>test</scope>
+                </dependency>
+              </dependencies></dependencyManagement>
+              <dependencies>
+                <dependency><groupId>g</groupId><artifactId>a</artifactId></dependency>
+                <dependency>
+                  <groupId>g</groupId><artifactId>a</artifactId><type>pom</type>
+                </dependency>
+              </dependencies>
+              <build>
+                <pluginManagement><plugins>
+                  <plugin><artifactId>maven-jar-plugin</artifactId><version>3.3.0</version></plugin>
+                </plugins></pluginManagement>
+                <plugins>
+                  <plugin>
+                    <groupId>org.apache.maven.plugins</groupId>
+                    <artifactId>maven-jar-plugin</artifactId>
+                  </plugin>
+                </plugins>
+              </build>
+            </project>",
+        );
+        apply_management(&mut model);
+
+        let dependencies = model.child("dependencies").unwrap();
+        assert_eq!(dependencies.children[0].text_of("version"), Some("1.0"));
+        assert_eq!(dependencies.children[0].text_of("scope"), Some("test"));
+        // Another type is another artifact
+        assert_eq!(dependencies.children[1].text_of("version"), None);
+        assert_eq!(
+            texts(&model, &["build", "plugins"], "version"),
+            vec!["3.3.0"]
+        );
+    }
+
+    #[test]
+    fn interpolates_nested_references() {
+        let values = |name: &str| match name {
+            "a" => Some("${b}-a".to_string()),
+            "b" => Some("b".to_string()),
+            _ => None,
+        };
+        assert_eq!(interpolate("${a}/${c}/${", &values, 0), "b-a/${c}/${");
+    }
+
+    #[test]
+    fn stops_interpolating_cycles() {
+        let values = |name: &str| match name {
+            "a" => Some("x${b}".to_string()),
+            "b" => Some("${a}".to_string()),
+            _ => None,
+        };
+        let interpolated = interpolate("${a}", &values, 0);
+        assert!(interpolated.ends_with("${a}") || interpolated.ends_with("${b}"));
+        assert_eq!(interpolated.matches('x').count(), MAX_DEPTH / 2);
+    }
+
+    #[test]
+    fn rejects_unsafe_pom_paths() {
+        let coordinates = |group_id: &str, artifact_id: &str, version: &str| Coordinates {
+            group_id: group_id.to_string(),
+            artifact_id: artifact_id.to_string(),
+            version: version.to_string(),
+        };
+        assert_eq!(
+            coordinates("org.example", "parent", "1.0").pom_path(),
+            Some("org/example/parent/1.0/parent-1.0.pom".to_string())
+        );
+        for unsafe_coordinates in [
+            coordinates("..", "parent", "1.0"),
+            coordinates("org..example", "parent", "1.0"),
+            coordinates(".org", "parent", "1.0"),
+            coordinates("org.", "parent", "1.0"),
+            coordinates("org/example", "parent", "1.0"),
+            coordinates("org.example", "../parent", "1.0"),
+            coordinates("org.example", "parent", "1.0/../.."),
+            coordinates("org.example", "parent", "${revision}"),
+            coordinates("org.example", "parent", ""),
+            coordinates("org.example", "par ent", "1.0"),
+        ] {
+            assert_eq!(unsafe_coordinates.pom_path(), None, "{unsafe_coordinates}");
+        }
+    }
+
+    #[test]
+    fn finds_parents_by_file_or_directory() {
+        let project = tempfile::tempdir().unwrap();
+        let root = project.path();
+        fs::create_dir_all(root.join("parent")).unwrap();
+        fs::create_dir_all(root.join("child")).unwrap();
+        fs::write(root.join("parent/parent-pom.xml"), "<project/>").unwrap();
+        fs::write(root.join("parent/pom.xml"), "<project/>").unwrap();
+        let pom = root.join("child/pom.xml");
+
+        let parent = |relative: &str| {
+            parse(&format!(
+                "<parent><relativePath>{relative}</relativePath></parent>"
+            ))
+        };
+        assert_eq!(
+            project_parent(&pom, &parent("../parent/parent-pom.xml"), root),
+            Some(root.join("parent/parent-pom.xml"))
+        );
+        assert_eq!(
+            project_parent(&pom, &parent("../parent"), root),
+            Some(root.join("parent/pom.xml"))
+        );
+        assert_eq!(project_parent(&pom, &parent("../missing.xml"), root), None);
+        assert_eq!(project_parent(&pom, &parent(""), root), None);
+    }
+}
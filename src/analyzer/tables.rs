@@ -0,0 +1,203 @@
+use crate::analyzer::{Chunk, Project, Report};
+use crate::data::Data;
+use arrow::array::{ArrayRef, ListBuilder, StringArray, StringBuilder, UInt64Array};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::error::ArrowError;
+use arrow::ipc::writer::FileWriter;
+use arrow::record_batch::RecordBatch;
+use clap::ValueEnum;
+use dashmap::DashMap;
+use parquet::arrow::ArrowWriter;
+use parquet::errors::ParquetError;
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::info;
+
+/// Format of the per-project results written by the analyzer
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum OutputFormat {
+    /// projects.json
+    #[default]
+    Json,
+    /// Arrow IPC (Feather v2) files: projects.arrow, external_repos.arrow and distros.arrow, next
+    /// to projects.json
+    Arrow,
+}
+
+/// Format of the per-project rows written by `export-projects`
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum ProjectsFormat {
+    /// Newline delimited JSON, one project per line
+    Ndjson,
+    /// Apache Parquet
+    Parquet,
+}
+
+impl ProjectsFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ProjectsFormat::Ndjson => "jsonl",
+            ProjectsFormat::Parquet => "parquet",
+        }
+    }
+}
+
+/// A project as a flat row, with sorted repository lists so exports are reproducible
+#[derive(Debug, Serialize)]
+struct ProjectRow<'a> {
+    name: &'a str,
+    external_repos: Vec<&'a String>,
+    distribution_repos: Vec<&'a String>,
+    /// The repos as written in the build files, before normalization
+    raw_external_repos: &'a BTreeSet<String>,
+    raw_distribution_repos: &'a BTreeSet<String>,
+    poms: usize,
+    gradle_scripts: usize,
+    errors: &'a [String],
+}
+
+impl<'a> From<&'a Project> for ProjectRow<'a> {
+    fn from(project: &'a Project) -> Self {
+        let mut external_repos: Vec<_> = project.repos.iter().collect();
+        external_repos.sort_unstable();
+        let mut distribution_repos: Vec<_> = project.dist_repos.iter().collect();
+        distribution_repos.sort_unstable();
+
+        ProjectRow {
+            name: &project.name,
+            external_repos,
+            distribution_repos,
+            raw_external_repos: &project.raw_repos,
+            raw_distribution_repos: &project.raw_dist_repos,
+            poms: project.poms,
+            gradle_scripts: project.gradle_scripts,
+            errors: &project.errors,
+        }
+    }
+}
+
+fn list_column<'a>(values: impl Iterator<Item = impl Iterator<Item = &'a String>>) -> ArrayRef {
+    let mut builder = ListBuilder::new(StringBuilder::new());
+    for list in values {
+        for value in list {
+            builder.values().append_value(value);
+        }
+        builder.append(true);
+    }
+    Arc::new(builder.finish())
+}
+
+fn count_column(values: impl Iterator<Item = usize>) -> ArrayRef {
+    Arc::new(UInt64Array::from_iter_values(values.map(|v| v as u64)))
+}
+
+fn projects_batch(projects: &[Project]) -> Result<RecordBatch, ArrowError> {
+    let rows: Vec<ProjectRow> = projects.iter().map(ProjectRow::from).collect();
+    let names = StringArray::from_iter_values(rows.iter().map(|r| r.name));
+    let repos = list_column(rows.iter().map(|r| r.external_repos.iter().copied()));
+    let dist_repos = list_column(rows.iter().map(|r| r.distribution_repos.iter().copied()));
+
+    RecordBatch::try_from_iter([
+        ("name", Arc::new(names) as ArrayRef),
+        ("external_repos", repos),
+        ("distribution_repos", dist_repos),
+        (
+            "raw_external_repos",
+            list_column(rows.iter().map(|r| r.raw_external_repos.iter())),
+        ),
+        (
+            "raw_distribution_repos",
+            list_column(rows.iter().map(|r| r.raw_distribution_repos.iter())),
+        ),
+        ("poms", count_column(rows.iter().map(|r| r.poms))),
+        (
+            "gradle_scripts",
+            count_column(rows.iter().map(|r| r.gradle_scripts)),
+        ),
+        ("errors", list_column(rows.iter().map(|r| r.errors.iter()))),
+    ])
+}
+
+fn counts_batch(counts: &DashMap<String, usize>) -> Result<RecordBatch, ArrowError> {
+    let mut counts: Vec<(String, usize)> = counts
+        .iter()
+        .map(|el| (el.key().clone(), *el.value()))
+        .collect();
+    counts.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+
+    let schema = Schema::new(vec![
+        Field::new("url", DataType::Utf8, false),
+        Field::new("count", DataType::UInt64, false),
+    ]);
+    RecordBatch::try_new(
+        Arc::new(schema),
+        vec![
+            Arc::new(StringArray::from_iter_values(
+                counts.iter().map(|(url, _)| url.as_str()),
+            )),
+            Arc::new(UInt64Array::from_iter_values(
+                counts.iter().map(|(_, count)| *count as u64),
+            )),
+        ],
+    )
+}
+
+fn write_batch(path: &Path, batch: &RecordBatch) -> Result<(), ArrowError> {
+    let file = File::create(path)?;
+    let mut writer = FileWriter::try_new(file, &batch.schema())?;
+    writer.write(batch)?;
+    writer.finish()
+}
+
+/// Writes the per-project results and the aggregate counts as Arrow IPC files
+///
+/// Warning: this method blocks
+pub fn write_arrow(
+    data: &Data,
+    report: &Report,
+    projects: &[Project],
+    chunk: Option<Chunk>,
+) -> Result<(), ArrowError> {
+    write_batch(
+        &data.output_path("projects", "arrow", chunk),
+        &projects_batch(projects)?,
+    )?;
+    write_batch(
+        &data.output_path("external_repos", "arrow", chunk),
+        &counts_batch(&report.external_repos)?,
+    )?;
+    write_batch(
+        &data.output_path("distros", "arrow", chunk),
+        &counts_batch(&report.distros)?,
+    )?;
+    info!("Wrote arrow tables");
+
+    Ok(())
+}
+
+/// Writes one json object per project and line
+///
+/// Warning: this method blocks
+pub fn write_ndjson(path: &Path, projects: &[Project]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for project in projects {
+        serde_json::to_writer(&mut writer, &ProjectRow::from(project))?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+/// Writes the projects as a Parquet table, with the same columns as projects.arrow
+///
+/// Warning: this method blocks
+pub fn write_parquet(path: &Path, projects: &[Project]) -> Result<(), ParquetError> {
+    let batch = projects_batch(projects)?;
+    let mut writer = ArrowWriter::try_new(File::create(path)?, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close()?;
+    Ok(())
+}
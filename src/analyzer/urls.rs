@@ -0,0 +1,55 @@
+//! Normalizes repository urls, so different spellings of the same repository are counted as one:
+//! `http` and `https`, trailing slashes, the case of the host, credentials and known aliases of
+//! repositories.
+
+use url::Url;
+
+pub const CENTRAL: &str = "https://repo.maven.apache.org/maven2";
+
+/// Canonical urls of repositories known under other urls, by host and path
+const ALIASES: &[(&str, &str)] = &[
+    ("repo1.maven.org/maven2", CENTRAL),
+    ("repo.maven.org/maven2", CENTRAL),
+    ("central.maven.org/maven2", CENTRAL),
+    ("repo.maven.apache.org/maven2", CENTRAL),
+    (
+        "dl.google.com/dl/android/maven2",
+        "https://maven.google.com",
+    ),
+    ("jcenter.bintray.com", "https://jcenter.bintray.com"),
+    ("plugins.gradle.org/m2", "https://plugins.gradle.org/m2"),
+];
+
+/// The url a repository is counted as. Urls that aren't http(s) or still contain `${...}`
+/// references are only trimmed.
+pub fn normalize(url: &str) -> String {
+    let url = url.trim();
+    let trimmed = || url.trim_end_matches('/').to_string();
+    if url.contains("${") {
+        return trimmed();
+    }
+    let Ok(parsed) = Url::parse(url) else {
+        return trimmed();
+    };
+    let Some(host) = parsed.host_str() else {
+        return trimmed();
+    };
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return trimmed();
+    }
+
+    // Parsing lowercases the host and drops default ports
+    let mut location = host.to_string();
+    if let Some(port) = parsed.port() {
+        location.push_str(&format!(":{port}"));
+    }
+    location.push_str(parsed.path().trim_end_matches('/'));
+    if let Some((_, canonical)) = ALIASES.iter().find(|(alias, _)| *alias == location) {
+        return canonical.to_string();
+    }
+
+    match parsed.query() {
+        Some(query) => format!("https://{location}?{query}"),
+        None => format!("https://{location}"),
+    }
+}
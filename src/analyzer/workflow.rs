@@ -0,0 +1,310 @@
+//! Publishing in GitHub Actions workflows (see fetch-workflows), correlated with the
+//! distributionManagement of the poms.
+
+use crate::analyzer::distribution::GITHUB_PACKAGES_HOST;
+use crate::analyzer::Project;
+use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use url::Url;
+
+/// Third party actions that publish artifacts, by their name without version
+const PUBLISH_ACTIONS: [&str; 2] = [
+    "jreleaser/release-action",
+    "samuelmeuli/action-maven-publish",
+];
+
+/// Whether a file in a project, by its path relative to the project, is a GitHub workflow
+pub fn is_workflow_file(relative: &Path) -> bool {
+    relative.parent() == Some(Path::new(".github/workflows"))
+        && relative
+            .extension()
+            .is_some_and(|ext| ext == "yml" || ext == "yaml")
+}
+
+#[derive(Debug, Deserialize)]
+struct WorkflowFile {
+    #[serde(default)]
+    jobs: BTreeMap<String, Job>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Job {
+    /// A reusable workflow the job calls instead of running steps
+    uses: Option<String>,
+    with: Option<BTreeMap<String, Value>>,
+    #[serde(default)]
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Step {
+    uses: Option<String>,
+    run: Option<String>,
+    /// `with:` without inputs is null
+    with: Option<BTreeMap<String, Value>>,
+}
+
+/// A kind of workflow step that publishes artifacts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PublishStep {
+    /// `actions/setup-java` with a `server-id`, writing a settings.xml to deploy with
+    SetupJavaServer,
+    /// Running `mvn deploy` (or `release:perform`)
+    MavenDeploy,
+    /// Running a Gradle `publish` task, other than `publishToMavenLocal`
+    GradlePublish,
+    /// Using one of the [PUBLISH_ACTIONS]
+    PublishAction,
+    /// Releasing with the JReleaser CLI, maven plugin or Gradle plugin
+    Jreleaser,
+}
+
+impl PublishStep {
+    /// Whether the step itself publishes, setup-java only prepares for it
+    pub fn publishes(self) -> bool {
+        self != PublishStep::SetupJavaServer
+    }
+}
+
+/// What a GitHub workflow does for publishing
+#[derive(Debug, Default)]
+pub struct Workflow {
+    pub steps: BTreeSet<PublishStep>,
+    /// Server ids configured with `actions/setup-java`
+    pub server_ids: BTreeSet<String>,
+    /// Configures GitHub Packages as maven registry
+    pub github_packages: bool,
+}
+
+pub fn read_workflow(path: &Path) -> color_eyre::Result<Workflow> {
+    let text = fs::read_to_string(path)?;
+    let file: WorkflowFile = serde_yaml::from_str(&text)?;
+
+    let mut workflow = Workflow {
+        github_packages: text.contains(GITHUB_PACKAGES_HOST),
+        ..Workflow::default()
+    };
+    for job in file.jobs.values() {
+        // Local reusable workflows (`./.github/workflows/...`) are read as workflows of their own
+        if let Some(uses) = &job.uses {
+            workflow.add_uses(uses, job.with.as_ref());
+        }
+        for step in &job.steps {
+            if let Some(uses) = &step.uses {
+                workflow.add_uses(uses, step.with.as_ref());
+            }
+            if let Some(run) = &step.run {
+                workflow.steps.extend(run_steps(run));
+            }
+        }
+    }
+
+    Ok(workflow)
+}
+
+impl Workflow {
+    /// Adds an action used by a step or a reusable workflow called by a job
+    fn add_uses(&mut self, uses: &str, with: Option<&BTreeMap<String, Value>>) {
+        let input = |name| with.and_then(|with| with.get(name)).and_then(Value::as_str);
+        // e.g. `actions/setup-java@v4` or `owner/repo/.github/workflows/release.yml@main`
+        let action = uses.split('@').next().unwrap_or_default();
+        let repo = action.splitn(3, '/').take(2).collect::<Vec<_>>().join("/");
+        if action == "actions/setup-java" {
+            if let Some(server_id) = input("server-id") {
+                self.steps.insert(PublishStep::SetupJavaServer);
+                self.github_packages |= server_id == "github";
+                self.server_ids.insert(server_id.to_string());
+            }
+        } else if action.starts_with("gradle/") {
+            // The Gradle actions only publish when running a publish task
+            if let Some(arguments) = input("arguments") {
+                let tasks: Vec<&str> = arguments.split_whitespace().collect();
+                if tasks.iter().any(|task| is_gradle_publish(task)) {
+                    self.steps.insert(PublishStep::GradlePublish);
+                }
+                if tasks.iter().any(|task| is_jreleaser_task(task)) {
+                    self.steps.insert(PublishStep::Jreleaser);
+                }
+            }
+        } else if PUBLISH_ACTIONS.contains(&repo.as_str()) {
+            self.steps.insert(PublishStep::PublishAction);
+        }
+    }
+}
+
+fn is_gradle_publish(task: &str) -> bool {
+    let task = task.rsplit(':').next().unwrap_or_default();
+    task.starts_with("publish") && task != "publishToMavenLocal"
+}
+
+fn is_maven_deploy(goal: &str) -> bool {
+    goal == "deploy" || goal.starts_with("deploy:") || goal == "release:perform"
+}
+
+/// The JReleaser commands that release or deploy, the others only check or assemble
+const JRELEASER_COMMANDS: [&str; 4] = ["full-release", "release", "deploy", "publish"];
+
+/// e.g. `jreleaser:full-release`
+fn is_jreleaser_goal(goal: &str) -> bool {
+    goal.strip_prefix("jreleaser:")
+        .is_some_and(|goal| JRELEASER_COMMANDS.contains(&goal))
+}
+
+/// e.g. `jreleaserFullRelease`
+fn is_jreleaser_task(task: &str) -> bool {
+    let task = task.rsplit(':').next().unwrap_or_default();
+    task.strip_prefix("jreleaser").is_some_and(|command| {
+        JRELEASER_COMMANDS
+            .iter()
+            .any(|known| known.replace('-', "").eq_ignore_ascii_case(command))
+    })
+}
+
+/// The publish steps of the commands in a `run` script
+fn run_steps(run: &str) -> BTreeSet<PublishStep> {
+    let mut steps = BTreeSet::new();
+    for command in run.replace("\\\n", " ").lines() {
+        let mut words = command.split_whitespace();
+        while let Some(word) = words.next() {
+            // `jbang jreleaser@jreleaser` runs the CLI as well
+            let program = word.rsplit('/').next().unwrap_or_default();
+            let program = program.split('@').next().unwrap_or_default();
+            if matches!(program, "mvn" | "mvnw" | "mvnd") {
+                if words.clone().any(is_maven_deploy) {
+                    steps.insert(PublishStep::MavenDeploy);
+                }
+                if words.clone().any(is_jreleaser_goal) {
+                    steps.insert(PublishStep::Jreleaser);
+                }
+            } else if matches!(program, "gradle" | "gradlew") {
+                if words.clone().any(is_gradle_publish) {
+                    steps.insert(PublishStep::GradlePublish);
+                }
+                if words.clone().any(is_jreleaser_task) {
+                    steps.insert(PublishStep::Jreleaser);
+                }
+            } else if program == "jreleaser"
+                && words
+                    .clone()
+                    .find(|word| !word.starts_with('-'))
+                    .is_some_and(|command| JRELEASER_COMMANDS.contains(&command))
+            {
+                steps.insert(PublishStep::Jreleaser);
+            }
+        }
+    }
+    steps
+}
+
+/// Projects using a publish step, and how many of them have a distributionManagement
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default)]
+pub struct StepUsage {
+    pub projects: usize,
+    pub with_distribution: usize,
+}
+
+/// Publishing in the workflows of the projects that have any
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct WorkflowUsage {
+    /// Projects with GitHub workflows, the others aren't counted below
+    pub with_workflows: usize,
+    pub steps: BTreeMap<PublishStep, StepUsage>,
+    /// Indexed by publishes in a workflow and has distributionManagement
+    pub publishing: [[usize; 2]; 2],
+    /// Amount of projects per setup-java server id
+    pub server_ids: BTreeMap<String, usize>,
+    /// Amount of projects publishing in a workflow per host of their distributionManagement
+    pub publish_hosts: BTreeMap<String, usize>,
+}
+
+impl WorkflowUsage {
+    pub fn add(&mut self, project: &Project) {
+        if project.workflows == 0 {
+            return;
+        }
+        self.with_workflows += 1;
+
+        let has_distribution = !project.dist_repos.is_empty();
+        for step in &project.publish_steps {
+            let usage = self.steps.entry(*step).or_default();
+            usage.projects += 1;
+            usage.with_distribution += usize::from(has_distribution);
+        }
+        self.publishing[usize::from(project.has_publish_workflow)]
+            [usize::from(has_distribution)] += 1;
+        for server_id in &project.workflow_server_ids {
+            *self.server_ids.entry(server_id.clone()).or_insert(0) += 1;
+        }
+        if project.has_publish_workflow {
+            let hosts: BTreeSet<_> = project
+                .dist_repos
+                .iter()
+                .filter_map(|repo| Url::parse(repo).ok()?.host_str().map(str::to_string))
+                .collect();
+            for host in hosts {
+                *self.publish_hosts.entry(host).or_insert(0) += 1;
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: WorkflowUsage) {
+        self.with_workflows += other.with_workflows;
+        for (step, usage) in other.steps {
+            let total = self.steps.entry(step).or_default();
+            total.projects += usage.projects;
+            total.with_distribution += usage.with_distribution;
+        }
+        for publishes in 0..2 {
+            for distribution in 0..2 {
+                self.publishing[publishes][distribution] +=
+                    other.publishing[publishes][distribution];
+            }
+        }
+        for (server_id, count) in other.server_ids {
+            *self.server_ids.entry(server_id).or_insert(0) += count;
+        }
+        for (host, count) in other.publish_hosts {
+            *self.publish_hosts.entry(host).or_insert(0) += count;
+        }
+    }
+
+    pub fn print(&self) {
+        if self.with_workflows == 0 {
+            return;
+        }
+
+        println!("Workflows ({} repos with workflows):", self.with_workflows);
+        let yes_no = |b| if b == 1 { "yes" } else { "no" };
+        println!("  publish workflow  distributionManagement  repos");
+        for publishes in 0..2 {
+            for distribution in 0..2 {
+                println!(
+                    "  {:>16}  {:>22}  {}",
+                    yes_no(publishes),
+                    yes_no(distribution),
+                    self.publishing[publishes][distribution]
+                );
+            }
+        }
+        for (step, usage) in &self.steps {
+            println!(
+                "  {step:?}: {} repos, {} with distributionManagement",
+                usage.projects, usage.with_distribution
+            );
+        }
+        if !self.server_ids.is_empty() {
+            println!("setup-java server ids: {:#?}", self.server_ids);
+        }
+        if !self.publish_hosts.is_empty() {
+            println!(
+                "distributionManagement hosts of repos publishing in workflows: {:#?}",
+                self.publish_hosts
+            );
+        }
+    }
+}
@@ -0,0 +1,114 @@
+//! A minimal xml tree of the poms and other maven files, read with quick-xml.
+//!
+//! Elements are named by their local name, so namespaced poms (`<pom:project>`) read like any
+//! other. CDATA is text, and text with entities a doctype declares is kept as written instead of
+//! failing the file.
+//! Attributes, comments and processing instructions aren't needed and dropped.
+
+use quick_xml::escape::unescape;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO Error: {0:?}")]
+    IO(#[from] io::Error),
+
+    #[error("Invalid xml: {0}")]
+    Xml(#[from] quick_xml::Error),
+
+    #[error("Invalid xml: unclosed <{0}>")]
+    Unclosed(String),
+
+    #[error("Invalid xml: no root element")]
+    NoRoot,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Element {
+    pub name: String,
+    /// The text directly in the element, trimmed
+    pub text: String,
+    pub children: Vec<Element>,
+}
+
+fn local_name(start: &BytesStart) -> String {
+    String::from_utf8_lossy(start.local_name().as_ref()).into_owned()
+}
+
+impl Element {
+    /// Parses a document into its root element
+    pub fn parse(bytes: &[u8]) -> Result<Element, Error> {
+        let mut reader = Reader::from_reader(bytes);
+        reader.config_mut().trim_text(true);
+
+        let mut stack = vec![Element::default()];
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf)? {
+                Event::Start(start) => stack.push(Element {
+                    name: local_name(&start),
+                    ..Default::default()
+                }),
+                Event::Empty(start) => stack.last_mut().unwrap().children.push(Element {
+                    name: local_name(&start),
+                    ..Default::default()
+                }),
+                Event::End(_) if stack.len() > 1 => {
+                    let element = stack.pop().unwrap();
+                    stack.last_mut().unwrap().children.push(element);
+                }
+                Event::Text(text) => {
+                    let raw = String::from_utf8_lossy(&text);
+                    let text = unescape(&raw).unwrap_or_else(|_| raw.clone());
+                    stack.last_mut().unwrap().text.push_str(&text);
+                }
+                Event::CData(text) => {
+                    let text = String::from_utf8_lossy(&text);
+                    stack.last_mut().unwrap().text.push_str(text.trim());
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        let mut document = stack.pop().unwrap();
+        if let Some(unclosed) = stack.pop() {
+            return Err(Error::Unclosed(unclosed.name));
+        }
+        document.children.pop().ok_or(Error::NoRoot)
+    }
+
+    pub fn read(path: &Path) -> Result<Element, Error> {
+        Element::parse(&fs::read(path)?)
+    }
+
+    /// The first child named `name`
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut Element> {
+        self.children.iter_mut().find(|child| child.name == name)
+    }
+
+    /// All children named `name`, for elements that are repeated
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    pub fn text_of(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.as_str())
+    }
+
+    /// The element at a path of child names
+    pub fn at(&self, path: &[&str]) -> Option<&Element> {
+        path.iter()
+            .try_fold(self, |element, name| element.child(name))
+    }
+}
@@ -0,0 +1,188 @@
+//! SHA-256 manifests of a data dir, in the format of `sha256sum`, so published dataset archives
+//! can be checked with `sha256sum -c` or `verify-checksums`.
+
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+pub const MANIFEST: &str = "SHA256SUMS";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO Error: {0:?}")]
+    IO(#[from] io::Error),
+
+    #[error("Error walking directory: {0:?}")]
+    Walk(#[from] walkdir::Error),
+
+    #[error("Invalid line {line} in {manifest:?}")]
+    InvalidManifest { manifest: PathBuf, line: usize },
+}
+
+fn sha256(path: &Path) -> Result<String, Error> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// All files below `dir` relative to it, except the manifests, following the symlinks of subsets
+fn files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in WalkDir::new(dir).follow_links(true) {
+        let entry = entry?;
+        if entry.file_type().is_file() && entry.file_name() != MANIFEST {
+            files.push(entry.path().strip_prefix(dir).unwrap().to_path_buf());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The manifest each file belongs in: the root manifest, or with `per_directory` the one in its
+/// top-level directory
+fn manifest_dir(file: &Path, per_directory: bool) -> PathBuf {
+    let mut components = file.components();
+    match (per_directory, components.next(), components.next()) {
+        (true, Some(dir), Some(_)) => PathBuf::from(dir.as_os_str()),
+        _ => PathBuf::new(),
+    }
+}
+
+/// Writes the manifests of a data dir, returning their paths and the amount of files
+pub fn write(data_dir: &Path, per_directory: bool) -> Result<(Vec<PathBuf>, usize), Error> {
+    // Manifests of an earlier run with the other layout would list files twice
+    for manifest in manifests(data_dir)? {
+        fs::remove_file(data_dir.join(manifest).join(MANIFEST))?;
+    }
+
+    let files = files(data_dir)?;
+    let sums: Vec<String> = files
+        .par_iter()
+        .map(|file| sha256(&data_dir.join(file)))
+        .collect::<Result<_, _>>()?;
+
+    let mut manifests: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+    for (file, sum) in files.iter().zip(sums) {
+        let dir = manifest_dir(file, per_directory);
+        let path = file.strip_prefix(&dir).unwrap();
+        manifests
+            .entry(dir)
+            .or_default()
+            .push(format!("{sum}  {}\n", path.display()));
+    }
+
+    let mut paths = Vec::new();
+    for (dir, lines) in manifests {
+        let path = data_dir.join(dir).join(MANIFEST);
+        let mut f = io::BufWriter::new(File::create(&path)?);
+        for line in lines {
+            f.write_all(line.as_bytes())?;
+        }
+        f.flush()?;
+        paths.push(path);
+    }
+
+    Ok((paths, files.len()))
+}
+
+#[derive(Debug, Default)]
+pub struct Verification {
+    pub ok: usize,
+    pub mismatched: Vec<PathBuf>,
+    pub missing: Vec<PathBuf>,
+    /// Files not in any manifest
+    pub unlisted: Vec<PathBuf>,
+}
+
+impl Verification {
+    pub fn success(&self) -> bool {
+        self.mismatched.is_empty() && self.missing.is_empty()
+    }
+
+    pub fn print(&self) {
+        println!("{} files ok", self.ok);
+        for (what, files) in [
+            ("mismatched", &self.mismatched),
+            ("missing", &self.missing),
+            ("not in a manifest", &self.unlisted),
+        ] {
+            if !files.is_empty() {
+                println!("{} files {what}: {files:#?}", files.len());
+            }
+        }
+    }
+}
+
+/// The directories, relative to the data dir, containing a manifest: the data dir itself and its
+/// top-level directories
+fn manifests(data_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut dirs = vec![PathBuf::new()];
+    for entry in fs::read_dir(data_dir)? {
+        dirs.push(PathBuf::from(entry?.file_name()));
+    }
+    dirs.retain(|dir| data_dir.join(dir).join(MANIFEST).is_file());
+    Ok(dirs)
+}
+
+/// Reads the manifest in `dir`, with paths relative to the data dir
+fn read_manifest(data_dir: &Path, dir: &Path) -> Result<Vec<(PathBuf, String)>, Error> {
+    let manifest = data_dir.join(dir).join(MANIFEST);
+    let mut entries = Vec::new();
+    for (i, line) in BufReader::new(File::open(&manifest)?).lines().enumerate() {
+        let line = line?;
+        // `sha256sum` separates with a space and a space (text) or `*` (binary mode)
+        let (sum, path) = line
+            .split_once(' ')
+            .filter(|(sum, _)| sum.len() == 64)
+            .and_then(|(sum, rest)| Some((sum, rest.strip_prefix([' ', '*'])?)))
+            .ok_or_else(|| Error::InvalidManifest {
+                manifest: manifest.clone(),
+                line: i + 1,
+            })?;
+        entries.push((dir.join(path), sum.to_string()));
+    }
+    Ok(entries)
+}
+
+/// Checks the files of a data dir against the manifests written by [write]
+pub fn verify(data_dir: &Path) -> Result<Verification, Error> {
+    let mut expected = Vec::new();
+    for dir in manifests(data_dir)? {
+        expected.extend(read_manifest(data_dir, &dir)?);
+    }
+
+    let results: Vec<(PathBuf, Option<bool>)> = expected
+        .par_iter()
+        .map(|(file, sum)| {
+            let path = data_dir.join(file);
+            let matches = path
+                .exists()
+                .then(|| sha256(&path).map(|actual| actual == *sum))
+                .transpose()?;
+            Ok((file.clone(), matches))
+        })
+        .collect::<Result<_, Error>>()?;
+
+    let mut verification = Verification::default();
+    for (file, matches) in results {
+        match matches {
+            Some(true) => verification.ok += 1,
+            Some(false) => verification.mismatched.push(file),
+            None => verification.missing.push(file),
+        }
+    }
+
+    let listed: BTreeSet<&PathBuf> = expected.iter().map(|(file, _)| file).collect();
+    verification.unlisted = files(data_dir)?
+        .into_iter()
+        .filter(|file| !listed.contains(file))
+        .collect();
+
+    Ok(verification)
+}
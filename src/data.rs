@@ -1,31 +1,280 @@
-use crate::analyzer::{Project, Report};
-use crate::{CsvRepo, Repo};
+use crate::analyzer::dedup::{ContentHash, DedupReport};
+use crate::analyzer::effective::EffectiveStatus;
+use crate::analyzer::parents::ParentStatus;
+use crate::analyzer::{Chunk, Project, Report};
+use crate::metrics::METRICS;
+use crate::{project_dir, CsvRepo, Repo};
+use dashmap::DashSet;
 use indicatif::ProgressBar;
 use rayon::iter::{ParallelBridge, ParallelIterator};
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
-use std::io::{BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::Ordering::Relaxed;
 use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::{fs, io};
 use thiserror::Error;
+use tokio::sync::OnceCell;
 use tokio::task::spawn_blocking;
-use tracing::info;
+use tracing::{info, warn};
+use walkdir::WalkDir;
+
+pub mod sqlite;
+
+/// The code forge a data dir's repos are scraped from. Every forge has its own repo store, fetched
+/// list and poms dir, and its own last id in `state.json`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgeKind {
+    #[default]
+    Github,
+    Gitlab,
+}
+
+impl ForgeKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ForgeKind::Github => "github",
+            ForgeKind::Gitlab => "gitlab",
+        }
+    }
+
+    /// The name of a file or dir of this forge in a data dir, GitHub keeps the unprefixed names
+    /// from before other forges were supported (`poms`, `gitlab-poms`)
+    pub fn file_name(self, name: &str) -> String {
+        match self {
+            ForgeKind::Github => name.to_string(),
+            forge => format!("{}-{name}", forge.name()),
+        }
+    }
+
+    /// `github.csv`, `gitlab.csv`
+    pub fn csv_name(self) -> String {
+        format!("{}.csv", self.name())
+    }
+}
+
+/// Where the repos and their fetched status are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Storage {
+    /// `github.csv` (or the csv of another forge) and the `fetched` list
+    Csv,
+    /// A SQLite database, also listing the downloaded files per repo
+    Sqlite,
+}
 
 #[derive(Debug, Clone)]
 pub struct Data {
+    forge: ForgeKind,
     pom_dir: PathBuf,
-    github_csv: PathBuf,
+    /// [Manifest]s of the repos being downloaded, by project dir name
+    manifest_dir: PathBuf,
+    /// Conditional request validators of the forge's responses, see [crate::scraper::cache]
+    http_cache_dir: PathBuf,
+    /// The repo csv of the forge
+    csv: PathBuf,
+    /// The sqlite store of the forge, used if it exists
+    sqlite: PathBuf,
     fetched: PathBuf,
+    fetched_lock: Arc<Mutex<()>>,
+    effective_status: PathBuf,
+    releases: PathBuf,
+    parent_status: PathBuf,
+    packages: PathBuf,
+    tombstones: PathBuf,
+    renames: PathBuf,
+    content_hashes: PathBuf,
+    dedup_report: PathBuf,
+    errors_log: PathBuf,
+    errors_lock: Arc<Mutex<()>>,
     report: PathBuf,
 
-    state_cache: Arc<AtomicUsize>,
+    /// Last ids of all forges, so writing the state keeps the ones of other forges
+    state_cache: Arc<Mutex<Forges>>,
     state_path: PathBuf,
     state_file_lock: Arc<Mutex<()>>,
+    history: Arc<Mutex<Vec<ProgressPoint>>>,
+    snapshots: PathBuf,
 
     csv_lock: Arc<Mutex<()>>,
+    /// Ids of all repos in the csv, loaded on first use
+    known_ids: Arc<OnceCell<DashSet<String>>>,
+    /// Ids in the fetched list, loaded on first use
+    fetched_ids: Arc<OnceCell<DashSet<String>>>,
+    /// Used instead of the csv and fetched list for [Storage::Sqlite]
+    store: Option<Arc<sqlite::Store>>,
+}
+
+/// An entry of the structured error log `errors.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ErrorRecord {
+    /// Name of the project dir
+    pub project: String,
+    /// Id of the repo on the forge, for errors while scraping
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repo_id: Option<String>,
+    /// What failed, e.g. `effective-pom` or `download`
+    pub stage: String,
+    /// Class of the error, e.g. `http` or `rate_limit`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<String>,
+    /// Status of the failed HTTP response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub message: String,
+    /// The end of the output of the failed process
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// Whether the stage may succeed when tried again, see `rp retry-failed`
+    #[serde(default)]
+    pub retryable: bool,
+    /// Seconds since the unix epoch
+    #[serde(default)]
+    pub timestamp: u64,
+}
+
+impl ErrorRecord {
+    /// A record of an error that happened now, without details
+    pub fn new(project: String, stage: &str, message: String) -> Self {
+        ErrorRecord {
+            project,
+            repo_id: None,
+            stage: stage.to_string(),
+            kind: None,
+            status: None,
+            message,
+            output: None,
+            retryable: false,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+}
+
+/// Whether a repo publishes GitHub releases, stored in `releases.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReleaseStatus {
+    /// Name of the project dir
+    pub project: String,
+    pub has_releases: bool,
+    /// Amount of releases
+    #[serde(default)]
+    pub releases: usize,
+    /// Names of the jar and pom assets of the releases
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+}
+
+/// File in a project dir with how its files were downloaded
+pub const METADATA_FILE: &str = "metadata.json";
+
+/// Where and when the files of a repo were downloaded, stored as [METADATA_FILE] next to them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RepoMetadata {
+    /// The commit all files were downloaded at
+    #[serde(default)]
+    pub sha: Option<String>,
+    /// The branch that commit was resolved from
+    #[serde(default)]
+    pub default_branch: Option<String>,
+    /// Seconds since the unix epoch
+    #[serde(default)]
+    pub fetched_at: u64,
+    /// Whether the forge truncated the listing of the repo's files, which was listed per directory
+    #[serde(default)]
+    pub truncated: bool,
+    /// Seconds since the unix epoch `rp watch` last found the default branch still at `sha`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked_at: Option<u64>,
+}
+
+/// The files of a repo that are being downloaded, so an interrupted download can be resumed
+/// without listing the repo again. Removed once the repo is fetched.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub metadata: RepoMetadata,
+    /// Paths in the repo of the files to download
+    pub paths: Vec<String>,
+}
+
+/// The repos an owner published maven packages to GitHub Packages from, stored in `packages.jsonl`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackagesStatus {
+    pub owner: String,
+    /// Project dir names of the repos
+    pub repos: Vec<String>,
+}
+
+/// A repo renamed on its forge, logged in `renames.csv`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rename {
+    pub id: String,
+    pub old_name: String,
+    pub new_name: String,
+    /// Seconds since the unix epoch
+    pub renamed_at: u64,
+}
+
+/// A repo removed on request, stored in `tombstones.jsonl` so it isn't scraped again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub id: String,
+    pub name: String,
+    /// Seconds since the unix epoch
+    pub removed_at: u64,
+}
+
+/// Removes the lines of a jsonl file whose `project` is one of `projects`
+fn remove_project_lines(path: &Path, projects: &HashSet<String>) -> Result<(), Error> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let mut kept = String::new();
+    for line in fs::read_to_string(path)?.lines() {
+        let value: serde_json::Value = serde_json::from_str(line)?;
+        let project = value["project"].as_str().unwrap_or_default();
+        if !projects.contains(project) {
+            kept.push_str(line);
+            kept.push('\n');
+        }
+    }
+    fs::write(path, kept)?;
+
+    Ok(())
+}
+
+/// Copies the lines of a jsonl file whose `field` is one of `values`, returning how many
+pub fn copy_matching_lines(
+    from: &Path,
+    to: &Path,
+    field: &str,
+    values: &HashSet<String>,
+) -> Result<usize, Error> {
+    if !from.exists() {
+        return Ok(0);
+    }
+
+    let mut copied = String::new();
+    let mut count = 0;
+    for line in fs::read_to_string(from)?.lines() {
+        let value: serde_json::Value = serde_json::from_str(line)?;
+        if values.contains(value[field].as_str().unwrap_or_default()) {
+            copied.push_str(line);
+            copied.push('\n');
+            count += 1;
+        }
+    }
+    fs::write(to, copied)?;
+
+    Ok(count)
 }
 
 #[derive(Debug, Error)]
@@ -38,52 +287,492 @@ pub enum Error {
     InvalidPath(String),
     #[error("error accessing csv file")]
     Csv(#[from] csv::Error),
+    #[error("no state snapshot to roll back to")]
+    NoSnapshot,
+    #[error("error accessing the sqlite store")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("the data dir already uses sqlite storage")]
+    StorageMismatch,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct State {
     last_id: Forges,
+    #[serde(default)]
+    history: Vec<ProgressPoint>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Amount of state snapshots kept
+const SNAPSHOTS: usize = 20;
+
+/// Amount of progress points kept in full, a week of points recorded every 10 minutes
+const HISTORY: usize = 1024;
+
+/// What the repo store looked like when a snapshot was taken
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoreMetadata {
+    /// First line of the csv
+    pub header: Option<String>,
+    pub rows: usize,
+    pub bytes: u64,
+    /// Lines in the fetched list
+    pub fetched: usize,
+}
+
+/// A copy of `state.json` and the metadata of the repo store, stored in `snapshots/` whenever
+/// a command starts with a state that changed since the last snapshot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// Milliseconds since the unix epoch, identifies the snapshot
+    pub timestamp: u64,
+    pub state: serde_json::Value,
+    pub store: StoreMetadata,
+}
+
+impl Snapshot {
+    pub fn last_id(&self, forge: ForgeKind) -> u64 {
+        self.state["last_id"][forge.name()]
+            .as_u64()
+            .unwrap_or_default()
+    }
+}
+
+/// The scraping progress at some moment, periodically recorded in `state.json` by fetch-and-download
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProgressPoint {
+    /// Seconds since the unix epoch
+    pub timestamp: u64,
+    pub last_id: usize,
+    /// Repos stored in the csv
+    pub repos: usize,
+    /// Stored repos with poms
+    pub poms: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct Forges {
     github: usize,
+    #[serde(default)]
+    gitlab: usize,
+}
+
+impl Forges {
+    fn get(&self, forge: ForgeKind) -> usize {
+        match forge {
+            ForgeKind::Github => self.github,
+            ForgeKind::Gitlab => self.gitlab,
+        }
+    }
+
+    fn set(&mut self, forge: ForgeKind, id: usize) {
+        match forge {
+            ForgeKind::Github => self.github = id,
+            ForgeKind::Gitlab => self.gitlab = id,
+        }
+    }
+}
+
+/// `report.json` for full runs, `report.chunk-0-of-8.json` for partial ones
+fn chunked_file_name(stem: &str, extension: &str, chunk: Option<Chunk>) -> String {
+    match chunk {
+        Some(chunk) => format!(
+            "{stem}.chunk-{}-of-{}.{extension}",
+            chunk.index, chunk.total
+        ),
+        None => format!("{stem}.{extension}"),
+    }
+}
+
+/// Reads a report from either a report file or a data directory containing a report.json
+pub fn read_report_file(path: &Path) -> Result<Report, Error> {
+    let file = if path.is_dir() {
+        File::open(path.join("report.json"))?
+    } else {
+        File::open(path)?
+    };
+    let report = serde_json::from_reader(file)?;
+    Ok(report)
+}
+
+pub fn write_report_file(path: &Path, report: &Report) -> Result<(), Error> {
+    let file = File::create(path)?;
+    serde_json::to_writer(file, report)?;
+    Ok(())
+}
+
+/// Writes a `projects.json` one project at a time, so the analysis doesn't have to hold all of
+/// them. The array is written to a `.part` file that [ProjectWriter::finish] renames, readers never
+/// see an unfinished one.
+pub struct ProjectWriter {
+    path: PathBuf,
+    part: PathBuf,
+    writer: BufWriter<File>,
+    written: usize,
+}
+
+impl ProjectWriter {
+    pub fn create(path: PathBuf) -> Result<Self, Error> {
+        let part = path.with_extension("json.part");
+        let mut writer = BufWriter::new(File::create(&part)?);
+        writer.write_all(b"[")?;
+        Ok(Self {
+            path,
+            part,
+            writer,
+            written: 0,
+        })
+    }
+
+    pub fn write(&mut self, project: &Project) -> Result<(), Error> {
+        if self.written > 0 {
+            self.writer.write_all(b",")?;
+        }
+        self.writer.write_all(b"\n")?;
+        serde_json::to_writer(&mut self.writer, project)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Closes the array and moves it to its path, returning the amount of projects written
+    pub fn finish(mut self) -> Result<usize, Error> {
+        self.writer.write_all(b"\n]")?;
+        self.writer.flush()?;
+        fs::rename(&self.part, &self.path)?;
+        Ok(self.written)
+    }
+}
+
+fn csv_reader(path: &Path) -> Result<csv::Reader<File>, Error> {
+    // Rows appended by older versions can have less columns
+    let reader = csv::ReaderBuilder::new().flexible(true).from_path(path)?;
+    Ok(reader)
+}
+
+/// How much information a csv row carries, used to pick between duplicate rows
+fn completeness(repo: &CsvRepo) -> usize {
+    usize::from(repo.has_pom)
+        + usize::from(repo.license.is_some())
+        + usize::from(repo.stars.is_some())
+}
+
+/// Removes rows with an id or name that was seen before, keeping the most complete row
+/// at the position of the first occurrence.
+fn dedup_repos(repos: Vec<CsvRepo>) -> Vec<CsvRepo> {
+    let total = repos.len();
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+    let mut by_name: HashMap<String, usize> = HashMap::new();
+    let mut unique: Vec<CsvRepo> = Vec::with_capacity(total);
+    let (mut duplicate_ids, mut duplicate_names) = (0, 0);
+
+    for repo in repos {
+        let existing = match (by_id.get(&repo.id), by_name.get(&repo.name)) {
+            (Some(&i), _) => {
+                duplicate_ids += 1;
+                Some(i)
+            }
+            (None, Some(&i)) => {
+                duplicate_names += 1;
+                Some(i)
+            }
+            (None, None) => None,
+        };
+
+        // Both the id and the name of a duplicate point to the kept row, a row matched by one
+        // of them can still be repeated with the other
+        let i = existing.unwrap_or(unique.len());
+        by_id.insert(repo.id.clone(), i);
+        by_name.insert(repo.name.clone(), i);
+        match existing {
+            Some(i) => {
+                if completeness(&repo) > completeness(&unique[i]) {
+                    unique[i] = repo;
+                }
+            }
+            None => unique.push(repo),
+        }
+    }
+
+    if unique.len() != total {
+        warn!(
+            "Ignored {} duplicate csv rows ({duplicate_ids} by id, {duplicate_names} by name), {} unique repos remain",
+            total - unique.len(),
+            unique.len()
+        );
+    }
+
+    unique
+}
+
+/// Amount of lines in a file, counted without reading it at once
+pub fn count_lines(path: &Path) -> io::Result<usize> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let (mut lines, mut last) = (0, b'\n');
+    loop {
+        let buf = reader.fill_buf()?;
+        let Some(&end) = buf.last() else {
+            break;
+        };
+        lines += buf.iter().filter(|&&b| b == b'\n').count();
+        last = end;
+        let len = buf.len();
+        reader.consume(len);
+    }
+    // The last line doesn't need to end with a newline
+    Ok(lines + usize::from(last != b'\n'))
+}
+
+/// Rewrites the repository csv if its header doesn't match the current columns of [CsvRepo],
+/// so rows appended later line up with the header.
+fn migrate_csv(path: &Path) -> Result<(), Error> {
+    let mut expected = csv::Writer::from_writer(vec![]);
+    expected.serialize(CsvRepo::default())?;
+    let expected = String::from_utf8(expected.into_inner().unwrap()).unwrap();
+    let expected = expected.lines().next().unwrap_or_default();
+
+    let mut header = String::new();
+    BufReader::new(File::open(path)?).read_line(&mut header)?;
+    if header.trim_end() == expected {
+        return Ok(());
+    }
+
+    info!("Migrating {path:?} to the current csv columns");
+    let repos: Vec<CsvRepo> = csv_reader(path)?.deserialize().collect::<Result<_, _>>()?;
+    write_csv(path, &repos)
+}
+
+/// Replaces a repository csv, writing a temporary file first so a crash doesn't leave it truncated
+fn write_csv(path: &Path, repos: &[CsvRepo]) -> Result<(), Error> {
+    let mut tmp = path.to_path_buf();
+    tmp.set_extension("csv.tmp");
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(true)
+        .from_path(&tmp)?;
+    for repo in repos {
+        wtr.serialize(repo)?;
+    }
+    wtr.flush()?;
+    drop(wtr);
+    fs::rename(tmp, path)?;
+
+    Ok(())
 }
 
 impl Data {
     pub async fn new(base_dir: &Path) -> Result<Self, Error> {
+        Self::with_options(base_dir, ForgeKind::Github, None).await
+    }
+
+    /// Opens the repos of a forge in a data dir, by default with the storage they already use
+    pub async fn with_options(
+        base_dir: &Path,
+        forge: ForgeKind,
+        storage: Option<Storage>,
+    ) -> Result<Self, Error> {
         if !base_dir.exists() {
             tokio::fs::create_dir_all(base_dir).await?;
         }
-        let state_path = base_dir.join("state.json");
-        let state_cache = Arc::new(AtomicUsize::new(0));
-        if state_path.exists() {
-            let data = tokio::fs::read(&state_path).await?;
-            let state: State = serde_json::from_slice(&data)?;
-            state_cache.store(state.last_id.github, Ordering::SeqCst);
+        let mut data = Self::open_forge(base_dir, forge);
+        match storage {
+            Some(Storage::Csv) if data.store.is_some() => return Err(Error::StorageMismatch),
+            Some(Storage::Sqlite) if data.store.is_none() => {
+                data.store = Some(Arc::new(sqlite::Store::new(data.sqlite.clone())));
+            }
+            _ => {}
+        }
+
+        if data.state_path.exists() {
+            let state = tokio::fs::read(&data.state_path).await?;
+            let state: State = serde_json::from_slice(&state)?;
+            *data.state_cache.lock().unwrap() = state.last_id;
+            *data.history.lock().unwrap() = state.history;
         }
 
-        let fetched = base_dir.join("fetched");
-        if !fetched.exists() {
-            tokio::fs::File::create(&fetched).await?;
+        if data.store.is_none() && !data.fetched.exists() {
+            tokio::fs::File::create(&data.fetched).await?;
         }
 
-        Ok(Self {
-            pom_dir: base_dir.join("poms"),
-            github_csv: base_dir.join("github.csv"),
-            report: base_dir.join("report.json"),
-            fetched,
+        if data.store.is_none() && data.csv.exists() {
+            let csv = data.csv.clone();
+            spawn_blocking(move || migrate_csv(&csv)).await.unwrap()?;
+        }
+
+        Ok(data)
+    }
+
+    /// Opens the GitHub repos of a data dir for reading only, without creating or migrating any
+    /// files
+    pub fn open(base_dir: &Path) -> Self {
+        Self::open_forge(base_dir, ForgeKind::Github)
+    }
+
+    /// Opens the repos of the forge of `like` in another data dir, stored the same way
+    pub fn open_like(base_dir: &Path, like: &Data) -> Self {
+        let mut data = Self::open_forge(base_dir, like.forge);
+        if like.store.is_some() && data.store.is_none() {
+            data.store = Some(Arc::new(sqlite::Store::new(data.sqlite.clone())));
+        }
+        data
+    }
+
+    /// Opens the repos of a forge in a data dir for reading only
+    pub fn open_forge(base_dir: &Path, forge: ForgeKind) -> Self {
+        let sqlite = base_dir.join(forge.file_name(sqlite::FILE_NAME));
+        Self {
+            forge,
+            store: sqlite
+                .exists()
+                .then(|| Arc::new(sqlite::Store::new(sqlite.clone()))),
+            sqlite,
+            pom_dir: base_dir.join(forge.file_name("poms")),
+            manifest_dir: base_dir.join(forge.file_name("manifests")),
+            http_cache_dir: base_dir.join(forge.file_name("http-cache")),
+            csv: base_dir.join(forge.csv_name()),
+            report: base_dir.join(forge.file_name("report.json")),
+            fetched: base_dir.join(forge.file_name("fetched")),
+            effective_status: base_dir.join(forge.file_name("effective.jsonl")),
+            releases: base_dir.join(forge.file_name("releases.jsonl")),
+            parent_status: base_dir.join(forge.file_name("parents.jsonl")),
+            packages: base_dir.join("packages.jsonl"),
+            tombstones: base_dir.join(forge.file_name("tombstones.jsonl")),
+            renames: base_dir.join(forge.file_name("renames.csv")),
+            content_hashes: base_dir.join(forge.file_name("content-hashes.jsonl")),
+            dedup_report: base_dir.join(forge.file_name("dedup-report.json")),
+            errors_log: base_dir.join(forge.file_name("errors.jsonl")),
+            errors_lock: Default::default(),
+            fetched_lock: Default::default(),
             state_file_lock: Default::default(),
-            state_path,
-            state_cache,
+            history: Default::default(),
+            snapshots: base_dir.join("snapshots"),
+            state_path: base_dir.join("state.json"),
+            state_cache: Default::default(),
             csv_lock: Arc::new(Mutex::new(())),
-        })
+            known_ids: Default::default(),
+            fetched_ids: Default::default(),
+        }
+    }
+
+    pub fn pom_dir(&self) -> &Path {
+        &self.pom_dir
+    }
+
+    pub fn http_cache_dir(&self) -> &Path {
+        &self.http_cache_dir
+    }
+
+    pub fn forge(&self) -> ForgeKind {
+        self.forge
+    }
+
+    /// The effective pom outcomes of the forge
+    pub fn effective_status(&self) -> &Path {
+        &self.effective_status
+    }
+
+    /// The parent pom outcomes of the forge
+    pub fn parent_status(&self) -> &Path {
+        &self.parent_status
+    }
+
+    /// The error log of the forge
+    pub fn errors_log(&self) -> &Path {
+        &self.errors_log
+    }
+
+    /// The duplicate clusters of the forge
+    pub fn dedup_report(&self) -> &Path {
+        &self.dedup_report
+    }
+
+    /// The repo csv of the forge
+    pub fn csv(&self) -> &Path {
+        &self.csv
+    }
+
+    /// The file the repos are stored in, the csv or the sqlite database
+    pub fn repo_store(&self) -> &Path {
+        match &self.store {
+            Some(store) => store.path(),
+            None => &self.csv,
+        }
+    }
+
+    /// The files the repos and their fetched status are stored in, modified whenever they change
+    pub fn store_files(&self) -> Vec<PathBuf> {
+        match &self.store {
+            Some(store) => vec![
+                store.path().to_path_buf(),
+                // Written to first, the database only changes on checkpoints
+                PathBuf::from(format!("{}-wal", store.path().display())),
+            ],
+            None => vec![self.csv.clone(), self.fetched.clone()],
+        }
+    }
+
+    pub fn storage(&self) -> Storage {
+        match self.store {
+            Some(_) => Storage::Sqlite,
+            None => Storage::Csv,
+        }
+    }
+
+    pub fn get_project_dir(&self, repo: &Repo) -> PathBuf {
+        self.pom_dir.join(repo.path())
     }
 
     pub fn get_pom_path(&self, repo: &Repo, path: &str) -> PathBuf {
         self.pom_dir.join(repo.path()).join(path)
     }
 
+    fn manifest_path(&self, repo: &Repo) -> PathBuf {
+        self.manifest_dir.join(format!("{}.json", repo.path()))
+    }
+
+    /// The manifest of a repo whose download was interrupted
+    pub async fn read_manifest(&self, repo: &Repo) -> Result<Option<Manifest>, Error> {
+        match tokio::fs::read(self.manifest_path(repo)).await {
+            Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn write_manifest(&self, repo: &Repo, manifest: &Manifest) -> Result<(), Error> {
+        tokio::fs::create_dir_all(&self.manifest_dir).await?;
+        let path = self.manifest_path(repo);
+        let tmp = path.with_extension("json.part");
+        tokio::fs::write(&tmp, serde_json::to_vec(manifest)?).await?;
+        tokio::fs::rename(tmp, path).await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_manifest(&self, repo: &Repo) -> Result<(), Error> {
+        match tokio::fs::remove_file(self.manifest_path(repo)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The metadata of a repo with downloaded files, `None` for other repos
+    pub async fn read_metadata(&self, repo: &Repo) -> Result<Option<RepoMetadata>, Error> {
+        let path = self.get_project_dir(repo).join(METADATA_FILE);
+        match tokio::fs::read(path).await {
+            Ok(json) => Ok(Some(serde_json::from_slice(&json)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn write_metadata(&self, repo: &Repo, metadata: &RepoMetadata) -> Result<(), Error> {
+        let path = self.get_project_dir(repo).join(METADATA_FILE);
+        let tmp = path.with_extension("json.part");
+        let json = serde_json::to_vec_pretty(metadata)?;
+        tokio::fs::write(&tmp, json).await?;
+        tokio::fs::rename(tmp, path).await?;
+
+        Ok(())
+    }
+
     pub async fn write_pom(&self, repo: &Repo, path: &str, bytes: &[u8]) -> Result<(), Error> {
         let file_path = self.get_pom_path(repo, path);
         let parent = file_path
@@ -91,56 +780,309 @@ impl Data {
             .ok_or_else(|| Error::InvalidPath("No Parent".to_string()))?;
         tokio::fs::create_dir_all(parent).await?;
 
-        let mut f = File::create(file_path)?;
+        // Written to a temporary file first, so a crash can't leave a partial file that would be
+        // taken as downloaded
+        let mut tmp = file_path.clone().into_os_string();
+        tmp.push(".part");
+        let mut f = File::create(&tmp)?;
         f.write_all(bytes)?;
+        drop(f);
+        fs::rename(tmp, file_path)?;
+        METRICS.files_downloaded.fetch_add(1, Relaxed);
+        METRICS.bytes_written.fetch_add(bytes.len() as u64, Relaxed);
+
+        if let Some(store) = self.store.clone() {
+            let (id, path) = (repo.id.clone(), path.to_string());
+            spawn_blocking(move || store.record_file(&id, &path))
+                .await
+                .unwrap()?;
+        }
 
         Ok(())
     }
 
-    pub fn write_projects(&self, projects: &[Project]) -> Result<(), Error> {
-        let mut path = self.report.clone();
-        path.set_file_name("projects.json");
-        let file = File::create(path)?;
-        serde_json::to_writer(file, projects)?;
+    /// The files downloaded for a repo, only recorded with [Storage::Sqlite]
+    ///
+    /// Warning: this method blocks
+    pub fn files(&self, repo: &Repo) -> Result<Option<Vec<String>>, Error> {
+        match &self.store {
+            Some(store) => Ok(Some(store.files(&repo.id)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn write_projects(&self, projects: &[Project], chunk: Option<Chunk>) -> Result<(), Error> {
+        let mut writer = self.project_writer(chunk)?;
+        for project in projects {
+            writer.write(project)?;
+        }
+        writer.finish()?;
 
         Ok(())
     }
 
     /// Warning: this method blocks
-    pub fn write_report(&self, report: Report) -> Result<(), Error> {
-        let path = self.report.clone();
-        let file = File::create(path)?;
-        serde_json::to_writer(file, &report)?;
-        Ok(())
+    pub fn project_writer(&self, chunk: Option<Chunk>) -> Result<ProjectWriter, Error> {
+        ProjectWriter::create(self.output_path("projects", "json", chunk))
+    }
+
+    /// Path of an analyzer output file of the forge in the data dir, e.g. `projects.arrow` or
+    /// `gitlab-projects.arrow`
+    pub fn output_path(&self, stem: &str, extension: &str, chunk: Option<Chunk>) -> PathBuf {
+        let mut path = self.report.clone();
+        path.set_file_name(
+            self.forge
+                .file_name(&chunked_file_name(stem, extension, chunk)),
+        );
+        path
+    }
+
+    /// The projects of the last analysis, sorted by name. `projects.json` has them in the order
+    /// they finished.
+    pub fn read_projects(&self) -> Result<Vec<Project>, Error> {
+        let file = File::open(self.output_path("projects", "json", None))?;
+        let mut projects: Vec<Project> = serde_json::from_reader(file)?;
+        projects.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        Ok(projects)
+    }
+
+    /// Writes the projects of `chunks` into `projects.json`, one chunk at a time, returning the
+    /// amount of projects
+    ///
+    /// Warning: this method blocks
+    pub fn merge_projects(&self, chunks: &[Chunk]) -> Result<usize, Error> {
+        let mut writer = self.project_writer(None)?;
+        for &chunk in chunks {
+            let file = File::open(self.output_path("projects", "json", Some(chunk)))?;
+            let projects: Vec<Project> = serde_json::from_reader(BufReader::new(file))?;
+            for project in &projects {
+                writer.write(project)?;
+            }
+        }
+        writer.finish()
+    }
+
+    /// Warning: this method blocks
+    pub fn write_report(&self, report: Report, chunk: Option<Chunk>) -> Result<(), Error> {
+        write_report_file(&self.output_path("report", "json", chunk), &report)
     }
 
     pub fn read_report(&self) -> Result<Report, Error> {
-        let file = File::open(&self.report)?;
-        let report = serde_json::from_reader(file)?;
-        Ok(report)
+        read_report_file(&self.report)
+    }
+
+    /// Lists all partial reports written by chunked analysis runs
+    pub fn partial_reports(&self) -> Result<Vec<PathBuf>, Error> {
+        let dir = self
+            .report
+            .parent()
+            .ok_or_else(|| Error::InvalidPath("No Parent".to_string()))?;
+
+        let prefix = self.forge.file_name("report.chunk-");
+        let mut reports: Vec<PathBuf> = dir
+            .read_dir()?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix) && name.ends_with(".json"))
+            })
+            .collect();
+        reports.sort();
+
+        Ok(reports)
     }
 
     pub fn get_last_id(&self) -> Result<usize, Error> {
-        Ok(self.state_cache.load(Ordering::SeqCst))
+        Ok(self.state_cache.lock().unwrap().get(self.forge))
     }
 
     pub async fn set_last_id(&self, id: usize) -> Result<(), Error> {
-        self.state_cache.store(id, Ordering::SeqCst);
+        self.state_cache.lock().unwrap().set(self.forge, id);
+        self.write_state().await
+    }
+
+    /// Warning: this method blocks
+    pub fn store_metadata(&self) -> Result<StoreMetadata, Error> {
+        if let Some(store) = &self.store {
+            let (rows, fetched) = store.counts()?;
+            return Ok(StoreMetadata {
+                header: Some(sqlite::FILE_NAME.to_string()),
+                rows,
+                bytes: fs::metadata(store.path()).map(|m| m.len()).unwrap_or(0),
+                fetched,
+            });
+        }
 
+        let (header, rows, bytes) = if self.csv.exists() {
+            let mut header = String::new();
+            BufReader::new(File::open(&self.csv)?).read_line(&mut header)?;
+            let header = header.trim_end_matches(['\r', '\n']).to_string();
+            (
+                (!header.is_empty()).then_some(header),
+                count_lines(&self.csv)?.saturating_sub(1),
+                fs::metadata(&self.csv)?.len(),
+            )
+        } else {
+            (None, 0, 0)
+        };
+        let fetched = if self.fetched.exists() {
+            count_lines(&self.fetched)?
+        } else {
+            0
+        };
+
+        Ok(StoreMetadata {
+            header,
+            rows,
+            bytes,
+            fetched,
+        })
+    }
+
+    /// All snapshots, oldest first
+    ///
+    /// Warning: this method blocks
+    pub fn snapshots(&self) -> Result<Vec<Snapshot>, Error> {
+        if !self.snapshots.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut snapshots = Vec::new();
+        for entry in fs::read_dir(&self.snapshots)? {
+            let snapshot: Snapshot = serde_json::from_slice(&fs::read(entry?.path())?)?;
+            snapshots.push(snapshot);
+        }
+        snapshots.sort_by_key(|snapshot| snapshot.timestamp);
+
+        Ok(snapshots)
+    }
+
+    fn read_state_value(&self) -> Result<serde_json::Value, Error> {
+        Ok(serde_json::from_slice(&fs::read(&self.state_path)?)?)
+    }
+
+    /// Snapshots the state if it changed since the last snapshot, removing the oldest ones. The
+    /// commands changing the state or the repo store call this when they start.
+    ///
+    /// Warning: this method blocks
+    pub fn snapshot_state(&self) -> Result<(), Error> {
+        if !self.state_path.exists() {
+            return Ok(());
+        }
+        let _guard = self.state_file_lock.lock().unwrap();
+        let state = self.read_state_value()?;
+        let mut snapshots = self.snapshots()?;
+        if snapshots.last().is_some_and(|last| last.state == state) {
+            return Ok(());
+        }
+
+        let snapshot = Snapshot {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis() as u64,
+            state,
+            store: self.store_metadata()?,
+        };
+        fs::create_dir_all(&self.snapshots)?;
+        let path = self
+            .snapshots
+            .join(format!("state-{}.json", snapshot.timestamp));
+        serde_json::to_writer_pretty(File::create(path)?, &snapshot)?;
+        snapshots.push(snapshot);
+
+        let remove = snapshots.len().saturating_sub(SNAPSHOTS);
+        for old in &snapshots[..remove] {
+            fs::remove_file(self.snapshots.join(format!("state-{}.json", old.timestamp)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Restores `state.json` from the snapshot with this timestamp, or by default from the newest
+    /// one differing from the current state. The current state is snapshotted first, so the
+    /// rollback can be undone as well.
+    ///
+    /// Warning: this method blocks
+    pub fn rollback_state(&self, timestamp: Option<u64>) -> Result<Snapshot, Error> {
+        let current = if self.state_path.exists() {
+            self.snapshot_state()?;
+            Some(self.read_state_value()?)
+        } else {
+            None
+        };
+
+        let snapshot = self
+            .snapshots()?
+            .into_iter()
+            .rev()
+            .find(|snapshot| match timestamp {
+                Some(timestamp) => snapshot.timestamp == timestamp,
+                None => current.as_ref() != Some(&snapshot.state),
+            })
+            .ok_or(Error::NoSnapshot)?;
+
+        let _guard = self.state_file_lock.lock().unwrap();
+        let mut file = BufWriter::new(File::create(&self.state_path)?);
+        serde_json::to_writer_pretty(&mut file, &snapshot.state)?;
+        file.write_all(b"\n")?;
+        file.flush()?;
+
+        Ok(snapshot)
+    }
+
+    /// The last id and history in `state.json` as they are now, which another process scraping
+    /// into the data dir may have changed since it was opened
+    ///
+    /// Warning: this method blocks
+    pub fn read_progress(&self) -> Result<(usize, Vec<ProgressPoint>), Error> {
+        if !self.state_path.exists() {
+            return Ok((0, Vec::new()));
+        }
+        let state: State = serde_json::from_slice(&fs::read(&self.state_path)?)?;
+        Ok((state.last_id.get(self.forge), state.history))
+    }
+
+    pub fn history(&self) -> Vec<ProgressPoint> {
+        self.history.lock().unwrap().clone()
+    }
+
+    /// Adds a point to the history, thinning out the older half of it once it has more than
+    /// [HISTORY] points, so `state.json` stays small however long the scraper runs
+    pub async fn record_progress(&self, point: ProgressPoint) -> Result<(), Error> {
+        {
+            let mut history = self.history.lock().unwrap();
+            history.push(point);
+            if history.len() > HISTORY {
+                let older = history.len() / 2;
+                let mut i = 0;
+                history.retain(|_| {
+                    i += 1;
+                    i > older || i % 2 == 1
+                });
+            }
+        }
+        self.write_state().await
+    }
+
+    async fn write_state(&self) -> Result<(), Error> {
         let lock = self.state_file_lock.clone();
         let state_path = self.state_path.clone();
+        let state_cache = self.state_cache.clone();
+        let history = self.history.clone();
         spawn_blocking(move || -> Result<(), Error> {
             let guard = lock.lock().unwrap();
 
+            // Read under the file lock so a slower writer can't overwrite newer state
+            let state = State {
+                last_id: state_cache.lock().unwrap().clone(),
+                history: history.lock().unwrap().clone(),
+            };
             let file = File::create(state_path)?;
             let mut file = BufWriter::new(file);
-            serde_json::to_writer_pretty(
-                &mut file,
-                &State {
-                    last_id: Forges { github: id },
-                },
-            )?;
-            file.write_all(&[b'\n'])?;
+            serde_json::to_writer_pretty(&mut file, &state)?;
+            file.write_all(b"\n")?;
 
             drop(guard);
 
@@ -152,19 +1094,78 @@ impl Data {
         Ok(())
     }
 
+    async fn known_ids(&self) -> Result<&DashSet<String>, Error> {
+        self.known_ids
+            .get_or_try_init(|| async {
+                let csv_path = self.csv.clone();
+                let data = self.clone();
+                spawn_blocking(move || -> Result<DashSet<String>, Error> {
+                    let tombstones = data.read_tombstones()?;
+                    let ids = DashSet::new();
+                    if let Some(store) = &data.store {
+                        for id in store.ids()? {
+                            ids.insert(id);
+                        }
+                    } else if csv_path.exists() {
+                        for record in csv_reader(&csv_path)?.deserialize() {
+                            let record: CsvRepo = record?;
+                            ids.insert(record.id);
+                        }
+                    }
+                    // Removed repos count as known so they aren't scraped again
+                    for tombstone in tombstones.iter() {
+                        ids.insert(tombstone.id.clone());
+                    }
+                    ids.shrink_to_fit();
+                    info!("Loaded {} known repository ids", ids.len());
+
+                    Ok(ids)
+                })
+                .await
+                .unwrap()
+            })
+            .await
+    }
+
+    async fn fetched_ids(&self) -> Result<&DashSet<String>, Error> {
+        self.fetched_ids
+            .get_or_try_init(|| async {
+                let data = self.clone();
+                spawn_blocking(move || Ok(data.read_fetched()?.into_iter().collect()))
+                    .await
+                    .unwrap()
+            })
+            .await
+    }
+
+    /// Whether a repository with this (node) id is already in the csv
+    pub async fn is_known_repo(&self, id: &str) -> Result<bool, Error> {
+        Ok(self.known_ids().await?.contains(id))
+    }
+
     pub async fn store_repo(&self, repo: CsvRepo) -> Result<(), Error> {
+        if let Some(known) = self.known_ids.get() {
+            known.insert(repo.id.clone());
+        }
+
+        if let Some(store) = self.store.clone() {
+            return spawn_blocking(move || Ok(store.store_repo(&repo)?))
+                .await
+                .unwrap();
+        }
+
         let lock = self.csv_lock.clone();
-        let github_csv = self.github_csv.clone();
+        let csv_path = self.csv.clone();
         spawn_blocking(move || -> Result<(), Error> {
             let guard = lock.lock().unwrap();
 
-            let mut csv = if github_csv.exists() {
-                let file = OpenOptions::new().append(true).open(&github_csv)?;
+            let mut csv = if csv_path.exists() {
+                let file = OpenOptions::new().append(true).open(&csv_path)?;
                 csv::WriterBuilder::new()
                     .has_headers(false)
                     .from_writer(file)
             } else {
-                let file = File::create(&github_csv)?;
+                let file = File::create(&csv_path)?;
                 csv::WriterBuilder::new()
                     .has_headers(true)
                     .from_writer(file)
@@ -181,36 +1182,196 @@ impl Data {
         Ok(())
     }
 
+    /// Warning: this method blocks
+    pub fn read_repos(&self) -> Result<Vec<CsvRepo>, Error> {
+        if let Some(store) = &self.store {
+            return Ok(store.repos()?);
+        }
+        // Nothing was scraped into a new data dir (or for a new forge) yet
+        if !self.csv.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut rdr = csv_reader(&self.csv)?;
+        let repos = rdr.deserialize().collect::<Result<_, _>>()?;
+        Ok(dedup_repos(repos))
+    }
+
+    /// Overwrites the repository csv
+    ///
+    /// Warning: this method blocks
+    pub fn write_repos(&self, repos: &[CsvRepo]) -> Result<(), Error> {
+        if let Some(store) = &self.store {
+            return Ok(store.replace_repos(repos)?);
+        }
+
+        let _guard = self.csv_lock.lock().unwrap();
+        write_csv(&self.csv, repos)
+    }
+
+    /// Ids of the fetched repos
+    ///
+    /// Warning: this method blocks
+    pub fn read_fetched(&self) -> Result<HashSet<String>, Error> {
+        if let Some(store) = &self.store {
+            return Ok(store.fetched_ids()?.into_iter().collect());
+        }
+        if !self.fetched.exists() {
+            return Ok(HashSet::new());
+        }
+        Ok(fs::read_to_string(&self.fetched)?
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+
+    /// Replaces the repos with a subset of the repos of another data dir, with their fetched
+    /// status and downloaded files
+    ///
+    /// Warning: this method blocks
+    pub fn write_subset(&self, from: &Data, repos: &[CsvRepo]) -> Result<(), Error> {
+        let fetched = from.read_fetched()?;
+        let fetched: Vec<&str> = repos
+            .iter()
+            .map(|repo| repo.id.as_str())
+            .filter(|id| fetched.contains(*id))
+            .collect();
+
+        match &self.store {
+            Some(store) => {
+                let mut files = Vec::new();
+                if let Some(from) = &from.store {
+                    for id in &fetched {
+                        files.extend(
+                            from.files(id)?
+                                .into_iter()
+                                .map(|path| (id.to_string(), path)),
+                        );
+                    }
+                }
+                store.replace_repos(repos)?;
+                store.import(&[], &fetched, &files)?;
+            }
+            None => {
+                self.write_repos(repos)?;
+                let mut lines = fetched.join("\n");
+                if !lines.is_empty() {
+                    lines.push('\n');
+                }
+                fs::write(&self.fetched, lines)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Copies the state and the records of the given projects (and owners, for packages) into
+    /// another data dir
+    ///
+    /// Warning: this method blocks
+    pub fn copy_records(
+        &self,
+        out: &Data,
+        projects: &HashSet<String>,
+        owners: &HashSet<String>,
+    ) -> Result<(), Error> {
+        for (from, to) in [
+            (&self.state_path, &out.state_path),
+            (&self.tombstones, &out.tombstones),
+        ] {
+            if from.exists() {
+                fs::copy(from, to)?;
+            }
+        }
+        for (from, to) in [
+            (&self.effective_status, &out.effective_status),
+            (&self.releases, &out.releases),
+            (&self.parent_status, &out.parent_status),
+            (&self.content_hashes, &out.content_hashes),
+            (&self.errors_log, &out.errors_log),
+        ] {
+            copy_matching_lines(from, to, "project", projects)?;
+        }
+        copy_matching_lines(&self.packages, &out.packages, "owner", owners)?;
+        Ok(())
+    }
+
     pub async fn get_non_fetched_repos(&self) -> Result<Vec<CsvRepo>, Error> {
+        if let Some(store) = self.store.clone() {
+            return spawn_blocking(move || Ok(store.non_fetched()?))
+                .await
+                .unwrap();
+        }
+
         let fetched = self.fetched.clone();
-        let github_csv = self.github_csv.clone();
+        let csv_path = self.csv.clone();
         spawn_blocking(move || -> Result<Vec<CsvRepo>, Error> {
             let done_str = fs::read_to_string(fetched)?;
             let done: HashSet<_> = done_str.lines().collect();
 
-            let mut rdr = csv::Reader::from_path(github_csv)?;
-            let mut repos = Vec::new();
+            let repos: Vec<CsvRepo> = csv_reader(&csv_path)?
+                .deserialize()
+                .collect::<Result<_, _>>()?;
 
-            for record in rdr.deserialize() {
-                let record: CsvRepo = record?;
-                if !done.contains(record.id.as_str()) {
-                    repos.push(record);
-                }
-            }
-
-            Ok(repos)
+            Ok(dedup_repos(repos)
+                .into_iter()
+                .filter(|repo| !done.contains(repo.id.as_str()))
+                .collect())
         })
         .await
         .unwrap()
     }
 
     pub async fn mark_fetched(&self, repo: &Repo) -> Result<(), Error> {
+        if let Some(store) = self.store.clone() {
+            let id = repo.id.clone();
+            return spawn_blocking(move || Ok(store.mark_fetched(&id)?))
+                .await
+                .unwrap();
+        }
+
+        // Repos fetched again, by retry-failed or watch, are only listed once
+        if !self.fetched_ids().await?.insert(repo.id.clone()) {
+            return Ok(());
+        }
         let fetched = self.fetched.clone();
-        let id = repo.id.clone();
+        let lock = self.fetched_lock.clone();
+        let line = format!("{}\n", repo.id);
         spawn_blocking(move || -> Result<(), Error> {
+            // Repos are fetched concurrently, lines mustn't interleave
+            let _guard = lock.lock().unwrap();
             let mut f = OpenOptions::new().append(true).open(&fetched)?;
-            f.write_all(id.as_bytes())?;
-            f.write_all("\n".as_bytes())?;
+            f.write_all(line.as_bytes())?;
+
+            Ok(())
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Latest effective pom build status per project dir name
+    ///
+    /// Warning: this method blocks
+    pub fn read_effective_status(&self) -> Result<HashMap<String, EffectiveStatus>, Error> {
+        if !self.effective_status.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let mut statuses = HashMap::new();
+        for line in fs::read_to_string(&self.effective_status)?.lines() {
+            let status: EffectiveStatus = serde_json::from_str(line)?;
+            statuses.insert(status.project.clone(), status);
+        }
+
+        Ok(statuses)
+    }
+
+    pub async fn record_effective_status(&self, status: &EffectiveStatus) -> Result<(), Error> {
+        let path = self.effective_status.clone();
+        let mut line = serde_json::to_vec(status)?;
+        line.push(b'\n');
+        spawn_blocking(move || -> Result<(), Error> {
+            let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+            f.write_all(&line)?;
 
             Ok(())
         })
@@ -218,20 +1379,449 @@ impl Data {
         .unwrap()
     }
 
+    /// Latest parent fetching status per project dir name
+    ///
+    /// Warning: this method blocks
+    pub fn read_parent_status(&self) -> Result<HashMap<String, ParentStatus>, Error> {
+        if !self.parent_status.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let mut statuses = HashMap::new();
+        for line in fs::read_to_string(&self.parent_status)?.lines() {
+            let status: ParentStatus = serde_json::from_str(line)?;
+            statuses.insert(status.project.clone(), status);
+        }
+
+        Ok(statuses)
+    }
+
+    pub async fn record_parent_status(&self, status: &ParentStatus) -> Result<(), Error> {
+        let path = self.parent_status.clone();
+        let mut line = serde_json::to_vec(status)?;
+        line.push(b'\n');
+        spawn_blocking(move || -> Result<(), Error> {
+            let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+            f.write_all(&line)?;
+
+            Ok(())
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Overwrites the content hashes of the projects
+    ///
+    /// Warning: this method blocks
+    pub fn write_content_hashes(&self, hashes: &[ContentHash]) -> Result<(), Error> {
+        let mut f = BufWriter::new(File::create(&self.content_hashes)?);
+        for hash in hashes {
+            serde_json::to_writer(&mut f, hash)?;
+            f.write_all(b"\n")?;
+        }
+        f.flush()?;
+
+        Ok(())
+    }
+
+    /// Warning: this method blocks
+    pub fn write_dedup_report(&self, report: &DedupReport) -> Result<(), Error> {
+        let f = BufWriter::new(File::create(&self.dedup_report)?);
+        serde_json::to_writer_pretty(f, report)?;
+
+        Ok(())
+    }
+
+    /// The clusters of duplicate projects, `None` if `rp dedup` didn't run
+    ///
+    /// Warning: this method blocks
+    pub fn read_dedup_report(&self) -> Result<Option<DedupReport>, Error> {
+        if !self.dedup_report.exists() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(
+            &self.dedup_report,
+        )?)?))
+    }
+
+    /// The GitHub releases of the repos, per project dir name
+    ///
+    /// Warning: this method blocks
+    pub fn read_releases(&self) -> Result<HashMap<String, ReleaseStatus>, Error> {
+        if !self.releases.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let mut releases = HashMap::new();
+        for line in fs::read_to_string(&self.releases)?.lines() {
+            let status: ReleaseStatus = serde_json::from_str(line)?;
+            releases.insert(status.project.clone(), status);
+        }
+
+        Ok(releases)
+    }
+
+    pub async fn record_releases(&self, status: &ReleaseStatus) -> Result<(), Error> {
+        let path = self.releases.clone();
+        let mut line = serde_json::to_vec(status)?;
+        line.push(b'\n');
+        spawn_blocking(move || -> Result<(), Error> {
+            let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+            f.write_all(&line)?;
+
+            Ok(())
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Project dir names of the repos with GitHub Packages maven packages, per owner
+    ///
+    /// Warning: this method blocks
+    pub fn read_packages(&self) -> Result<HashMap<String, HashSet<String>>, Error> {
+        if !self.packages.exists() {
+            return Ok(HashMap::new());
+        }
+
+        let mut packages = HashMap::new();
+        for line in fs::read_to_string(&self.packages)?.lines() {
+            let status: PackagesStatus = serde_json::from_str(line)?;
+            packages.insert(status.owner, status.repos.into_iter().collect());
+        }
+
+        Ok(packages)
+    }
+
+    pub async fn record_packages(&self, status: &PackagesStatus) -> Result<(), Error> {
+        let path = self.packages.clone();
+        let mut line = serde_json::to_vec(status)?;
+        line.push(b'\n');
+        spawn_blocking(move || -> Result<(), Error> {
+            let mut f = OpenOptions::new().create(true).append(true).open(path)?;
+            f.write_all(&line)?;
+
+            Ok(())
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Warning: this method blocks
+    pub fn read_tombstones(&self) -> Result<Vec<Tombstone>, Error> {
+        if !self.tombstones.exists() {
+            return Ok(Vec::new());
+        }
+
+        fs::read_to_string(&self.tombstones)?
+            .lines()
+            .map(|line| Ok(serde_json::from_str(line)?))
+            .collect()
+    }
+
+    /// Removes all data of the repos with this name (`owner/repo` or `owner.repo`) or id: their
+    /// rows in the csv and fetched list, project dir and entries in the jsonl ledgers.
+    /// The removals are recorded as tombstones so the repos aren't scraped again.
+    ///
+    /// Warning: this method blocks
+    pub fn remove_repo(&self, name_or_id: &str) -> Result<Vec<Tombstone>, Error> {
+        let removed = match &self.store {
+            Some(store) => store.remove_repos(name_or_id)?,
+            None => self.remove_csv_repos(name_or_id)?,
+        };
+        if removed.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let projects: HashSet<String> =
+            removed.iter().map(|repo| project_dir(&repo.name)).collect();
+        for project in &projects {
+            let dir = self.pom_dir.join(project);
+            match fs::symlink_metadata(&dir) {
+                // Subsets link to the project dirs of the full data dir
+                Ok(meta) if meta.is_symlink() => fs::remove_file(dir)?,
+                Ok(_) => fs::remove_dir_all(dir)?,
+                Err(_) => {}
+            }
+            let manifest = self.manifest_dir.join(format!("{project}.json"));
+            if manifest.exists() {
+                fs::remove_file(manifest)?;
+            }
+        }
+        for ledger in [
+            &self.effective_status,
+            &self.releases,
+            &self.parent_status,
+            &self.content_hashes,
+            &self.errors_log,
+        ] {
+            remove_project_lines(ledger, &projects)?;
+        }
+        if self.packages.exists() {
+            let mut lines = String::new();
+            for line in fs::read_to_string(&self.packages)?.lines() {
+                let mut status: PackagesStatus = serde_json::from_str(line)?;
+                status.repos.retain(|repo| !projects.contains(repo));
+                lines.push_str(&serde_json::to_string(&status)?);
+                lines.push('\n');
+            }
+            fs::write(&self.packages, lines)?;
+        }
+
+        let removed_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let tombstones: Vec<Tombstone> = removed
+            .into_iter()
+            .map(|repo| Tombstone {
+                id: repo.id,
+                name: repo.name,
+                removed_at,
+            })
+            .collect();
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.tombstones)?;
+        for tombstone in &tombstones {
+            serde_json::to_writer(&mut f, tombstone)?;
+            f.write_all(b"\n")?;
+            if let Some(known) = self.known_ids.get() {
+                known.insert(tombstone.id.clone());
+            }
+        }
+
+        Ok(tombstones)
+    }
+
+    /// Stores a repo renamed on the forge under its new name: in the repo store, and by moving its
+    /// project dir and manifest. The rename is logged in `renames.csv`.
+    ///
+    /// Warning: this method blocks
+    pub fn rename_repo(&self, repo: &Repo, name: &str) -> Result<(), Error> {
+        // Held while rewriting the csv, so repos stored in the meantime aren't lost
+        let _guard = self.csv_lock.lock().unwrap();
+
+        match &self.store {
+            Some(store) => store.rename_repo(&repo.id, name)?,
+            None => {
+                let mut repos: Vec<CsvRepo> = csv_reader(&self.csv)?
+                    .deserialize()
+                    .collect::<Result<_, _>>()?;
+                for stored in repos.iter_mut().filter(|stored| stored.id == repo.id) {
+                    stored.name = name.to_string();
+                }
+                write_csv(&self.csv, &repos)?;
+            }
+        }
+
+        let renamed = Repo {
+            id: repo.id.clone(),
+            name: name.to_string(),
+        };
+        for (from, to) in [
+            (self.get_project_dir(repo), self.get_project_dir(&renamed)),
+            (self.manifest_path(repo), self.manifest_path(&renamed)),
+        ] {
+            // Subsets link to the project dirs of the full data dir, the link is moved
+            if fs::symlink_metadata(&from).is_err() {
+                continue;
+            }
+            if fs::symlink_metadata(&to).is_ok() {
+                warn!(
+                    "Not moving {from:?} of renamed {} to existing {to:?}",
+                    repo.name
+                );
+                continue;
+            }
+            fs::rename(from, to)?;
+        }
+
+        let exists = self.renames.exists();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.renames)?;
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(!exists)
+            .from_writer(file);
+        wtr.serialize(Rename {
+            id: repo.id.clone(),
+            old_name: repo.name.clone(),
+            new_name: name.to_string(),
+            renamed_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        })?;
+        wtr.flush()?;
+
+        Ok(())
+    }
+
+    /// Removes the repos from the csv and fetched list, returning them
+    fn remove_csv_repos(&self, name_or_id: &str) -> Result<Vec<CsvRepo>, Error> {
+        let repos: Vec<CsvRepo> = csv_reader(&self.csv)?
+            .deserialize()
+            .collect::<Result<_, _>>()?;
+        let (removed, kept): (Vec<_>, Vec<_>) = repos.into_iter().partition(|repo| {
+            repo.id == name_or_id
+                || repo.name == name_or_id
+                || project_dir(&repo.name) == name_or_id
+        });
+        if removed.is_empty() {
+            return Ok(removed);
+        }
+        self.write_repos(&kept)?;
+
+        let ids: HashSet<&str> = removed.iter().map(|repo| repo.id.as_str()).collect();
+        let fetched: String = fs::read_to_string(&self.fetched)?
+            .lines()
+            .filter(|id| !ids.contains(id))
+            .flat_map(|id| [id, "\n"])
+            .collect();
+        fs::write(&self.fetched, fetched)?;
+        if let Some(fetched_ids) = self.fetched_ids.get() {
+            fetched_ids.retain(|id| !ids.contains(id.as_str()));
+        }
+
+        Ok(removed)
+    }
+
+    /// Imports the csv, the fetched list and the files in the project dirs into a new
+    /// sqlite store, which the data dir uses from then on. The csv files are left in place.
+    ///
+    /// Warning: this method blocks
+    pub fn migrate_to_sqlite(&self) -> Result<(usize, usize, usize), Error> {
+        if self.store.is_some() {
+            return Err(Error::StorageMismatch);
+        }
+
+        let repos = self.read_repos()?;
+        let fetched = if self.fetched.exists() {
+            fs::read_to_string(&self.fetched)?
+        } else {
+            String::new()
+        };
+        let fetched: Vec<&str> = fetched.lines().collect();
+
+        let mut files = Vec::new();
+        for repo in repos.iter().filter(|repo| repo.has_pom) {
+            let dir = self.pom_dir.join(project_dir(&repo.name));
+            for entry in WalkDir::new(&dir).follow_links(true) {
+                let Ok(entry) = entry else {
+                    continue;
+                };
+                if entry.file_type().is_file() {
+                    let path = entry.path().strip_prefix(&dir).unwrap();
+                    files.push((repo.id.clone(), path.to_string_lossy().into_owned()));
+                }
+            }
+        }
+
+        // Imported into a temporary file first, an interrupted import shouldn't switch storage
+        let path = self.sqlite.clone();
+        let tmp = path.with_extension("sqlite.new");
+        if tmp.exists() {
+            fs::remove_file(&tmp)?;
+        }
+        let (repos, fetched) = sqlite::Store::new(tmp.clone()).import(&repos, &fetched, &files)?;
+        fs::rename(tmp, path)?;
+
+        Ok((repos, fetched, files.len()))
+    }
+
+    /// Appends to the structured error log
+    ///
+    /// Warning: this method blocks
+    pub fn record_error(&self, record: &ErrorRecord) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let _guard = self.errors_lock.lock().unwrap();
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.errors_log)?;
+        f.write_all(&line)?;
+
+        Ok(())
+    }
+
+    /// The structured error log, oldest first
+    ///
+    /// Warning: this method blocks
+    pub fn read_errors(&self) -> Result<Vec<ErrorRecord>, Error> {
+        let _guard = self.errors_lock.lock().unwrap();
+        if !self.errors_log.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut records = Vec::new();
+        for line in fs::read_to_string(&self.errors_log)?.lines() {
+            records.push(serde_json::from_str(line)?);
+        }
+
+        Ok(records)
+    }
+
+    /// Drops the errors of a stage recorded before `before` for the repos with these ids from the
+    /// error log, after they were retried successfully
+    ///
+    /// Warning: this method blocks
+    pub fn clear_errors(
+        &self,
+        stage: &str,
+        repo_ids: &HashSet<String>,
+        before: u64,
+    ) -> Result<(), Error> {
+        let _guard = self.errors_lock.lock().unwrap();
+        if !self.errors_log.exists() {
+            return Ok(());
+        }
+
+        let mut kept = String::new();
+        for line in fs::read_to_string(&self.errors_log)?.lines() {
+            let record: ErrorRecord = serde_json::from_str(line)?;
+            let cleared = record.stage == stage
+                && record.timestamp < before
+                && record
+                    .repo_id
+                    .as_ref()
+                    .is_some_and(|id| repo_ids.contains(id));
+            if !cleared {
+                kept.push_str(line);
+                kept.push('\n');
+            }
+        }
+        fs::write(&self.errors_log, kept)?;
+
+        Ok(())
+    }
+
     pub async fn update_csv_has_pom(&self) -> Result<(), Error> {
         info!("Updating csv from filesystem");
-        let csv = self.github_csv.clone();
-        let mut new_csv = self.github_csv.clone();
-        new_csv.set_extension("csv.new");
-        if new_csv.exists() {
-            tokio::fs::remove_file(&new_csv).await?;
-        }
+        // Project dirs are named like [Repo::path]
         let dirs: HashSet<String> = self
             .get_project_dirs()
             .await?
             .into_iter()
-            .map(|el| el.to_string_lossy().to_string())
+            .filter_map(|dir| Some(dir.file_name()?.to_string_lossy().to_string()))
             .collect();
+        if let Some(store) = self.store.clone() {
+            let updated = spawn_blocking(move || store.set_has_pom(&dirs))
+                .await
+                .unwrap()?;
+            info!("Marked {updated} repos as having poms");
+            return Ok(());
+        }
+
+        let csv = self.csv.clone();
+        let mut new_csv = self.csv.clone();
+        new_csv.set_extension("csv.new");
+        if new_csv.exists() {
+            tokio::fs::remove_file(&new_csv).await?;
+        }
 
         let spinner = ProgressBar::new(dirs.len() as u64);
 
@@ -239,15 +1829,14 @@ impl Data {
 
         let new_path = new_csv.clone();
         spawn_blocking(move || -> Result<(), Error> {
-            let mut rdr = csv::Reader::from_path(&csv)?;
+            let repos: Vec<CsvRepo> = csv_reader(&csv)?.deserialize().collect::<Result<_, _>>()?;
             let mut wtr = csv::WriterBuilder::new()
                 .has_headers(true)
                 .from_path(new_path)?;
 
-            for record in rdr.deserialize() {
+            for mut csv_record in dedup_repos(repos) {
                 spinner.tick();
-                let mut csv_record: CsvRepo = record?;
-                let path = csv_record.name.replace('/', ".");
+                let path = project_dir(&csv_record.name);
                 csv_record.has_pom = csv_record.has_pom || dirs.contains(&path);
                 if csv_record.has_pom {
                     spinner.inc(1);
@@ -263,7 +1852,7 @@ impl Data {
         .await
         .unwrap()?;
 
-        tokio::fs::rename(new_csv, &self.github_csv).await?;
+        tokio::fs::rename(new_csv, &self.csv).await?;
 
         info!("consolidated CSV successfully");
 
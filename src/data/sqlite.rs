@@ -0,0 +1,327 @@
+//! Repo store in a SQLite database, for data dirs with millions of repos where rewriting the csv
+//! and the fetched list gets slow.
+
+use crate::{project_dir, CsvRepo};
+use rusqlite::{params, Connection, OptionalExtension, ToSql};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const FILE_NAME: &str = "repos.sqlite";
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    CREATE TABLE IF NOT EXISTS repos (
+        id TEXT PRIMARY KEY,
+        name TEXT NOT NULL,
+        has_pom INTEGER NOT NULL,
+        license TEXT
+    );
+    CREATE INDEX IF NOT EXISTS repos_name ON repos (name);
+    CREATE TABLE IF NOT EXISTS fetched (
+        id TEXT PRIMARY KEY,
+        fetched_at INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS files (
+        repo_id TEXT NOT NULL,
+        path TEXT NOT NULL,
+        PRIMARY KEY (repo_id, path)
+    );
+";
+
+/// Columns of `repos` added after it was created, added to older databases when connecting
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("stars", "INTEGER"),
+    ("forks", "INTEGER"),
+    ("created_at", "TEXT"),
+    ("disk_usage", "INTEGER"),
+];
+
+/// Keeps the most complete information when a repo is stored again, like `dedup_repos` does for
+/// the csv
+const UPSERT: &str = "
+    INSERT INTO repos (id, name, has_pom, license, stars, forks, created_at, disk_usage)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+    ON CONFLICT (id) DO UPDATE SET
+        name = excluded.name,
+        has_pom = has_pom OR excluded.has_pom,
+        license = coalesce(excluded.license, license),
+        stars = coalesce(excluded.stars, stars),
+        forks = coalesce(excluded.forks, forks),
+        created_at = coalesce(excluded.created_at, created_at),
+        disk_usage = coalesce(excluded.disk_usage, disk_usage)
+";
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+fn repo(row: &rusqlite::Row) -> rusqlite::Result<CsvRepo> {
+    Ok(CsvRepo {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        has_pom: row.get(2)?,
+        license: row.get(3)?,
+        stars: row.get(4)?,
+        forks: row.get(5)?,
+        created_at: row.get(6)?,
+        disk_usage: row.get(7)?,
+    })
+}
+
+/// The parameters of [UPSERT]
+fn upsert_params(repo: &CsvRepo) -> [&dyn ToSql; 8] {
+    [
+        &repo.id,
+        &repo.name,
+        &repo.has_pom,
+        &repo.license,
+        &repo.stars,
+        &repo.forks,
+        &repo.created_at,
+        &repo.disk_usage,
+    ]
+}
+
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info('repos')")?;
+    let columns: HashSet<String> = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    for (column, kind) in ADDED_COLUMNS {
+        if !columns.contains(*column) {
+            conn.execute(&format!("ALTER TABLE repos ADD COLUMN {column} {kind}"), [])?;
+        }
+    }
+    Ok(())
+}
+
+/// The database of a data dir, connected on first use
+#[derive(Debug)]
+pub struct Store {
+    path: PathBuf,
+    connection: Mutex<Option<Connection>>,
+}
+
+impl Store {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            connection: Mutex::new(None),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn connection(&self) -> rusqlite::Result<MutexGuard<'_, Option<Connection>>> {
+        let mut connection = self.connection.lock().unwrap();
+        if connection.is_none() {
+            let conn = Connection::open(&self.path)?;
+            conn.execute_batch(SCHEMA)?;
+            migrate(&conn)?;
+            *connection = Some(conn);
+        }
+        Ok(connection)
+    }
+
+    /// Runs `f` in a transaction
+    fn transaction<T>(
+        &self,
+        f: impl FnOnce(&rusqlite::Transaction) -> rusqlite::Result<T>,
+    ) -> rusqlite::Result<T> {
+        let mut connection = self.connection()?;
+        let tx = connection.as_mut().unwrap().transaction()?;
+        let result = f(&tx)?;
+        tx.commit()?;
+        Ok(result)
+    }
+
+    pub fn ids(&self) -> rusqlite::Result<Vec<String>> {
+        self.transaction(|tx| {
+            let mut stmt = tx.prepare("SELECT id FROM repos")?;
+            let ids = stmt.query_map([], |row| row.get(0))?.collect();
+            ids
+        })
+    }
+
+    pub fn store_repo(&self, repo: &CsvRepo) -> rusqlite::Result<()> {
+        self.transaction(|tx| {
+            tx.execute(UPSERT, upsert_params(repo))?;
+            Ok(())
+        })
+    }
+
+    /// All repos in the order they were stored
+    pub fn repos(&self) -> rusqlite::Result<Vec<CsvRepo>> {
+        self.transaction(|tx| {
+            let mut stmt =
+                tx.prepare("SELECT id, name, has_pom, license, stars, forks, created_at, disk_usage FROM repos ORDER BY rowid")?;
+            let repos = stmt.query_map([], repo)?.collect();
+            repos
+        })
+    }
+
+    /// Replaces all repos, keeping the fetched status of the ones still present
+    pub fn replace_repos(&self, repos: &[CsvRepo]) -> rusqlite::Result<()> {
+        self.transaction(|tx| {
+            tx.execute("DELETE FROM repos", [])?;
+            let mut stmt = tx.prepare(UPSERT)?;
+            for repo in repos {
+                stmt.execute(upsert_params(repo))?;
+            }
+            tx.execute(
+                "DELETE FROM fetched WHERE id NOT IN (SELECT id FROM repos)",
+                [],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn non_fetched(&self) -> rusqlite::Result<Vec<CsvRepo>> {
+        self.transaction(|tx| {
+            let mut stmt = tx.prepare(
+                "SELECT id, name, has_pom, license, stars, forks, created_at, disk_usage FROM repos
+                 WHERE id NOT IN (SELECT id FROM fetched) ORDER BY rowid",
+            )?;
+            let repos = stmt.query_map([], repo)?.collect();
+            repos
+        })
+    }
+
+    pub fn fetched_ids(&self) -> rusqlite::Result<Vec<String>> {
+        self.transaction(|tx| {
+            let mut stmt = tx.prepare("SELECT id FROM fetched")?;
+            let ids = stmt.query_map([], |row| row.get(0))?.collect();
+            ids
+        })
+    }
+
+    pub fn mark_fetched(&self, id: &str) -> rusqlite::Result<()> {
+        self.transaction(|tx| {
+            tx.execute(
+                "INSERT OR REPLACE INTO fetched (id, fetched_at) VALUES (?1, ?2)",
+                params![id, now()],
+            )?;
+            Ok(())
+        })
+    }
+
+    pub fn rename_repo(&self, id: &str, name: &str) -> rusqlite::Result<()> {
+        self.transaction(|tx| {
+            tx.execute(
+                "UPDATE repos SET name = ?2 WHERE id = ?1",
+                params![id, name],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// Removes the repos matching an id or name (`owner/repo` or `owner.repo`) with their
+    /// fetched status and files, returning them
+    pub fn remove_repos(&self, name_or_id: &str) -> rusqlite::Result<Vec<CsvRepo>> {
+        self.transaction(|tx| {
+            let mut stmt = tx.prepare(
+                "SELECT id, name, has_pom, license, stars, forks, created_at, disk_usage FROM repos
+                 WHERE id = ?1 OR name = ?1 OR replace(name, '/', '.') = ?1",
+            )?;
+            let removed: Vec<CsvRepo> = stmt
+                .query_map([name_or_id], repo)?
+                .collect::<Result<_, _>>()?;
+            for repo in &removed {
+                tx.execute("DELETE FROM repos WHERE id = ?1", [&repo.id])?;
+                tx.execute("DELETE FROM fetched WHERE id = ?1", [&repo.id])?;
+                tx.execute("DELETE FROM files WHERE repo_id = ?1", [&repo.id])?;
+            }
+            Ok(removed)
+        })
+    }
+
+    /// Sets `has_pom` for the repos whose project dir (`owner.repo`) is in `dirs`
+    pub fn set_has_pom(&self, dirs: &HashSet<String>) -> rusqlite::Result<usize> {
+        let repos = self.repos()?;
+        self.transaction(|tx| {
+            let mut stmt = tx.prepare("UPDATE repos SET has_pom = 1 WHERE id = ?1")?;
+            let mut updated = 0;
+            for repo in repos {
+                if !repo.has_pom && dirs.contains(&project_dir(&repo.name)) {
+                    updated += stmt.execute([&repo.id])?;
+                }
+            }
+            Ok(updated)
+        })
+    }
+
+    /// Records a file downloaded for a repo, by its path in the repo
+    pub fn record_file(&self, repo_id: &str, path: &str) -> rusqlite::Result<()> {
+        self.transaction(|tx| {
+            tx.execute(
+                "INSERT OR IGNORE INTO files (repo_id, path) VALUES (?1, ?2)",
+                params![repo_id, path],
+            )?;
+            Ok(())
+        })
+    }
+
+    /// The files downloaded for a repo
+    pub fn files(&self, repo_id: &str) -> rusqlite::Result<Vec<String>> {
+        self.transaction(|tx| {
+            let mut stmt = tx.prepare("SELECT path FROM files WHERE repo_id = ?1 ORDER BY path")?;
+            let files = stmt.query_map([repo_id], |row| row.get(0))?.collect();
+            files
+        })
+    }
+
+    /// Amount of repos and of fetched repos
+    pub fn counts(&self) -> rusqlite::Result<(usize, usize)> {
+        self.transaction(|tx| {
+            let count = |table: &str| -> rusqlite::Result<usize> {
+                tx.query_row(&format!("SELECT count(*) FROM {table}"), [], |row| {
+                    row.get(0)
+                })
+            };
+            Ok((count("repos")?, count("fetched")?))
+        })
+    }
+
+    /// Imports the repos and fetched ids of a csv data dir, returning the amount of both
+    pub fn import(
+        &self,
+        repos: &[CsvRepo],
+        fetched: &[&str],
+        files: &[(String, String)],
+    ) -> rusqlite::Result<(usize, usize)> {
+        self.transaction(|tx| {
+            let mut insert = tx.prepare(UPSERT)?;
+            for repo in repos {
+                insert.execute(upsert_params(repo))?;
+            }
+
+            let mut mark =
+                tx.prepare("INSERT OR IGNORE INTO fetched (id, fetched_at) VALUES (?1, ?2)")?;
+            let mut marked = 0;
+            let now = now();
+            for id in fetched {
+                let known: Option<i64> = tx
+                    .query_row("SELECT 1 FROM repos WHERE id = ?1", [id], |row| row.get(0))
+                    .optional()?;
+                if known.is_some() {
+                    marked += mark.execute(params![id, now])?;
+                }
+            }
+
+            let mut file =
+                tx.prepare("INSERT OR IGNORE INTO files (repo_id, path) VALUES (?1, ?2)")?;
+            for (repo_id, path) in files {
+                file.execute(params![repo_id, path])?;
+            }
+
+            Ok((repos.len(), marked))
+        })
+    }
+}
@@ -0,0 +1,531 @@
+use crate::analyzer::{repo_name, Project, Report};
+use crate::data::Data;
+use crate::{data, CsvRepo, Repo};
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::mem::take;
+use std::path::Path;
+use thiserror::Error;
+use tracing::{info, warn};
+use url::Url;
+use walkdir::WalkDir;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Data error: {0:?}")]
+    Data(#[from] data::Error),
+
+    #[error("IO Error: {0:?}")]
+    IO(#[from] io::Error),
+
+    #[error("Error walking directory: {0:?}")]
+    Walk(#[from] walkdir::Error),
+}
+
+/// Replaces repository names by stable pseudonymous ids using a keyed HMAC.
+///
+/// The same key always yields the same ids, so datasets exported at different times stay joinable
+/// while the names can't be recovered without the key.
+#[derive(Clone)]
+pub struct Anonymizer {
+    mac: Hmac<Sha256>,
+}
+
+impl Anonymizer {
+    pub fn new(key: &str) -> Self {
+        Self {
+            mac: Hmac::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length"),
+        }
+    }
+
+    /// Pseudonym for a repository name (`owner/repo`)
+    pub fn pseudonym(&self, name: &str) -> String {
+        let mut mac = self.mac.clone();
+        mac.update(name.as_bytes());
+        let bytes = mac.finalize().into_bytes();
+
+        bytes[..16].iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Pseudonym for a project directory name (`owner.repo`)
+    pub fn pseudonym_for_dir(&self, dir: &str) -> String {
+        self.pseudonym(&repo_name(dir))
+    }
+
+    /// Replaces the owners of GitHub and GitLab pages hosts (`<owner>.github.io`) by pseudonyms
+    pub fn anonymize_host(&self, host: &str) -> String {
+        match pages_owner(host) {
+            Some((owner, pages)) => format!("{}{pages}", self.pseudonym(owner)),
+            None => host.to_string(),
+        }
+    }
+
+    /// Replaces the owners and repositories in urls by pseudonyms, in pages
+    /// (`<owner>.github.io/<repo>`) and in the path of forges (`maven.pkg.github.com/<owner>/<repo>`)
+    pub fn anonymize_url(&self, url: &str) -> String {
+        let Ok(mut parsed) = Url::parse(url) else {
+            return self.anonymize_host(url);
+        };
+        let Some(host) = parsed.host_str().map(str::to_string) else {
+            return url.to_string();
+        };
+
+        let anonymized_host = self.anonymize_host(&host);
+        if anonymized_host != host && parsed.set_host(Some(&anonymized_host)).is_err() {
+            return url.to_string();
+        }
+        if let Some((owner, _)) = pages_owner(&host) {
+            let mut segments: Vec<String> = parsed
+                .path_segments()
+                .map(|segments| segments.map(str::to_string).collect())
+                .unwrap_or_default();
+            if let Some(repo) = segments.first_mut().filter(|s| !s.is_empty()) {
+                *repo = self.pseudonym(&format!("{owner}/{repo}"));
+            }
+            parsed.set_path(&segments.join("/"));
+        } else if OWNER_PATH_HOSTS.contains(&host.as_str()) {
+            let mut segments: Vec<String> = parsed
+                .path_segments()
+                .map(|segments| segments.map(str::to_string).collect())
+                .unwrap_or_default();
+            if let Some(owner) = segments.first().filter(|s| !s.is_empty()).cloned() {
+                if let Some(repo) = segments.get_mut(1).filter(|s| !s.is_empty()) {
+                    *repo = self.pseudonym(&format!("{owner}/{repo}"));
+                }
+                segments[0] = self.pseudonym(&owner);
+            }
+            parsed.set_path(&segments.join("/"));
+        }
+
+        // Url adds a slash to urls without a path, which normalized urls don't have
+        let anonymized = parsed.to_string();
+        if url.ends_with('/') {
+            anonymized
+        } else {
+            anonymized.trim_end_matches('/').to_string()
+        }
+    }
+
+    fn anonymize_urls<T: FromIterator<String>>(&self, urls: impl IntoIterator<Item = String>) -> T {
+        urls.into_iter()
+            .map(|url| self.anonymize_url(&url))
+            .collect()
+    }
+
+    fn anonymize_report(&self, report: &mut Report) {
+        for name in report.has_distro_repos.iter_mut() {
+            *name = self.pseudonym_for_dir(name);
+        }
+        for (name, _) in report.poms_per_repo.outliers.iter_mut() {
+            *name = self.pseudonym_for_dir(name);
+        }
+        // Error messages contain file paths and therefore repository names
+        for error in report.errors.iter_mut() {
+            *error = String::from("<redacted>");
+        }
+
+        let url = |url: &str| self.anonymize_url(url);
+        let host = |host: &str| self.anonymize_host(host);
+        report.distros = anonymize_counts(take(&mut report.distros), url);
+        report.external_repos = anonymize_counts(take(&mut report.external_repos), url);
+        report.distro_declarations = anonymize_counts(take(&mut report.distro_declarations), url);
+        report.external_repo_declarations =
+            anonymize_counts(take(&mut report.external_repo_declarations), url);
+        report.distro_hosts = anonymize_counts(take(&mut report.distro_hosts), host);
+        report.external_hosts = anonymize_counts(take(&mut report.external_hosts), host);
+        let automation = &mut report.automation;
+        automation.dependabot_registries =
+            anonymize_counts(take(&mut automation.dependabot_registries), url);
+        automation.renovate_registries =
+            anonymize_counts(take(&mut automation.renovate_registries), url);
+        // Parents of a project are often published by its owner
+        report.parents.missing = anonymize_counts(take(&mut report.parents.missing), |parent| {
+            self.pseudonym(parent)
+        })
+        .into_iter()
+        .collect();
+    }
+
+    fn anonymize_project(&self, project: &mut Project) {
+        project.name = self.pseudonym_for_dir(&project.name);
+        project.duplicate_of = project
+            .duplicate_of
+            .as_deref()
+            .map(|name| self.pseudonym_for_dir(name));
+        for error in project.errors.iter_mut() {
+            *error = String::from("<redacted>");
+        }
+        if let Some(parents) = project.parents.as_mut() {
+            parents.project = project.name.clone();
+            for parent in parents.unresolved.iter_mut() {
+                *parent = self.pseudonym(parent);
+            }
+        }
+        // Module directories are commonly named after the repository
+        project.modules.clear();
+
+        project.repos = self.anonymize_urls(take(&mut project.repos));
+        project.dist_repos = self.anonymize_urls(take(&mut project.dist_repos));
+        project.raw_repos = self.anonymize_urls(take(&mut project.raw_repos));
+        project.raw_dist_repos = self.anonymize_urls(take(&mut project.raw_dist_repos));
+        let url = |url: &str| self.anonymize_url(url);
+        project.repo_declarations = anonymize_counts(take(&mut project.repo_declarations), url)
+            .into_iter()
+            .collect();
+        project.dist_declarations = anonymize_counts(take(&mut project.dist_declarations), url)
+            .into_iter()
+            .collect();
+        if let Some(dependabot) = project.dependabot.as_mut() {
+            dependabot.registries = self.anonymize_urls(take(&mut dependabot.registries));
+        }
+        if let Some(renovate) = project.renovate.as_mut() {
+            renovate.registries = self.anonymize_urls(take(&mut renovate.registries));
+        }
+    }
+
+    fn anonymize(&self, repo: CsvRepo) -> CsvRepo {
+        let pseudonym = self.pseudonym(&repo.name);
+        CsvRepo {
+            id: pseudonym.clone(),
+            name: pseudonym,
+            ..repo
+        }
+    }
+}
+
+/// Anonymizes the keys of counts, summing the counts of keys that become the same
+fn anonymize_counts(
+    counts: impl IntoIterator<Item = (String, usize)>,
+    anonymize: impl Fn(&str) -> String,
+) -> DashMap<String, usize> {
+    let anonymized = DashMap::new();
+    for (key, count) in counts {
+        *anonymized.entry(anonymize(&key)).or_insert(0) += count;
+    }
+    anonymized
+}
+
+/// Hosts of GitHub and GitLab pages, whose subdomain is the owner
+const PAGES_HOSTS: [&str; 2] = [".github.io", ".gitlab.io"];
+
+/// The owner of a pages host and the pages domain it's on
+fn pages_owner(host: &str) -> Option<(&str, &'static str)> {
+    PAGES_HOSTS.into_iter().find_map(|pages| {
+        let owner = host.strip_suffix(pages).filter(|owner| !owner.is_empty())?;
+        Some((owner, pages))
+    })
+}
+
+/// Hosts whose url paths start with the owner and repository
+const OWNER_PATH_HOSTS: [&str; 5] = [
+    "github.com",
+    "maven.pkg.github.com",
+    "raw.githubusercontent.com",
+    "gitlab.com",
+    "codeberg.org",
+];
+
+/// Common open source licenses, all of which allow redistribution of the (build) files
+pub const DEFAULT_ALLOWED_LICENSES: [&str; 20] = [
+    "MIT",
+    "Apache-2.0",
+    "BSD-2-Clause",
+    "BSD-3-Clause",
+    "ISC",
+    "0BSD",
+    "Unlicense",
+    "CC0-1.0",
+    "BSL-1.0",
+    "Zlib",
+    "WTFPL",
+    "MPL-2.0",
+    "EPL-1.0",
+    "EPL-2.0",
+    "LGPL-2.1",
+    "LGPL-3.0",
+    "GPL-2.0",
+    "GPL-3.0",
+    "AGPL-3.0",
+    "CC-BY-4.0",
+];
+
+/// Why a repository was left out of an export
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Exclusion {
+    /// GitHub didn't detect a license, or reported it as `NOASSERTION` (custom license text)
+    NoLicense,
+    /// The license is not in the allowed list
+    Restrictive(String),
+}
+
+impl Display for Exclusion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Exclusion::NoLicense => write!(f, "no license"),
+            Exclusion::Restrictive(license) => write!(f, "restrictive license {license}"),
+        }
+    }
+}
+
+/// Only lets repositories with an allowed SPDX license through
+#[derive(Debug, Clone)]
+pub struct LicenseFilter {
+    allowed: HashSet<String>,
+}
+
+impl LicenseFilter {
+    pub fn new(allowed: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            allowed: allowed.into_iter().collect(),
+        }
+    }
+
+    pub fn check(&self, license: Option<&str>) -> Result<(), Exclusion> {
+        match license {
+            None | Some("") | Some("NOASSERTION") => Err(Exclusion::NoLicense),
+            Some(license) if self.allowed.contains(license) => Ok(()),
+            Some(license) => Err(Exclusion::Restrictive(license.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ExportSummary {
+    pub repos: usize,
+    pub files: usize,
+    pub excluded: BTreeMap<Exclusion, usize>,
+}
+
+impl ExportSummary {
+    pub fn print(&self) {
+        println!(
+            "Exported {} repositories with {} files",
+            self.repos, self.files
+        );
+
+        if !self.excluded.is_empty() {
+            let total: usize = self.excluded.values().sum();
+            println!("Excluded {total} repositories:");
+            for (reason, count) in &self.excluded {
+                println!("  {reason}: {count}");
+            }
+        }
+    }
+}
+
+/// Copies all files of a project into the export, following the symlinks of subsets
+fn copy_project(from: &Path, to: &Path) -> Result<usize, Error> {
+    let mut files = 0;
+    for entry in WalkDir::new(from).follow_links(true) {
+        let entry = entry?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let relative = entry
+            .path()
+            .strip_prefix(from)
+            .expect("walkdir yields paths inside the root");
+        let target = to.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(entry.path(), target)?;
+        files += 1;
+    }
+
+    Ok(files)
+}
+
+/// Exports the dataset (repos and their fetched status, pom directories, report and projects) into
+/// another data directory.
+///
+/// Warning: this method blocks
+pub fn export(
+    data: &Data,
+    out: &Data,
+    anonymizer: Option<&Anonymizer>,
+    license_filter: Option<&LicenseFilter>,
+) -> Result<ExportSummary, Error> {
+    let mut summary = ExportSummary::default();
+    let mut exported = Vec::new();
+    let mut excluded_dirs = HashSet::new();
+
+    for repo in data.read_repos()? {
+        if let Some(filter) = license_filter {
+            if let Err(reason) = filter.check(repo.license.as_deref()) {
+                *summary.excluded.entry(reason).or_insert(0) += 1;
+                excluded_dirs.insert(Repo::from(repo).path());
+                continue;
+            }
+        }
+
+        let from = data.get_project_dir(&Repo::from(repo.clone()));
+        let repo = match anonymizer {
+            Some(anonymizer) => anonymizer.anonymize(repo),
+            None => repo,
+        };
+
+        // Build files name their repository and its owner in too many places to scrub them
+        if from.exists() && anonymizer.is_none() {
+            let to = out.get_project_dir(&Repo::from(repo.clone()));
+            summary.files += copy_project(&from, &to)?;
+        }
+
+        summary.repos += 1;
+        exported.push(repo);
+    }
+
+    // Anonymized repos have no files, so they aren't fetched in the export
+    match anonymizer {
+        Some(_) => out.write_repos(&exported)?,
+        None => out.write_subset(data, &exported)?,
+    }
+    info!("Exported {} repos", exported.len());
+
+    // Without the excluded repos the aggregate counts have to be recomputed from the projects
+    let projects = data.read_projects().map(|projects| -> Vec<Project> {
+        projects
+            .into_iter()
+            .filter(|project| !excluded_dirs.contains(&project.name))
+            .collect()
+    });
+
+    match data.read_report() {
+        Ok(mut report) => {
+            if let (false, Ok(projects)) = (excluded_dirs.is_empty(), &projects) {
+                report = Report::from_projects(projects, report.errors);
+            }
+            if let Some(anonymizer) = anonymizer {
+                anonymizer.anonymize_report(&mut report);
+            }
+            out.write_report(report, None)?;
+        }
+        Err(e) => warn!("Not exporting report: {e}"),
+    }
+
+    match projects {
+        Ok(mut projects) => {
+            if let Some(anonymizer) = anonymizer {
+                for project in projects.iter_mut() {
+                    anonymizer.anonymize_project(project);
+                }
+            }
+            out.write_projects(&projects, None)?;
+        }
+        Err(e) => warn!("Not exporting projects: {e}"),
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const LEAKS: [&str; 3] = ["alice", "secret-app", "secret-parent"];
+
+    fn assert_anonymized(exported: &impl serde::Serialize) {
+        let json = serde_json::to_string(exported).unwrap();
+        for leak in LEAKS {
+            assert!(!json.contains(leak), "{leak} left in {json}");
+        }
+    }
+
+    #[test]
+    fn anonymizes_urls_of_owners() {
+        let anonymizer = Anonymizer::new("k");
+        for url in [
+            "https://maven.pkg.github.com/alice/secret-app",
+            "https://alice.github.io/secret-app/maven",
+            "https://raw.githubusercontent.com/alice/secret-app/main/repo",
+            "https://gitlab.com/alice/secret-app/-/packages",
+            "alice.gitlab.io",
+        ] {
+            assert_anonymized(&anonymizer.anonymize_url(url));
+        }
+        assert_eq!(
+            anonymizer.anonymize_url("https://repo.maven.apache.org/maven2"),
+            "https://repo.maven.apache.org/maven2"
+        );
+        assert_eq!(
+            anonymizer.anonymize_url("https://alice.github.io/secret-app"),
+            anonymizer.anonymize_url("https://alice.github.io/secret-app")
+        );
+    }
+
+    #[test]
+    fn anonymizes_projects() {
+        let anonymizer = Anonymizer::new("k");
+        let mut project: Project = serde_json::from_value(json!({
+            "name": "alice.secret-app",
+            "repos": ["https://alice.github.io/secret-app"],
+            "dist_repos": ["https://maven.pkg.github.com/alice/secret-app"],
+            "raw_repos": ["https://alice.github.io/secret-app/"],
+            "raw_dist_repos": ["https://maven.pkg.github.com/alice/secret-app/"],
+            "repo_declarations": {"https://alice.github.io/secret-app": 1},
+            "dist_declarations": {"https://maven.pkg.github.com/alice/secret-app": 1},
+            "dependabot": {
+                "maven": true,
+                "intervals": ["daily"],
+                "registries": ["https://maven.pkg.github.com/alice/secret-app"]
+            },
+            "renovate": {"parsed": true, "registries": ["alice.github.io"]},
+            "parents": {
+                "project": "alice.secret-app",
+                "resolved": 0,
+                "downloaded": 0,
+                "unresolved": ["io.github.alice:secret-parent:1.0"]
+            },
+            "duplicate_of": "alice.secret-app-copy",
+            "modules": {"pom.xml": ["secret-app-core"]},
+            "errors": ["poms/alice.secret-app/pom.xml: invalid xml"]
+        }))
+        .unwrap();
+
+        anonymizer.anonymize_project(&mut project);
+        assert_anonymized(&project);
+        assert_eq!(project.name, anonymizer.pseudonym("alice/secret-app"));
+        assert_eq!(project.parents.unwrap().project, project.name);
+    }
+
+    #[test]
+    fn anonymizes_reports() {
+        let anonymizer = Anonymizer::new("k");
+        let mut report = Report {
+            has_distro_repos: vec!["alice.secret-app".to_string()],
+            errors: vec!["poms/alice.secret-app/pom.xml: invalid xml".to_string()],
+            ..Default::default()
+        };
+        let url = "https://maven.pkg.github.com/alice/secret-app".to_string();
+        for counts in [
+            &report.distros,
+            &report.external_repos,
+            &report.distro_declarations,
+            &report.external_repo_declarations,
+            &report.automation.dependabot_registries,
+            &report.automation.renovate_registries,
+        ] {
+            counts.insert(url.clone(), 1);
+        }
+        report.distro_hosts.insert("alice.github.io".to_string(), 1);
+        report
+            .external_hosts
+            .insert("alice.github.io".to_string(), 1);
+        report.poms_per_repo.add("alice.secret-app", 3);
+        report
+            .parents
+            .missing
+            .insert("io.github.alice:secret-parent:1.0".to_string(), 2);
+
+        anonymizer.anonymize_report(&mut report);
+        assert_anonymized(&report);
+        assert_eq!(report.parents.missing.values().sum::<usize>(), 2);
+    }
+}
@@ -0,0 +1,126 @@
+//! The scraper, data and analyzer of the `rp` command line tool, for embedding them.
+//!
+//! [data::Data] is a data dir, [scraper::Scraper] fills it with repos and their build files
+//! from a [scraper::forge::Forge] and [analyzer::analyze] aggregates them into a
+//! [analyzer::Report].
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+pub mod analyzer;
+pub mod checksums;
+pub mod data;
+pub mod export;
+pub mod metrics;
+pub mod notify;
+pub mod scraper;
+#[cfg(feature = "serve")]
+pub mod serve;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Repo {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct CsvRepo {
+    // Can't use serde(flatten) due to https://github.com/BurntSushi/rust-csv/issues/188
+    pub id: String,
+    pub name: String,
+    /// Whether build files were found, poms or the Gradle scripts when downloading those too
+    pub has_pom: bool,
+    /// SPDX id of the license as detected by GitHub
+    #[serde(default)]
+    pub license: Option<String>,
+    /// Stars when the repo was loaded, empty for rows stored before it was recorded
+    #[serde(default)]
+    pub stars: Option<u64>,
+    #[serde(default)]
+    pub forks: Option<u64>,
+    /// e.g. `2024-01-01T12:00:00Z`
+    #[serde(default)]
+    pub created_at: Option<String>,
+    /// Size of the repo in KB as reported by GitHub
+    #[serde(default)]
+    pub disk_usage: Option<u64>,
+}
+
+impl From<CsvRepo> for Repo {
+    fn from(value: CsvRepo) -> Self {
+        Repo {
+            id: value.id,
+            name: value.name,
+        }
+    }
+}
+
+impl Repo {
+    /// Name of the project dir of the repo, see [project_dir]
+    pub fn path(&self) -> String {
+        project_dir(&self.name)
+    }
+
+    pub fn to_csv_repo(self, has_pom: bool) -> CsvRepo {
+        CsvRepo {
+            id: self.id,
+            name: self.name,
+            has_pom,
+            ..Default::default()
+        }
+    }
+}
+
+/// Name of the project dir of a repo named `owner/repo`, `owner.repo`. GitHub owners can't contain
+/// dots, but GitLab namespaces can and can be nested (`group/sub/project`), so `%`, `.` and `/` are
+/// percent-encoded in the namespace to keep its first dot separating it from the repo, see
+/// [analyzer::repo_name]
+pub fn project_dir(name: &str) -> String {
+    match name.rsplit_once('/') {
+        Some((namespace, repo)) => format!(
+            "{}.{repo}",
+            namespace
+                .replace('%', "%25")
+                .replace('.', "%2E")
+                .replace('/', "%2F")
+        ),
+        None => name.to_string(),
+    }
+}
+
+pub const SEED: [u8; 32] = [42; 32];
+
+/// Keeps at most `max` items per owner, preserving the order of `items`.
+///
+/// Shuffle `items` with a seeded rng beforehand to sample the kept items deterministically.
+pub fn cap_per_owner<T>(items: Vec<T>, max: usize, owner: impl Fn(&T) -> &str) -> Vec<T> {
+    let mut per_owner: HashMap<String, usize> = HashMap::new();
+    items
+        .into_iter()
+        .filter(|item| {
+            let count = per_owner.entry(owner(item).to_string()).or_insert(0);
+            *count += 1;
+            *count <= max
+        })
+        .collect()
+}
+
+/// Takes up to `n` items, equally many of every stratum as far as the strata have enough and the
+/// rest from the larger ones, preserving the order of `items` within a stratum.
+pub fn stratify<T, K: Ord>(items: Vec<T>, n: usize, stratum: impl Fn(&T) -> K) -> Vec<T> {
+    let mut strata: BTreeMap<K, VecDeque<T>> = BTreeMap::new();
+    for item in items {
+        strata.entry(stratum(&item)).or_default().push_back(item);
+    }
+
+    let mut taken = Vec::with_capacity(n);
+    while taken.len() < n && strata.values().any(|items| !items.is_empty()) {
+        for items in strata.values_mut() {
+            if taken.len() == n {
+                break;
+            }
+            taken.extend(items.pop_front());
+        }
+    }
+    taken
+}
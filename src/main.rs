@@ -1,198 +1,1434 @@
-use crate::data::Data;
-use crate::scraper::Scraper;
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 use color_eyre::eyre::bail;
+use maven_scraper_core::analyzer::bench;
+use maven_scraper_core::analyzer::dedup;
+use maven_scraper_core::analyzer::effective;
+use maven_scraper_core::analyzer::graph::{Graph, GraphFormat};
+use maven_scraper_core::analyzer::parents;
+use maven_scraper_core::analyzer::tables::{self, OutputFormat, ProjectsFormat};
+use maven_scraper_core::analyzer::{Chunk, Report};
+use maven_scraper_core::data::{Data, ForgeKind};
+use maven_scraper_core::export::{Anonymizer, LicenseFilter};
+use maven_scraper_core::notify::{Event, Notifier, NotifyOptions};
+use maven_scraper_core::scraper::patterns::Pattern;
+use maven_scraper_core::scraper::selection::Selection;
+use maven_scraper_core::scraper::{forge, progress, watch, BuildSystem, Scraper, Strategy};
+use maven_scraper_core::{
+    analyzer, cap_per_owner, checksums, data, export, metrics, project_dir, stratify, CsvRepo, SEED,
+};
 use rand::prelude::SliceRandom;
 use rand::SeedableRng;
 use rand_chacha::ChaCha20Rng;
-use serde::{Deserialize, Serialize};
-use std::fs;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
 use std::os::unix::fs::symlink;
-use std::path::PathBuf;
-use std::time::Duration;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use std::{fs, io};
+use tracing::level_filters::LevelFilter;
+use tracing::warn;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
 
-pub mod analyzer;
-mod data;
-pub mod scraper;
+#[derive(Subcommand)]
+enum Commands {
+    /// Fetch all Java repos from Github and fetch all pom files of them (recursively)
+    FetchAndDownload {
+        /// Build systems to download the build files of, e.g. `maven,gradle`
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "maven")]
+        build_files: Vec<BuildSystem>,
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
-pub struct Repo {
-    pub id: String,
-    pub name: String,
-}
+        /// Glob patterns of other files to download, e.g. `gradle.properties,.mvn/wrapper/*.properties`
+        #[arg(long, value_delimiter = ',')]
+        patterns: Vec<Pattern>,
 
-#[derive(Serialize, Deserialize, Clone)]
-pub struct CsvRepo {
-    // Can't use serde(flatten) due to https://github.com/BurntSushi/rust-csv/issues/188
-    pub id: String,
-    pub name: String,
-    pub has_pom: bool,
-}
+        /// How the files of a repo are downloaded
+        #[arg(long, value_enum, default_value_t)]
+        strategy: Strategy,
 
-impl From<CsvRepo> for Repo {
-    fn from(value: CsvRepo) -> Self {
-        Repo {
-            id: value.id,
-            name: value.name,
-        }
-    }
-}
+        /// Repos with more files than this are downloaded from their tarball with `--strategy auto`
+        #[arg(long, value_name = "N", default_value_t = 25)]
+        tarball_threshold: usize,
 
-impl Repo {
-    pub fn path(&self) -> String {
-        self.name.replace('/', ".")
-    }
+        #[command(flatten)]
+        selection: Selection,
+    },
 
-    pub fn to_csv_repo(self, has_pom: bool) -> CsvRepo {
-        CsvRepo {
-            id: self.id,
-            name: self.name,
-            has_pom,
-        }
-    }
-}
+    /// Keep scraping in rounds until Ctrl+C: load the new repos and download the files of fetched
+    /// repos again once their default branch moved
+    Watch {
+        /// Build systems to download the build files of, e.g. `maven,gradle`
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "maven")]
+        build_files: Vec<BuildSystem>,
 
-const SEED: [u8; 32] = [42; 32];
+        /// Glob patterns of other files to download, e.g. `gradle.properties,.mvn/wrapper/*.properties`
+        #[arg(long, value_delimiter = ',')]
+        patterns: Vec<Pattern>,
 
-#[derive(Subcommand)]
-enum Commands {
-    /// Fetch all Java repos from Github and fetch all pom files of them (recursively)
-    FetchAndDownload,
+        /// How the files of a repo are downloaded
+        #[arg(long, value_enum, default_value_t)]
+        strategy: Strategy,
+
+        /// Repos with more files than this are downloaded from their tarball with `--strategy auto`
+        #[arg(long, value_name = "N", default_value_t = 25)]
+        tarball_threshold: usize,
+
+        /// Seconds from the start of a round to the start of the next
+        #[arg(long, value_name = "SECS", default_value_t = 3600)]
+        interval: u64,
+
+        /// Hours after which a fetched repo is checked for a moved default branch again
+        #[arg(long, value_name = "HOURS", default_value_t = 168)]
+        check_after: u64,
+
+        #[command(flatten)]
+        selection: Selection,
+    },
 
     /// Per repository, only download the poms (recursively)
     /// This uses an already existing csv file
-    DownloadPoms,
+    DownloadPoms {
+        /// Build systems to download the build files of, e.g. `maven,gradle`
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "maven")]
+        build_files: Vec<BuildSystem>,
+
+        /// Glob patterns of other files to download, e.g. `gradle.properties,.mvn/wrapper/*.properties`
+        #[arg(long, value_delimiter = ',')]
+        patterns: Vec<Pattern>,
+
+        /// How the files of a repo are downloaded
+        #[arg(long, value_enum, default_value_t)]
+        strategy: Strategy,
+
+        /// Repos with more files than this are downloaded from their tarball with `--strategy auto`
+        #[arg(long, value_name = "N", default_value_t = 25)]
+        tarball_threshold: usize,
+
+        /// Amount of repos downloaded at the same time
+        #[arg(long, value_name = "N", default_value_t = 8)]
+        concurrency: usize,
+    },
+
+    /// Build the effective poms of all projects ahead of `analyze --effective`, resuming earlier runs
+    BuildEffective {
+        /// Amount of maven processes running at the same time (defaults to the amount of cpus)
+        #[arg(long, value_name = "N")]
+        mvn_jobs: Option<usize>,
+
+        /// Seconds after which a maven invocation is killed
+        #[arg(long, default_value_t = 300)]
+        timeout: u64,
+
+        /// Also retry projects that failed in an earlier run
+        #[arg(long)]
+        retry_failed: bool,
+    },
 
     /// Analyze the (effective) poms for the repositories
     Analyze {
         /// Create effective poms (~2s per POM)
         #[arg(long)]
         effective: bool,
+
+        /// Only analyze a deterministic shard of the projects (e.g. `0/8`) and write a partial report
+        #[arg(long)]
+        chunk: Option<Chunk>,
+
+        /// Analyze all N chunks one after another and merge their reports, to hold less in memory
+        #[arg(long, value_name = "N", conflicts_with_all = ["chunk", "bootstrap"])]
+        shards: Option<NonZeroUsize>,
+
+        /// Bootstrap N resamples of the projects to add confidence intervals to the report
+        #[arg(long, value_name = "N")]
+        bootstrap: Option<usize>,
+
+        /// Analyze at most N (deterministically sampled) repositories of the same owner
+        #[arg(long, value_name = "N")]
+        max_per_owner: Option<usize>,
+
+        /// Format of the per-project results (the report itself is always json)
+        #[arg(long, value_enum, default_value_t)]
+        output_format: OutputFormat,
+
+        /// Maximum amount of maven processes running at the same time (by default only limited by --jobs)
+        #[arg(long, value_name = "N")]
+        mvn_jobs: Option<usize>,
+
+        /// Scan poms for repository urls without fully parsing them, falling back to the parser if unsure
+        #[arg(long)]
+        fast_path: bool,
+
+        /// Only analyze projects whose files changed since the last incremental run, reusing the rest
+        #[arg(long)]
+        incremental: bool,
+
+        /// Only analyze the first project of every cluster of projects with the same poms, see dedup
+        #[arg(long)]
+        exclude_duplicates: bool,
+    },
+
+    /// Hash the poms of every project to find copies and mirrors, writing the clusters of
+    /// projects with the same poms to dedup-report.json
+    Dedup,
+
+    /// Merges multiple reports into a single report.json
+    ///
+    /// Without inputs, the partial reports of a chunked analysis in the data dir are merged
+    MergeReports {
+        /// Report files or data directories containing a report.json
+        inputs: Vec<PathBuf>,
+
+        /// Where to write the merged report, defaults to the report.json in the data dir
+        #[arg(long)]
+        out: Option<PathBuf>,
     },
 
     /// Gets the most popular hostnames from a report.json
     AnalyzeHostnames,
 
+    /// Exports the bipartite graph of repositories and the repository hosts they use (from projects.json)
+    ExportGraph {
+        #[arg(long, value_enum, default_value_t = GraphFormat::Dot)]
+        format: GraphFormat,
+
+        /// File to write the graph to, defaults to stdout
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Exports the per-project results (from projects.json) as rows for dataframe tooling
+    ExportProjects {
+        #[arg(long, value_enum)]
+        format: ProjectsFormat,
+
+        /// File to write the rows to, defaults to projects.jsonl or projects.parquet in the data dir
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+
+    /// Compares a report of a subset against the report of the full dataset to quantify sampling bias
+    CompareReports {
+        /// Report file or data directory of the sample
+        sample: PathBuf,
+
+        /// Report file or data directory of the full dataset
+        population: PathBuf,
+
+        /// Amount of most popular hosts to compare
+        #[arg(long, default_value_t = 15)]
+        top: usize,
+    },
+
     PrintReport,
 
-    /// creates an N large random subset of the data dir using a fixed seed of [42; 32]
+    /// creates an N large random subset of the data dir, by default using a fixed seed of [42; 32]
     CreateRandomSubset {
         n: usize,
         from: PathBuf,
         out: PathBuf,
+
+        #[command(flatten)]
+        options: SubsetOptions,
+    },
+    /// Splits the data dir into disjoint train and test data dirs (at repository granularity)
+    SplitDataset {
+        out: PathBuf,
+
+        /// Share of repositories that end up in the train split
+        #[arg(long, default_value_t = 0.8)]
+        ratio: f64,
+
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
     },
+
     /// Updates the has_pom field in the csv to correspond to the filesystem
     ConsolidateCsv,
 
+    /// Exports the dataset into a new data directory, e.g. for publishing it
+    ExportDataset {
+        out: PathBuf,
+
+        /// Replace repository names and ids, also in repository urls, by pseudonyms derived from
+        /// this key (keyed HMAC). The build files aren't exported then.
+        #[arg(long, env = "ANONYMIZE_KEY", hide_env_values = true)]
+        anonymize_key: Option<String>,
+
+        /// Exclude repositories without a license or with a license not in --allowed-licenses
+        #[arg(long)]
+        license_filter: bool,
+
+        /// SPDX ids of licenses that allow redistributing the repository contents
+        #[arg(long, value_delimiter = ',', default_values_t = export::DEFAULT_ALLOWED_LICENSES.map(String::from))]
+        allowed_licenses: Vec<String>,
+    },
+
+    /// Measure pom parsing, effective pom, csv reading and disk writing speed on this data dir
+    Bench {
+        /// Maximum amount of poms to parse
+        #[arg(long, default_value_t = 10_000)]
+        poms: usize,
+
+        /// Amount of projects to build an effective pom for (0 to skip, requires maven)
+        #[arg(long, default_value_t = 3)]
+        effective: usize,
+    },
+
     /// Fetch Workflows
     FetchWorkflows,
 
+    /// Fetch the maven, dependabot and renovate configuration files of repos downloaded before they were fetched
+    FetchBuildFiles,
+
+    /// Check which repos with poms publish GitHub releases and their jar and pom assets, for the
+    /// distribution channels report
+    FetchReleases {
+        /// Only check the repos with a distributionManagement in the last analysis
+        #[arg(long)]
+        distribution_only: bool,
+    },
+
+    /// Download the files or check the releases again of repos whose last attempt failed with a
+    /// retryable error (network errors, rate limits, server errors) in errors.jsonl
+    RetryFailed {
+        /// Build systems to download the build files of, e.g. `maven,gradle`
+        #[arg(long, value_enum, value_delimiter = ',', default_value = "maven")]
+        build_files: Vec<BuildSystem>,
+
+        /// Glob patterns of other files to download, e.g. `gradle.properties,.mvn/wrapper/*.properties`
+        #[arg(long, value_delimiter = ',')]
+        patterns: Vec<Pattern>,
+
+        /// Amount of repos retried at the same time
+        #[arg(long, value_name = "N", default_value_t = 8)]
+        concurrency: usize,
+    },
+
+    /// Load the license, stars, forks, creation date and disk usage of repos stored before those
+    /// were recorded into the repo store. Don't run it while scraping into the same data dir
+    BackfillMetadata,
+
+    /// Fetch the GitHub Packages maven packages of owners configuring them in the last analysis.
+    /// Needs tokens with the read:packages scope
+    FetchPackages,
+
+    /// Download the parent poms projects reference from outside the project (and their parents)
+    /// from Maven Central or the declared repositories into the local repository (--mvn-local-repo),
+    /// so effective poms can be built offline. Resumes earlier runs
+    FetchParents {
+        /// Amount of projects fetched at the same time
+        #[arg(long, default_value_t = 8)]
+        concurrency: usize,
+
+        /// Also retry projects with parents that weren't found in an earlier run
+        #[arg(long)]
+        retry_failed: bool,
+    },
+
+    /// Print the coverage of the GitHub id space and the projected completion of fetch-and-download
+    Progress {
+        /// Id of the newest GitHub repository, looked up using the tokens if not given
+        #[arg(long)]
+        max_id: Option<usize>,
+    },
+
+    /// Serve a dashboard and JSON API of the scrape progress, report and projects of the data dir
+    #[cfg(feature = "serve")]
+    Serve {
+        #[arg(long, default_value_t = 8080)]
+        port: u16,
+
+        /// Address to listen on, e.g. `0.0.0.0` to be reachable from other machines
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: std::net::IpAddr,
+    },
+
+    /// Delete all data of a repository, e.g. for an opt-out request, and never scrape it again
+    RemoveRepo {
+        /// Name (`owner/repo`), project dir name (`owner.repo`) or id of the repository
+        repo: String,
+    },
+
+    /// Write a SHA256SUMS manifest of all files in the data dir, e.g. before publishing it
+    Checksums {
+        /// Write a manifest per top-level directory instead of a single one
+        #[arg(long)]
+        per_directory: bool,
+    },
+
+    /// Check the files in the data dir against its SHA256SUMS manifests
+    VerifyChecksums,
+
+    /// Restore state.json from a snapshot taken when an earlier command changing it or the repos
+    /// started
+    RollbackState {
+        /// Timestamp of the snapshot, defaults to the newest one differing from the current state
+        timestamp: Option<u64>,
+
+        /// Only list the snapshots
+        #[arg(long)]
+        list: bool,
+    },
+
+    /// Lists the profiles in the data root
+    Profiles,
+
+    /// Imports github.csv, the fetched list and the downloaded files into a SQLite store, which
+    /// the data dir uses from then on
+    MigrateToSqlite,
+
     /// Distinct Repos per HostName
     DistinctReposPerHostname,
 }
 
+impl Commands {
+    /// Whether the command changes state.json or the repos, and snapshots them first
+    fn changes_store(&self) -> bool {
+        matches!(
+            self,
+            Commands::FetchAndDownload { .. }
+                | Commands::Watch { .. }
+                | Commands::DownloadPoms { .. }
+                | Commands::ConsolidateCsv
+                | Commands::RetryFailed { .. }
+                | Commands::BackfillMetadata
+                | Commands::RemoveRepo { .. }
+                | Commands::MigrateToSqlite
+        )
+    }
+
+    /// Whether the command sends requests to the forge, and needs its tokens
+    fn uses_forge(&self) -> bool {
+        matches!(
+            self,
+            Commands::FetchAndDownload { .. }
+                | Commands::Watch { .. }
+                | Commands::DownloadPoms { .. }
+                | Commands::FetchWorkflows
+                | Commands::FetchBuildFiles
+                | Commands::FetchReleases { .. }
+                | Commands::RetryFailed { .. }
+                | Commands::BackfillMetadata
+                | Commands::FetchPackages
+                | Commands::Progress { max_id: None }
+        )
+    }
+}
+
+/// How create-random-subset samples the repos
+#[derive(Debug, Clone, clap::Args)]
+pub struct SubsetOptions {
+    /// Take at most N (randomly chosen) repositories of the same owner
+    #[arg(long, value_name = "N")]
+    max_per_owner: Option<usize>,
+
+    /// Seed of the sample, instead of the fixed seed
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Only sample repos with poms
+    #[arg(long)]
+    only_with_pom: bool,
+
+    /// Take equally many repos of every value of this column, as far as there are enough
+    #[arg(long, value_enum)]
+    stratify_by: Option<Stratum>,
+}
+
+/// Column a subset can be stratified by
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum Stratum {
+    /// Whether poms were found
+    HasPom,
+    /// Whether the project has a distributionManagement, per the report of the data dir
+    Distribution,
+    /// Stars by order of magnitude (0, 1-9, 10-99, ...), see backfill-metadata
+    Stars,
+    /// Whether a license was detected
+    License,
+}
+
+/// Order of magnitude of a star count, `unknown` for repos stored without it
+fn star_bucket(stars: Option<u64>) -> String {
+    match stars {
+        None => "unknown".to_string(),
+        Some(0) => "0".to_string(),
+        Some(stars) => {
+            let low = 10u64.pow(stars.ilog10());
+            format!("{low}-{}", low * 10 - 1)
+        }
+    }
+}
+
+const DEFAULT_PROFILE: &str = "sample10_000";
+
 #[derive(Parser)]
 struct Cli {
-    /// The data directory to analyze or download into
-    #[arg(short, long = "data", default_value = "./data/sample10_000")]
-    data_dir: PathBuf,
+    /// The data directory to analyze or download into, overriding the profile
+    #[arg(short, long = "data", global = true)]
+    data_dir: Option<PathBuf>,
+
+    /// Named dataset (e.g. `full`, `sample10k`) in the data root, with its own csv, state and poms
+    #[arg(short, long, env = "PROFILE", global = true, default_value = DEFAULT_PROFILE)]
+    profile: String,
+
+    /// Directory containing the profiles
+    #[arg(long, env = "DATA_ROOT", global = true, default_value = "./data")]
+    root: PathBuf,
+
+    /// Where repos are stored, defaults to what the data dir already uses or csv for new ones
+    #[arg(long, value_enum, global = true)]
+    storage: Option<data::Storage>,
+
+    /// Increase log verbosity (-v: info, -vv: debug, -vvv: trace)
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Only log errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Log filter in tracing EnvFilter syntax (e.g. `info,maven_scraper_core::scraper=warn`), overrides RUST_LOG and -v/-q
+    #[arg(long, global = true)]
+    log_filter: Option<String>,
+
+    /// Print a JSON summary of the command as the only line of stdout, printing and logging
+    /// everything else to stderr instead
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Serve Prometheus metrics of the scrape on this address, e.g. `0.0.0.0:9090`
+    #[arg(long, env = "METRICS_ADDR", global = true, value_name = "ADDR")]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Amount of worker threads used for analysis, defaults to the amount of cores
+    #[arg(short, long, env = "JOBS", global = true)]
+    jobs: Option<usize>,
 
     /// Github tokens to use when fetching from GitHub
     #[arg(env = "GH_TOKENS", hide_env_values = true, num_args = 1.., value_delimiter = ',')]
     tokens: Vec<String>,
 
+    #[command(flatten)]
+    forge: forge::Options,
+
+    #[command(flatten)]
+    maven: analyzer::Maven,
+
+    #[command(flatten)]
+    notify: NotifyOptions,
+
     #[command(subcommand)]
     cmd: Commands,
 }
 
-pub fn create_subset(n: usize, from: PathBuf, out: PathBuf) -> color_eyre::Result<()> {
-    let mut rng = ChaCha20Rng::from_seed(SEED);
+impl Cli {
+    /// The data dir given with --data, or the one of the profile
+    fn data_dir(&self) -> color_eyre::Result<PathBuf> {
+        if let Some(data_dir) = &self.data_dir {
+            return Ok(data_dir.clone());
+        }
+
+        let mut components = Path::new(&self.profile).components();
+        if !matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        ) {
+            bail!("Invalid profile name {:?}", self.profile);
+        }
+
+        Ok(self.root.join(&self.profile))
+    }
+}
+
+/// Names of the data dirs in the data root, which contain a repo store
+fn profiles(root: &Path) -> color_eyre::Result<Vec<String>> {
+    let mut profiles = Vec::new();
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        let path = entry.path();
+        if data::ForgeKind::value_variants().iter().any(|forge| {
+            path.join(forge.csv_name()).is_file()
+                || path
+                    .join(forge.file_name(data::sqlite::FILE_NAME))
+                    .is_file()
+        }) {
+            profiles.push(entry.file_name().to_string_lossy().into_owned());
+        }
+    }
+    profiles.sort();
+    Ok(profiles)
+}
+
+/// Prints the profiles in the data root with the amount of repos in each, marking the selected one
+fn list_profiles(root: &Path, selected: &Path) -> color_eyre::Result<Outcome> {
+    let profiles = profiles(root)?;
+    for profile in &profiles {
+        let repos = Data::open(&root.join(profile)).store_metadata()?.rows;
+        let marker = if root.join(profile) == selected {
+            "*"
+        } else {
+            " "
+        };
+        println!("{marker} {profile:<20} {repos:>10} repos");
+    }
+    Ok(Outcome::default().count("profiles", profiles.len()))
+}
 
-    let mut reader = csv::Reader::from_path(from.join("github.csv")).unwrap();
+pub fn create_subset(
+    n: usize,
+    from: PathBuf,
+    out: PathBuf,
+    options: &SubsetOptions,
+    forge: ForgeKind,
+) -> color_eyre::Result<SubsetSummary> {
+    let mut rng = match options.seed {
+        Some(seed) => ChaCha20Rng::seed_from_u64(seed),
+        None => ChaCha20Rng::from_seed(SEED),
+    };
 
-    let mut repos: Vec<CsvRepo> = reader.deserialize().map(|el| el.unwrap()).collect();
+    let from = Data::open_forge(&from, forge);
+    let mut repos = from.read_repos()?;
+    if options.only_with_pom {
+        repos.retain(|repo| repo.has_pom);
+    }
 
     repos.shuffle(&mut rng);
 
-    repos.truncate(n);
+    if let Some(max) = options.max_per_owner {
+        repos = cap_per_owner(repos, max, |repo| {
+            repo.name.split('/').next().unwrap_or_default()
+        });
+    }
+
+    match options.stratify_by {
+        Some(Stratum::HasPom) => repos = stratify(repos, n, |repo| repo.has_pom),
+        Some(Stratum::Distribution) => {
+            let distributing: HashSet<String> =
+                from.read_report()?.has_distro_repos.into_iter().collect();
+            repos = stratify(repos, n, |repo| {
+                distributing.contains(&project_dir(&repo.name))
+            });
+        }
+        Some(Stratum::Stars) => repos = stratify(repos, n, |repo| star_bucket(repo.stars)),
+        Some(Stratum::License) => repos = stratify(repos, n, |repo| repo.license.is_some()),
+        None => repos.truncate(n),
+    }
 
-    fs::create_dir_all(out.join("poms"))?;
+    write_subset(&from, &out, &repos)
+}
 
-    let fetched = from.join("fetched");
+/// Pom directory links of a (re-)written subset
+#[derive(Debug, Default)]
+pub struct SubsetSummary {
+    pub created: usize,
+    /// Broken links or links to another directory that were replaced
+    pub repaired: usize,
+    /// Links that were already correct
+    pub existing: usize,
+}
 
-    if fetched.exists() {
-        fs::copy(fetched, out.join("fetched"))?;
+impl SubsetSummary {
+    fn print(&self, out: &Path) {
+        println!(
+            "Linked poms into {out:?}: {} created, {} repaired, {} already linked",
+            self.created, self.repaired, self.existing
+        );
     }
+}
+
+/// Writes a data dir containing the given repos, symlinking their poms from the original data dir
+/// and copying the state and the per-project results of the given repos.
+/// Safe to run again on an existing subset, keeping correct links and replacing broken ones.
+fn write_subset(from: &Data, out: &Path, repos: &[CsvRepo]) -> color_eyre::Result<SubsetSummary> {
+    let out = Data::open_like(out, from);
+    fs::create_dir_all(out.pom_dir())?;
+    let mut summary = SubsetSummary::default();
+
+    let projects: HashSet<String> = repos.iter().map(|repo| project_dir(&repo.name)).collect();
+    let owners: HashSet<String> = repos
+        .iter()
+        .filter_map(|repo| Some(repo.name.split_once('/')?.0.to_string()))
+        .collect();
+    from.copy_records(&out, &projects, &owners)?;
 
-    let mut writer = csv::Writer::from_path(out.join("github.csv")).unwrap();
     for repo in repos {
-        let repo_path = repo.name.replace('/', ".");
-        if let Ok(path) = from.join("poms").join(&repo_path).canonicalize() {
-            if path.exists() {
-                symlink(path, out.join("poms").join(&repo_path))?;
+        let repo_path = project_dir(&repo.name);
+        if let Ok(path) = from.pom_dir().join(&repo_path).canonicalize() {
+            let link = out.pom_dir().join(&repo_path);
+            match fs::symlink_metadata(&link) {
+                Ok(meta)
+                    if !meta.is_symlink() || link.canonicalize().ok() == Some(path.clone()) =>
+                {
+                    summary.existing += 1;
+                }
+                Ok(_) => {
+                    fs::remove_file(&link)?;
+                    symlink(path, link)?;
+                    summary.repaired += 1;
+                }
+                Err(_) => {
+                    symlink(path, link)?;
+                    summary.created += 1;
+                }
             }
         }
+    }
+    out.write_subset(from, repos)?;
+
+    Ok(summary)
+}
+
+#[derive(Serialize)]
+struct SplitManifest<'a> {
+    split: &'a str,
+    source: &'a Path,
+    seed: u64,
+    ratio: f64,
+    repos: Vec<&'a str>,
+}
+
+/// Splits the repositories of a data dir into two disjoint `train` and `test` data dirs
+pub fn split_dataset(
+    source: PathBuf,
+    out: PathBuf,
+    ratio: f64,
+    seed: u64,
+    forge: ForgeKind,
+) -> color_eyre::Result<()> {
+    if !(0.0..=1.0).contains(&ratio) {
+        bail!("Ratio has to be between 0 and 1");
+    }
 
-        writer.serialize(&repo).unwrap();
+    let mut rng = ChaCha20Rng::seed_from_u64(seed);
+    let from = Data::open_forge(&source, forge);
+    let mut repos = from.read_repos()?;
+    // A repo in both splits would leak the test set into training
+    let mut ids = HashSet::new();
+    repos.retain(|repo| ids.insert(repo.id.clone()));
+    // Sort first so the split only depends on the seed, not on the order of the csv
+    repos.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+    repos.shuffle(&mut rng);
+
+    let at = (repos.len() as f64 * ratio).round() as usize;
+    let (train, test) = repos.split_at(at);
+
+    for (split, repos) in [("train", train), ("test", test)] {
+        let dir = out.join(split);
+        write_subset(&from, &dir, repos)?.print(&dir);
+
+        let manifest = SplitManifest {
+            split,
+            source: &source,
+            seed,
+            ratio,
+            repos: repos.iter().map(|repo| repo.name.as_str()).collect(),
+        };
+        let file = File::create(dir.join("manifest.json"))?;
+        serde_json::to_writer_pretty(file, &manifest)?;
+
+        println!("Wrote {} repos to {dir:?}", repos.len());
     }
 
     Ok(())
 }
 
+/// What a command did, for the `--json` summary
+#[derive(Debug, Default)]
+struct Outcome {
+    errors: usize,
+    outputs: Vec<PathBuf>,
+    counts: BTreeMap<&'static str, usize>,
+}
+
+impl Outcome {
+    fn count(mut self, name: &'static str, count: usize) -> Self {
+        self.counts.insert(name, count);
+        self
+    }
+
+    fn output(mut self, path: impl Into<PathBuf>) -> Self {
+        self.outputs.push(path.into());
+        self
+    }
+
+    fn errors(mut self, errors: usize) -> Self {
+        self.errors = errors;
+        self
+    }
+}
+
+/// Machine readable summary of a finished command, printed with `--json`
+#[derive(Serialize)]
+struct JsonSummary {
+    command: String,
+    success: bool,
+    seconds: f64,
+    errors: usize,
+    /// The error the command failed with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    outputs: Vec<PathBuf>,
+    counts: BTreeMap<&'static str, usize>,
+}
+
+/// Amount of repos and of repos with poms in the csv
+fn csv_counts(data: &Data, outcome: Outcome) -> color_eyre::Result<Outcome> {
+    let repos = data.read_repos()?;
+    Ok(outcome
+        .count("repos", repos.len())
+        .count(
+            "with_poms",
+            repos.iter().filter(|repo| repo.has_pom).count(),
+        )
+        .output(data.repo_store()))
+}
+
+/// The filter for log output: --log-filter, then RUST_LOG, then the verbosity flags
+fn log_filter(cli: &Cli) -> color_eyre::Result<EnvFilter> {
+    if let Some(filter) = &cli.log_filter {
+        return Ok(EnvFilter::try_new(filter)?);
+    }
+
+    if !cli.quiet && cli.verbose == 0 {
+        if let Ok(filter) = EnvFilter::try_from_default_env() {
+            return Ok(filter);
+        }
+    }
+
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => LevelFilter::ERROR,
+        (false, 0) => LevelFilter::WARN,
+        (false, 1) => LevelFilter::INFO,
+        (false, 2) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    };
+
+    Ok(EnvFilter::default().add_directive(level.into()))
+}
+
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
     dotenv::dotenv().ok();
     color_eyre::install().unwrap();
 
-    console_subscriber::ConsoleLayer::builder()
-        .retention(Duration::from_secs(60))
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches)?;
+    let command = matches.subcommand_name().unwrap_or_default().to_string();
+
+    let json = cli.json;
+    // The console layer stays unfiltered, tokio-console needs the runtime's spans
+    tracing_subscriber::registry()
+        .with(
+            console_subscriber::ConsoleLayer::builder()
+                .retention(Duration::from_secs(60))
+                .spawn(),
+        )
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_writer(move || -> Box<dyn Write> {
+                    if json {
+                        Box::new(io::stderr())
+                    } else {
+                        Box::new(io::stdout())
+                    }
+                })
+                .with_filter(log_filter(&cli)?),
+        )
         .init();
 
-    let cli = Cli::parse();
+    if let Some(jobs) = cli.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()?;
+    }
+
+    if let Some(addr) = cli.metrics_addr {
+        metrics::serve(addr).await?;
+    }
+
+    let notifier = Notifier::new(
+        cli.notify.clone(),
+        format!("rp {command} {}", cli.data_dir()?.display()),
+    );
+    // Stdout only has the summary with --json, everything printed for humans goes to stderr
+    let redirect = json
+        .then(|| gag::Redirect::stdout(io::stderr()))
+        .transpose()?;
+    let start = Instant::now();
+    let result = run(cli, &notifier).await;
+    io::stdout().flush()?;
+    drop(redirect);
+
+    let elapsed = start.elapsed();
+    match &result {
+        Err(e) => notifier.notify(Event::Failed, &format!("{e:#}")).await,
+        Ok(outcome) if elapsed.as_secs() >= notifier.options().min_duration => {
+            let message = format!(
+                "Took {:.1} minutes with {} errors",
+                elapsed.as_secs_f64() / 60.0,
+                outcome.errors
+            );
+            notifier.notify(Event::Finished, &message).await
+        }
+        Ok(_) => {}
+    }
+
+    if json {
+        let failed = Outcome::default().errors(1);
+        let (outcome, error) = match &result {
+            Ok(outcome) => (outcome, None),
+            Err(e) => (&failed, Some(format!("{e:#}"))),
+        };
+        let summary = JsonSummary {
+            command,
+            success: error.is_none(),
+            seconds: elapsed.as_secs_f64(),
+            errors: outcome.errors,
+            error,
+            outputs: outcome.outputs.clone(),
+            counts: outcome.counts.clone(),
+        };
+        println!("{}", serde_json::to_string(&summary)?);
+    }
+
+    result.map(|_| ())
+}
+
+async fn run(mut cli: Cli, notifier: &Notifier) -> color_eyre::Result<Outcome> {
+    let data_dir = cli.data_dir()?;
+    // Listed before opening the data dir, which creates it, and without needing tokens
+    if let Commands::Profiles = cli.cmd {
+        return list_profiles(&cli.root, &data_dir);
+    }
+
+    match cli.forge.forge {
+        _ if !cli.cmd.uses_forge() => {}
+        data::ForgeKind::Github if cli.tokens.is_empty() => bail!("Please provide Github Tokens"),
+        data::ForgeKind::Gitlab if cli.forge.gitlab_tokens.is_empty() => {
+            bail!("Please provide GitLab Tokens")
+        }
+        _ => {}
+    }
+    // Only scraping starts a new dataset, a mistyped profile shouldn't silently create one
+    if cli.data_dir.is_none()
+        && !data_dir.exists()
+        && !matches!(
+            cli.cmd,
+            Commands::FetchAndDownload { .. } | Commands::Watch { .. }
+        )
+    {
+        let available = profiles(&cli.root).unwrap_or_default();
+        bail!(
+            "Profile {:?} doesn't exist in {:?}, available: {}",
+            cli.profile,
+            cli.root,
+            available.join(", ")
+        );
+    }
 
-    if cli.tokens.is_empty() {
-        bail!("Please provide Github Tokens");
+    let data = Data::with_options(data_dir.as_path(), cli.forge.forge, cli.storage).await?;
+    if cli.cmd.changes_store() {
+        let data = data.clone();
+        tokio::task::spawn_blocking(move || data.snapshot_state()).await??;
     }
 
-    let data = Data::new(cli.data_dir.as_path()).await?;
+    // Maven runs in the project dirs, so the path has to be absolute
+    let local_repo = cli
+        .maven
+        .local_repo
+        .take()
+        .unwrap_or_else(|| data_dir.join("m2"));
+    cli.maven.local_repo = Some(std::path::absolute(local_repo)?);
 
-    match cli.cmd {
-        Commands::FetchAndDownload => {
-            let scraper = Scraper::new(cli.tokens, data.clone());
+    let outcome = match cli.cmd {
+        Commands::FetchAndDownload {
+            build_files,
+            patterns,
+            strategy,
+            tarball_threshold,
+            selection,
+        } => {
+            let scraper = Scraper::new(&cli.forge, cli.tokens, data.clone(), notifier.clone())
+                .with_build_systems(build_files)
+                .with_patterns(patterns)
+                .with_strategy(strategy, tarball_threshold)
+                .with_selection(selection);
             scraper.fetch_and_download().await?;
+            let progress = data.history().pop();
+            Outcome::default()
+                .count("last_id", progress.as_ref().map_or(0, |p| p.last_id))
+                .count("repos", progress.as_ref().map_or(0, |p| p.repos))
+                .count("with_poms", progress.as_ref().map_or(0, |p| p.poms))
         }
-        Commands::DownloadPoms => {
-            let scraper = Scraper::new(cli.tokens, data.clone());
-            scraper.download_files().await?;
+        Commands::Watch {
+            build_files,
+            patterns,
+            strategy,
+            tarball_threshold,
+            interval,
+            check_after,
+            selection,
+        } => {
+            let scraper = Scraper::new(&cli.forge, cli.tokens, data.clone(), notifier.clone())
+                .with_build_systems(build_files)
+                .with_patterns(patterns)
+                .with_strategy(strategy, tarball_threshold)
+                .with_selection(selection);
+            let options = watch::Options {
+                interval: Duration::from_secs(interval),
+                check_after: Duration::from_secs(check_after * 60 * 60),
+            };
+            scraper.watch(&options).await?;
+            let progress = data.history().pop();
+            Outcome::default()
+                .count("last_id", progress.as_ref().map_or(0, |p| p.last_id))
+                .count("repos", progress.as_ref().map_or(0, |p| p.repos))
+                .count("with_poms", progress.as_ref().map_or(0, |p| p.poms))
+        }
+        Commands::DownloadPoms {
+            build_files,
+            patterns,
+            strategy,
+            tarball_threshold,
+            concurrency,
+        } => {
+            let scraper = Scraper::new(&cli.forge, cli.tokens, data.clone(), notifier.clone())
+                .with_build_systems(build_files)
+                .with_patterns(patterns)
+                .with_strategy(strategy, tarball_threshold);
+            scraper.download_files(concurrency).await?;
             data.update_csv_has_pom().await?;
+            csv_counts(&data, Outcome::default())?
+        }
+        Commands::BuildEffective {
+            mvn_jobs,
+            timeout,
+            retry_failed,
+        } => {
+            let options = effective::Options {
+                jobs: mvn_jobs
+                    .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get())),
+                timeout: Duration::from_secs(timeout),
+                retry_failed,
+                maven: cli.maven,
+            };
+            let summary = effective::build_all(&data, options).await?;
+            summary.print();
+            Outcome::default()
+                .count("succeeded", summary.succeeded)
+                .count("failed", summary.failed)
+                .count("skipped", summary.skipped)
+                .count("poms_built", summary.poms_built)
+                .count("poms_failed", summary.poms_failed)
+                .count("poms_timed_out", summary.poms_timed_out)
+                .errors(summary.failed)
+                .output(data.effective_status())
+        }
+        Commands::Analyze {
+            effective,
+            chunk,
+            shards,
+            bootstrap,
+            max_per_owner,
+            output_format,
+            mvn_jobs,
+            fast_path,
+            incremental,
+            exclude_duplicates,
+        } => {
+            let options = analyzer::Options {
+                build_effective: effective,
+                chunk,
+                bootstrap_iterations: bootstrap,
+                max_per_owner,
+                output_format,
+                mvn_jobs,
+                fast_path,
+                incremental,
+                exclude_duplicates,
+                maven: cli.maven,
+            };
+            let mut projects = vec![data.output_path("projects", "json", chunk)];
+            if let OutputFormat::Arrow = output_format {
+                // Sharded runs leave the Arrow tables in the files of their chunks
+                let chunks = match shards {
+                    Some(shards) => Chunk::all(shards.get()).into_iter().map(Some).collect(),
+                    None => vec![chunk],
+                };
+                projects.extend(
+                    chunks
+                        .into_iter()
+                        .map(|chunk| data.output_path("projects", "arrow", chunk)),
+                );
+            }
+            let report_path = data.output_path("report", "json", chunk);
+            let report = match shards {
+                Some(shards) => analyzer::analyze_shards(data, options, shards.get()).await?,
+                None => analyzer::analyze(data, options).await?,
+            };
+            report.print();
+            let outcome = Outcome::default()
+                .count("repos", report.total)
+                .count("has_external_repos", report.has_external_repos)
+                .count("has_distro_repos", report.has_distro_repos.len())
+                .errors(report.errors.len())
+                .output(report_path);
+            projects.into_iter().fold(outcome, Outcome::output)
+        }
+        Commands::Dedup => {
+            let report = dedup::dedup_all(&data).await?;
+            report.print();
+            Outcome::default()
+                .count("projects", report.projects)
+                .count("clusters", report.clusters.len())
+                .output(data.dedup_report())
         }
-        Commands::Analyze { effective } => {
-            let report = analyzer::analyze(data, effective).await?;
+        Commands::MergeReports { inputs, out } => {
+            let inputs = if inputs.is_empty() {
+                data.partial_reports()?
+            } else {
+                inputs
+            };
+            if inputs.is_empty() {
+                bail!("No reports to merge found");
+            }
+
+            let mut merged: Option<Report> = None;
+            let (inputs, paths) = (inputs.len(), inputs);
+            for path in paths {
+                let partial = data::read_report_file(&path)?;
+                match merged.as_mut() {
+                    Some(report) => report.merge(partial),
+                    None => merged = Some(partial),
+                }
+            }
+
+            let report = merged.unwrap();
+            let out = match out {
+                Some(out) => {
+                    data::write_report_file(&out, &report)?;
+                    out
+                }
+                None => {
+                    data.write_report(report.clone(), None)?;
+                    data.output_path("report", "json", None)
+                }
+            };
             report.print();
+            Outcome::default()
+                .count("repos", report.total)
+                .count("inputs", inputs)
+                .errors(report.errors.len())
+                .output(out)
         }
         Commands::AnalyzeHostnames => {
             analyzer::most_popular_hostnames(data)?;
+            Outcome::default()
         }
-        Commands::CreateRandomSubset { n, from, out } => {
-            create_subset(n, from, out)?;
+        Commands::ExportGraph { format, out } => {
+            if cli.json && out.is_none() {
+                bail!("Stdout is for the summary with --json, pass --out to write the graph");
+            }
+            let graph = Graph::from_projects(&data.read_projects()?);
+            let mut writer: BufWriter<Box<dyn Write>> = match &out {
+                Some(path) => BufWriter::new(Box::new(File::create(path)?)),
+                None => BufWriter::new(Box::new(io::stdout().lock())),
+            };
+            graph.write(format, &mut writer)?;
+            writer.flush()?;
+            Outcome {
+                outputs: out.into_iter().collect(),
+                ..Default::default()
+            }
+        }
+        Commands::ExportProjects { format, out } => {
+            let projects = data.read_projects()?;
+            let out = out.unwrap_or_else(|| data.output_path("projects", format.extension(), None));
+            match format {
+                ProjectsFormat::Ndjson => tables::write_ndjson(&out, &projects)?,
+                ProjectsFormat::Parquet => tables::write_parquet(&out, &projects)?,
+            }
+            Outcome::default()
+                .count("projects", projects.len())
+                .output(out)
+        }
+        Commands::CompareReports {
+            sample,
+            population,
+            top,
+        } => {
+            let sample = data::read_report_file(&sample)?;
+            let population = data::read_report_file(&population)?;
+            analyzer::compare::compare(&sample, &population, top).print();
+            Outcome::default()
+        }
+        Commands::CreateRandomSubset {
+            n,
+            from,
+            out,
+            options,
+        } => {
+            let summary = create_subset(n, from, out.clone(), &options, cli.forge.forge)?;
+            summary.print(&out);
+            let outcome = Outcome::default()
+                .count("created", summary.created)
+                .count("repaired", summary.repaired)
+                .count("existing", summary.existing);
+            csv_counts(&Data::open_forge(&out, cli.forge.forge), outcome)?
+        }
+        Commands::SplitDataset { out, ratio, seed } => {
+            split_dataset(data_dir, out.clone(), ratio, seed, cli.forge.forge)?;
+            Outcome::default()
+                .output(out.join("train"))
+                .output(out.join("test"))
         }
         Commands::ConsolidateCsv => {
             data.update_csv_has_pom().await?;
+            csv_counts(&data, Outcome::default())?
+        }
+        Commands::ExportDataset {
+            out,
+            anonymize_key,
+            license_filter,
+            allowed_licenses,
+        } => {
+            let out_dir = out.clone();
+            let out = Data::new(&out).await?;
+            let anonymizer = anonymize_key.as_deref().map(Anonymizer::new);
+            let license_filter = license_filter.then(|| LicenseFilter::new(allowed_licenses));
+            let summary = tokio::task::spawn_blocking(move || {
+                export::export(&data, &out, anonymizer.as_ref(), license_filter.as_ref())
+            })
+            .await??;
+            summary.print();
+            Outcome::default()
+                .count("repos", summary.repos)
+                .count("files", summary.files)
+                .count("excluded", summary.excluded.values().sum())
+                .output(out_dir)
         }
         Commands::PrintReport => {
             let report = data.read_report()?;
             report.print();
+            Outcome::default()
+                .count("repos", report.total)
+                .errors(report.errors.len())
+        }
+        Commands::Bench { poms, effective } => {
+            let options = bench::Options {
+                poms,
+                effective,
+                maven: cli.maven,
+            };
+            let result =
+                tokio::task::spawn_blocking(move || bench::bench(&data, &options)).await??;
+            result.print();
+            Outcome::default()
+                .count("poms", result.parse.items)
+                .count("parse_ms", result.parse.elapsed.as_millis() as usize)
+                .count(
+                    "fast_path_ms",
+                    result.fast_path.elapsed.as_millis() as usize,
+                )
+                .count("fast_path_fallbacks", result.fast_path_fallbacks)
+                .count("fast_path_mismatches", result.fast_path_mismatches)
+                .count("effective_built", result.effective.len())
+                .count("effective_failed", result.effective_failed)
+                .errors(result.parse_errors)
         }
         Commands::FetchWorkflows => {
-            let scraper = Scraper::new(cli.tokens, data.clone());
+            let scraper = Scraper::new(&cli.forge, cli.tokens, data.clone(), notifier.clone());
             let n = scraper.download_all_workflows().await?;
             println!("Fetched {n} workflows");
+            Outcome::default().count("repos_with_workflows", n)
+        }
+        Commands::FetchBuildFiles => {
+            let scraper = Scraper::new(&cli.forge, cli.tokens, data.clone(), notifier.clone());
+            let n = scraper.download_all_build_files().await?;
+            println!("Fetched build files of {n} repos");
+            Outcome::default().count("repos_with_build_files", n)
+        }
+        Commands::FetchReleases { distribution_only } => {
+            let scraper = Scraper::new(&cli.forge, cli.tokens, data.clone(), notifier.clone());
+            let n = scraper.fetch_all_releases(distribution_only).await?;
+            println!("Found GitHub releases for {n} repos");
+            Outcome::default().count("repos_with_releases", n)
+        }
+        Commands::RetryFailed {
+            build_files,
+            patterns,
+            concurrency,
+        } => {
+            let scraper = Scraper::new(&cli.forge, cli.tokens, data.clone(), notifier.clone())
+                .with_build_systems(build_files)
+                .with_patterns(patterns);
+            let summary = scraper.retry_failed(concurrency).await?;
+            data.update_csv_has_pom().await?;
+            summary.print();
+            Outcome::default()
+                .count("retried", summary.retried)
+                .count("succeeded", summary.succeeded)
+                .count("skipped", summary.skipped)
+                .errors(summary.failed)
+                .output(data.errors_log())
+        }
+        Commands::BackfillMetadata => {
+            let scraper = Scraper::new(&cli.forge, cli.tokens, data.clone(), notifier.clone());
+            let (backfilled, missing) = scraper.backfill_metadata().await?;
+            println!("Backfilled the metadata of {backfilled} repos, {missing} repos left without");
+            Outcome::default()
+                .count("backfilled", backfilled)
+                .count("missing", missing)
+        }
+        Commands::FetchPackages => {
+            let scraper = Scraper::new(&cli.forge, cli.tokens, data.clone(), notifier.clone());
+            let n = scraper.fetch_all_packages().await?;
+            println!("Found maven packages for {n} owners");
+            Outcome::default().count("owners_with_packages", n)
+        }
+        Commands::FetchParents {
+            concurrency,
+            retry_failed,
+        } => {
+            let options = parents::Options {
+                jobs: concurrency,
+                retry_failed,
+                local_repo: cli.maven.local_repo.clone().unwrap(),
+            };
+            let summary = parents::fetch_all(&data, options).await?;
+            summary.print();
+            Outcome::default()
+                .count("succeeded", summary.succeeded)
+                .count("failed", summary.failed)
+                .count("skipped", summary.skipped)
+                .count("parents_resolved", summary.parents_resolved)
+                .count("parents_downloaded", summary.parents_downloaded)
+                .count("parents_unresolved", summary.parents_unresolved)
+                .errors(summary.failed)
+                .output(data.parent_status())
+        }
+        Commands::Progress { max_id } => {
+            let max_id = match max_id {
+                Some(max_id) => max_id,
+                None => {
+                    Scraper::new(&cli.forge, cli.tokens, data.clone(), notifier.clone())
+                        .max_repository_id()
+                        .await?
+                }
+            };
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let history = data.history();
+            progress::print(&history, max_id, now);
+            Outcome::default()
+                .count("max_id", max_id)
+                .count("last_id", data.get_last_id()?)
         }
+        #[cfg(feature = "serve")]
+        Commands::Serve { port, bind } => {
+            maven_scraper_core::serve::serve(data.clone(), SocketAddr::new(bind, port)).await?;
+            Outcome::default()
+        }
+        Commands::RemoveRepo { repo } => {
+            let removed = data.remove_repo(&repo)?;
+            if removed.is_empty() {
+                bail!("No repository {repo} found");
+            }
+            for tombstone in &removed {
+                println!("Removed {} ({})", tombstone.name, tombstone.id);
+            }
+            println!("Analyze and export the data dir again to remove it from reports and exports");
+            Outcome::default().count("removed", removed.len())
+        }
+        Commands::Checksums { per_directory } => {
+            let data_dir = data_dir.clone();
+            let (manifests, files) =
+                tokio::task::spawn_blocking(move || checksums::write(&data_dir, per_directory))
+                    .await??;
+            println!("Wrote checksums of {files} files to {manifests:?}");
+            Outcome {
+                outputs: manifests,
+                ..Default::default()
+            }
+            .count("files", files)
+        }
+        Commands::VerifyChecksums => {
+            let data_dir = data_dir.clone();
+            let verification =
+                tokio::task::spawn_blocking(move || checksums::verify(&data_dir)).await??;
+            verification.print();
+            if !verification.success() {
+                bail!("Checksum verification failed");
+            }
+            Outcome::default()
+                .count("ok", verification.ok)
+                .count("unlisted", verification.unlisted.len())
+        }
+        Commands::RollbackState { timestamp, list } => {
+            if list {
+                let snapshots = data.snapshots()?;
+                for snapshot in &snapshots {
+                    println!(
+                        "{}  last_id {:>10}  repos {:>8}  fetched {:>8}",
+                        snapshot.timestamp,
+                        snapshot.last_id(data.forge()),
+                        snapshot.store.rows,
+                        snapshot.store.fetched
+                    );
+                }
+                Outcome::default().count("snapshots", snapshots.len())
+            } else {
+                let snapshot = data.rollback_state(timestamp)?;
+                println!(
+                    "Rolled back to the state of {} with last_id {}",
+                    snapshot.timestamp,
+                    snapshot.last_id(data.forge())
+                );
+
+                let store = data.store_metadata()?;
+                if store.header != snapshot.store.header {
+                    warn!(
+                        "The csv header changed since the snapshot: {:?} -> {:?}",
+                        snapshot.store.header, store.header
+                    );
+                }
+                if store.rows != snapshot.store.rows {
+                    println!(
+                        "The csv has {} rows, {} at the snapshot. Repos stored since are kept and skipped when scraping again",
+                        store.rows, snapshot.store.rows
+                    );
+                }
+                Outcome::default()
+                    .count("timestamp", snapshot.timestamp as usize)
+                    .count("last_id", snapshot.last_id(data.forge()) as usize)
+            }
+        }
+        Commands::MigrateToSqlite => {
+            let migrate = data.clone();
+            let (repos, fetched, files) =
+                tokio::task::spawn_blocking(move || migrate.migrate_to_sqlite()).await??;
+            println!("Imported {repos} repos, {fetched} fetched, {files} files");
+            Outcome::default()
+                .count("repos", repos)
+                .count("fetched", fetched)
+                .count("files", files)
+                .output(data_dir.join(data::sqlite::FILE_NAME))
+        }
+        Commands::Profiles => unreachable!("profiles are listed without opening the data dir"),
         Commands::DistinctReposPerHostname => {
-            let report = data.read_report().unwrap();
+            let report = data.read_report()?;
             analyzer::distinct_repos_per_hostname(report.external_repos);
+            Outcome::default()
         }
-    }
+    };
 
-    Ok(())
+    Ok(outcome)
 }
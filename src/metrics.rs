@@ -0,0 +1,182 @@
+//! Prometheus metrics of long-running scrapes, served in the text exposition format with
+//! `--metrics-addr`. The metrics are process wide, like a default Prometheus registry.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+pub static METRICS: Metrics = Metrics::new();
+
+#[derive(Debug)]
+pub struct Metrics {
+    pub repos_listed: AtomicU64,
+    pub repos_stored: AtomicU64,
+    pub files_downloaded: AtomicU64,
+    pub bytes_written: AtomicU64,
+    pub rate_limited: AtomicU64,
+    pub rate_limit_sleeps: AtomicU64,
+    pub rate_limit_sleep_seconds: AtomicU64,
+    /// Conditional requests answered with 304 Not Modified
+    pub not_modified: AtomicU64,
+    /// Index of the token the last request was sent with
+    pub token_index: AtomicU64,
+    pub last_id: AtomicU64,
+    /// Listed repos waiting to be loaded
+    pub repos_queued: AtomicU64,
+    /// Repos whose files are being downloaded
+    pub downloads_in_flight: AtomicU64,
+    errors: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl Metrics {
+    const fn new() -> Self {
+        Self {
+            repos_listed: AtomicU64::new(0),
+            repos_stored: AtomicU64::new(0),
+            files_downloaded: AtomicU64::new(0),
+            bytes_written: AtomicU64::new(0),
+            rate_limited: AtomicU64::new(0),
+            rate_limit_sleeps: AtomicU64::new(0),
+            rate_limit_sleep_seconds: AtomicU64::new(0),
+            not_modified: AtomicU64::new(0),
+            token_index: AtomicU64::new(0),
+            last_id: AtomicU64::new(0),
+            repos_queued: AtomicU64::new(0),
+            downloads_in_flight: AtomicU64::new(0),
+            errors: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Counts a failed repo (or batch of them) by the kind of error, e.g. `http`
+    pub fn error(&self, kind: &'static str) {
+        *self.errors.lock().unwrap().entry(kind).or_insert(0) += 1;
+    }
+
+    /// The metrics in the Prometheus text exposition format
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: &AtomicU64| {
+            let value = value.load(Ordering::Relaxed);
+            let _ = writeln!(out, "# HELP rp_{name} {help}\n# TYPE rp_{name} {kind}");
+            let _ = writeln!(out, "rp_{name} {value}");
+        };
+
+        let counters = [
+            (
+                "repos_listed_total",
+                "Repos listed by the forge",
+                &self.repos_listed,
+            ),
+            (
+                "repos_stored_total",
+                "Repos selected and stored",
+                &self.repos_stored,
+            ),
+            (
+                "files_downloaded_total",
+                "Files downloaded",
+                &self.files_downloaded,
+            ),
+            (
+                "bytes_written_total",
+                "Bytes of downloaded files written",
+                &self.bytes_written,
+            ),
+            (
+                "rate_limited_total",
+                "Responses that were rate limited",
+                &self.rate_limited,
+            ),
+            (
+                "rate_limit_sleeps_total",
+                "Times all tokens were rate limited",
+                &self.rate_limit_sleeps,
+            ),
+            (
+                "rate_limit_sleep_seconds_total",
+                "Seconds slept waiting for a rate limit reset",
+                &self.rate_limit_sleep_seconds,
+            ),
+            (
+                "not_modified_total",
+                "Conditional requests answered with 304 Not Modified",
+                &self.not_modified,
+            ),
+        ];
+        for (name, help, value) in counters {
+            metric(name, "counter", help, value);
+        }
+
+        let gauges = [
+            (
+                "token_index",
+                "Index of the token last used",
+                &self.token_index,
+            ),
+            ("last_id", "Id of the last listed repo", &self.last_id),
+            (
+                "repos_queued",
+                "Listed repos waiting to be loaded",
+                &self.repos_queued,
+            ),
+            (
+                "downloads_in_flight",
+                "Repos whose files are being downloaded",
+                &self.downloads_in_flight,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            metric(name, "gauge", help, value);
+        }
+
+        let _ = writeln!(
+            out,
+            "# HELP rp_errors_total Failed repos by kind of error\n# TYPE rp_errors_total counter"
+        );
+        for (kind, count) in self.errors.lock().unwrap().iter() {
+            let _ = writeln!(out, "rp_errors_total{{kind=\"{kind}\"}} {count}");
+        }
+
+        out
+    }
+}
+
+/// Serves the metrics on every path of `addr` in the background
+pub async fn serve(addr: SocketAddr) -> io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving metrics on http://{addr}/metrics");
+
+    tokio::spawn(async move {
+        loop {
+            let mut stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Failed accepting metrics connection: {e}");
+                    continue;
+                }
+            };
+            tokio::spawn(async move {
+                // The request itself doesn't matter, only one thing is served
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+
+                let body = METRICS.render();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                if let Err(e) = stream.write_all(response.as_bytes()).await {
+                    warn!("Failed serving metrics: {e}");
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
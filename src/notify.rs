@@ -0,0 +1,195 @@
+//! Webhook notifications about long-running commands, so a multi-day run failing overnight
+//! doesn't go unnoticed.
+
+use clap::{Args, ValueEnum};
+use reqwest::Client;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tracing::warn;
+
+/// Amount of recent outcomes the error rate is computed over
+const ERROR_WINDOW: usize = 100;
+/// Outcomes needed before the error rate is considered
+const ERROR_MIN_SAMPLES: usize = 20;
+
+/// Payload of the webhook
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub enum NotifyFormat {
+    /// JSON with the event and message
+    #[default]
+    Generic,
+    /// Slack incoming webhooks (also Mattermost, Discord's `/slack` endpoints)
+    Slack,
+    /// A Matrix `m.room.message` event
+    Matrix,
+    /// Plain text with a title header, as ntfy expects
+    Ntfy,
+}
+
+#[derive(Debug, Clone, Args)]
+pub struct NotifyOptions {
+    /// Webhook notified when a long-running command finishes or fails, hits the error rate
+    /// threshold or runs out of tokens
+    #[arg(
+        long = "notify-url",
+        env = "NOTIFY_URL",
+        global = true,
+        value_name = "URL"
+    )]
+    pub url: Option<String>,
+
+    #[arg(
+        id = "notify_format",
+        long = "notify-format",
+        value_enum,
+        default_value_t,
+        global = true
+    )]
+    pub format: NotifyFormat,
+
+    /// Only notify about finished commands that ran at least this long
+    #[arg(
+        long = "notify-min-duration",
+        default_value_t = 60,
+        global = true,
+        value_name = "SECONDS"
+    )]
+    pub min_duration: u64,
+
+    /// Notify when more than this fraction of the recent repos failed to scrape
+    #[arg(
+        long = "notify-error-rate",
+        default_value_t = 0.5,
+        global = true,
+        value_name = "FRACTION"
+    )]
+    pub error_rate: f64,
+
+    /// Notify when all tokens have been rate limited for this long
+    #[arg(
+        long = "notify-tokens-exhausted",
+        default_value_t = 30,
+        global = true,
+        value_name = "MINUTES"
+    )]
+    pub tokens_exhausted: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Event {
+    Finished,
+    Failed,
+    ErrorRate,
+    TokensExhausted,
+}
+
+impl Event {
+    fn title(self) -> &'static str {
+        match self {
+            Event::Finished => "Finished",
+            Event::Failed => "Failed",
+            Event::ErrorRate => "High error rate",
+            Event::TokensExhausted => "Tokens exhausted",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    options: NotifyOptions,
+    /// What is running, e.g. the command and data dir, prefixed to every message
+    source: String,
+    client: Client,
+}
+
+impl Notifier {
+    pub fn new(options: NotifyOptions, source: String) -> Self {
+        Self {
+            options,
+            source,
+            client: Client::new(),
+        }
+    }
+
+    pub fn options(&self) -> &NotifyOptions {
+        &self.options
+    }
+
+    pub fn tokens_exhausted_after(&self) -> Duration {
+        Duration::from_secs(self.options.tokens_exhausted * 60)
+    }
+
+    /// Sends a notification if a webhook is configured, failures are only logged
+    pub async fn notify(&self, event: Event, message: &str) {
+        let Some(url) = &self.options.url else {
+            return;
+        };
+
+        let text = format!("[{}] {}: {message}", self.source, event.title());
+        let request = self.client.post(url).timeout(Duration::from_secs(30));
+        let request = match self.options.format {
+            NotifyFormat::Generic => request.json(&json!({
+                "event": event,
+                "source": self.source,
+                "message": message,
+            })),
+            NotifyFormat::Slack => request.json(&json!({ "text": text })),
+            NotifyFormat::Matrix => request.json(&json!({ "msgtype": "m.text", "body": text })),
+            NotifyFormat::Ntfy => request
+                .header("Title", format!("{}: {}", self.source, event.title()))
+                .body(message.to_string()),
+        };
+
+        match request.send().await {
+            Ok(resp) if !resp.status().is_success() => {
+                warn!("Notification webhook responded with {}", resp.status())
+            }
+            Ok(_) => {}
+            Err(e) => warn!("Failed sending notification: {e}"),
+        }
+    }
+}
+
+/// Failure rate over the recent outcomes, alerting once when it goes over the threshold
+#[derive(Debug)]
+pub struct ErrorRate {
+    window: VecDeque<bool>,
+    threshold: f64,
+    alerted: bool,
+}
+
+impl ErrorRate {
+    pub fn new(threshold: f64) -> Self {
+        Self {
+            window: VecDeque::with_capacity(ERROR_WINDOW),
+            threshold,
+            alerted: false,
+        }
+    }
+
+    /// Records an outcome, returning the error rate when it just went over the threshold
+    pub fn record(&mut self, failed: bool) -> Option<f64> {
+        if self.window.len() == ERROR_WINDOW {
+            self.window.pop_front();
+        }
+        self.window.push_back(failed);
+        if self.window.len() < ERROR_MIN_SAMPLES {
+            return None;
+        }
+
+        let rate =
+            self.window.iter().filter(|&&failed| failed).count() as f64 / self.window.len() as f64;
+        if rate <= self.threshold {
+            self.alerted = false;
+            None
+        } else if !self.alerted {
+            self.alerted = true;
+            Some(rate)
+        } else {
+            None
+        }
+    }
+}
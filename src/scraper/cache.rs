@@ -0,0 +1,105 @@
+//! On-disk cache of the validators (`ETag`, `Last-Modified`) of GitHub responses, so scraping a
+//! data dir again sends conditional requests. GitHub answers those with `304 Not Modified` when
+//! nothing changed, which doesn't count against the rate limit of the token.
+//!
+//! API responses are cached with the fields read from their body, to return them again on a 304.
+//! Responses of urls pinned to a sha never change and aren't cached. Downloaded files are their
+//! own body, so only their validators and the url they were downloaded from are cached.
+//!
+//! The cache keeps a file per downloaded file, so it's only used with `--http-cache`.
+
+use reqwest::{header, RequestBuilder, Response};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    /// The url the response was for
+    pub url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+    /// The fields read from the body of API responses
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<Value>,
+}
+
+impl Entry {
+    /// The validators of a response to `url`, without a body
+    pub fn of(url: &str, resp: &Response) -> Self {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from)
+        };
+        Entry {
+            url: url.to_string(),
+            etag: header(header::ETAG),
+            last_modified: header(header::LAST_MODIFIED),
+            body: None,
+        }
+    }
+
+    /// Makes a request conditional on the response having changed since this entry
+    pub fn conditional(&self, mut request: RequestBuilder) -> RequestBuilder {
+        if let Some(etag) = &self.etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+        }
+        request
+    }
+}
+
+/// Entries stored as json files named by the SHA-256 of their key, in subdirectories by the first
+/// two characters of the hash to keep directories small
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    dir: PathBuf,
+}
+
+impl HttpCache {
+    pub fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        let hash = format!("{:x}", Sha256::digest(key.as_bytes()));
+        self.dir.join(&hash[..2]).join(format!("{hash}.json"))
+    }
+
+    /// The cached entry of a key, `None` if there is none or it can't be read
+    pub async fn get(&self, key: &str) -> Option<Entry> {
+        let json = match tokio::fs::read(self.path(key)).await {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                warn!("Failed reading cached response of {key}: {e}");
+                return None;
+            }
+        };
+        serde_json::from_slice(&json)
+            .inspect_err(|e| warn!("Invalid cached response of {key}: {e}"))
+            .ok()
+    }
+
+    pub async fn put(&self, key: &str, entry: &Entry) -> io::Result<()> {
+        let path = self.path(key);
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        // Written to a temporary file first, so concurrent readers never see a partial entry
+        let tmp = path.with_extension("json.part");
+        tokio::fs::write(&tmp, serde_json::to_vec(entry)?).await?;
+        tokio::fs::rename(tmp, path).await
+    }
+}
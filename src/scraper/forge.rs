@@ -0,0 +1,515 @@
+//! The code forges repositories are scraped from, and what their clients share: errors, rate
+//! limit budgets of the tokens and retrying.
+
+use crate::data::{self, ForgeKind};
+use crate::metrics::METRICS;
+use crate::notify::{Event, Notifier};
+use crate::scraper::selection::Selection;
+use crate::{CsvRepo, Repo};
+use async_trait::async_trait;
+use reqwest::{header, RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::future::Future;
+use std::io;
+use std::sync::atomic::Ordering::Relaxed;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+use tokio::task::yield_now;
+use tokio::time::sleep;
+use tracing::{error, warn};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("reqwest error occurred {0:?}")]
+    Reqwest(#[from] reqwest::Error),
+    #[error("rate limit hit {0}")]
+    RateLimit(StatusCode),
+    #[error("other http error: {0}")]
+    HttpError(StatusCode),
+
+    #[error("Data error occurred: {0:?}")]
+    DataError(#[from] data::Error),
+
+    #[error("Response did not contain requested data")]
+    EmptyData,
+    #[error("Invalid response: {0}")]
+    Serde(#[from] serde_json::Error),
+    #[error("IO Error {0}")]
+    Io(#[from] io::Error),
+}
+
+impl Error {
+    /// Label of the error in the metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Reqwest(_) => "reqwest",
+            Error::RateLimit(_) => "rate_limit",
+            Error::HttpError(_) => "http",
+            Error::DataError(_) => "data",
+            Error::EmptyData => "empty_data",
+            Error::Serde(_) => "invalid_response",
+            Error::Io(_) => "io",
+        }
+    }
+
+    /// Status of the failed HTTP response
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::RateLimit(status) | Error::HttpError(status) => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether the request may succeed when sent again later: network errors, rate limits,
+    /// server errors and invalid responses, but not e.g. a 404 of a deleted repo
+    pub fn retryable(&self) -> bool {
+        match self {
+            Error::Reqwest(_) | Error::RateLimit(_) | Error::EmptyData | Error::Serde(_) => true,
+            Error::HttpError(status) => status.is_server_error(),
+            Error::DataError(_) | Error::Io(_) => false,
+        }
+    }
+}
+
+// Forge selection, shared by all commands
+#[derive(Debug, Clone, clap::Args)]
+pub struct Options {
+    /// Forge to scrape, every forge has its own repos, poms and last id in the data dir
+    #[arg(long, value_enum, global = true, default_value_t)]
+    pub forge: ForgeKind,
+
+    /// GitLab instance to scrape with `--forge gitlab`
+    #[arg(
+        long,
+        env = "GITLAB_URL",
+        global = true,
+        default_value = "https://gitlab.com"
+    )]
+    pub gitlab_url: String,
+
+    /// GitLab tokens to use when fetching from GitLab
+    #[arg(
+        long,
+        env = "GITLAB_TOKENS",
+        hide_env_values = true,
+        value_delimiter = ','
+    )]
+    pub gitlab_tokens: Vec<String>,
+
+    /// Cache the validators of GitHub responses in `http-cache/`, so scraping the data dir again
+    /// sends conditional requests
+    #[arg(long, global = true)]
+    pub http_cache: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Node {
+    pub path: String,
+}
+
+/// The commit the files of a repository are downloaded at
+#[derive(Debug, Clone)]
+pub struct Head {
+    pub sha: String,
+    pub default_branch: String,
+    /// The current name of the repository, if it was renamed since it was stored
+    pub renamed_to: Option<String>,
+}
+
+/// The files of a repository at a commit
+#[derive(Debug, Default)]
+pub struct Tree {
+    pub nodes: Vec<Node>,
+    /// Whether the forge truncated the listing, so it had to be listed with a fallback
+    pub truncated: bool,
+}
+
+/// A repository as enumerated by a forge, in order of increasing id
+#[derive(Debug)]
+pub struct ListedRepo {
+    pub id: usize,
+    /// What [Forge::load_repositories] takes
+    pub node_id: String,
+    pub fork: bool,
+}
+
+/// A repository with the details deciding whether it's scraped, see [Selection]
+#[derive(Debug)]
+pub struct LoadedRepo {
+    pub repo: Repo,
+    pub languages: Vec<String>,
+    /// SPDX id (GitHub) or license key (GitLab)
+    pub license: Option<String>,
+    pub stars: u64,
+    pub forks: u64,
+    /// e.g. `2024-01-01T12:00:00Z`
+    pub created_at: Option<String>,
+    /// In KB, only reported by GitHub
+    pub disk_usage: Option<u64>,
+    /// Only loaded if [Selection::needs_topics]
+    pub topics: Vec<String>,
+    /// Seconds since the unix epoch of the last push (GitHub) or activity (GitLab)
+    pub pushed_at: Option<u64>,
+    pub archived: bool,
+}
+
+impl LoadedRepo {
+    /// Copies the license and metadata into the stored row of the repo. A missing license doesn't
+    /// clear the stored one, forges may leave it out for repos outside the selection.
+    pub fn enrich(&self, repo: &mut CsvRepo) {
+        if self.license.is_some() {
+            repo.license = self.license.clone();
+        }
+        repo.stars = Some(self.stars);
+        repo.forks = Some(self.forks);
+        repo.created_at = self.created_at.clone();
+        repo.disk_usage = self.disk_usage;
+    }
+}
+
+#[async_trait]
+pub trait Forge: Debug + Send + Sync {
+    fn kind(&self) -> ForgeKind;
+
+    /// The next page of repositories with an id above `since`
+    async fn list_repositories(&self, since: usize) -> Result<Vec<ListedRepo>, Error>;
+
+    /// Loads repositories by their [ListedRepo::node_id], skipping ones that no longer exist.
+    /// Details only needed by repos matching the selection may be left out for the others.
+    async fn load_repositories(
+        &self,
+        node_ids: &[String],
+        selection: &Selection,
+    ) -> Result<Vec<LoadedRepo>, Error>;
+
+    /// Resolves the default branch of a repository to its current commit, so the tree and files
+    /// are downloaded from the same commit even when it's pushed to meanwhile
+    async fn head(&self, repo: &Repo) -> Result<Head, Error>;
+
+    /// All files of a repository at a commit
+    async fn tree(&self, repo: &Repo, sha: &str) -> Result<Tree, Error>;
+
+    /// Downloads a file at a commit into the data dir, unless it was downloaded before and isn't
+    /// to be overwritten
+    ///
+    /// path being the path inside the repo
+    async fn download_file(
+        &self,
+        repo: &Repo,
+        sha: &str,
+        path: &str,
+        overwrite: bool,
+    ) -> Result<(), Error>;
+}
+
+/// The rate limited resource of REST requests, the only one of GitLab
+pub const REST: &str = "core";
+/// The rate limited resource of GitHub's GraphQL API
+pub const GRAPHQL: &str = "graphql";
+
+/// How long a token is left alone after a rate limit that didn't say until when
+const RATE_LIMIT_BACKOFF: u64 = 60;
+
+/// Seconds since the unix epoch of a UTC timestamp like `2024-01-01T12:00:00Z`
+pub fn parse_timestamp(timestamp: &str) -> Option<u64> {
+    let (date, time) = timestamp.trim_end_matches('Z').split_once('T')?;
+    let mut date = date.splitn(3, '-').map(str::parse::<i64>);
+    let (year, month, day) = (date.next()?.ok()?, date.next()?.ok()?, date.next()?.ok()?);
+    let mut time = time
+        .splitn(3, ':')
+        .map(|part| part.split('.').next()?.parse::<i64>().ok());
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 {
+        return None;
+    }
+
+    // Days since the epoch of a date in the proleptic Gregorian calendar
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+
+    u64::try_from(days * 86_400 + hour * 3600 + minute * 60 + second).ok()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// What a token has left of a rate limit, as last reported by the forge
+#[derive(Debug, Clone, Copy)]
+struct Budget {
+    remaining: u64,
+    /// Seconds since the unix epoch
+    reset: u64,
+}
+
+/// The API tokens of a forge. Requests use the token with the most budget left, as reported by
+/// the rate limit headers of earlier responses.
+#[derive(Debug)]
+pub struct Tokens {
+    tokens: Vec<String>,
+    /// Per rate limited resource (e.g. `core`, `graphql`), the budget of every token
+    budgets: Mutex<HashMap<String, Vec<Option<Budget>>>>,
+    notifier: Notifier,
+    /// Since when all tokens are rate limited, and whether that was notified
+    exhausted_since: Mutex<Option<(Instant, bool)>>,
+}
+
+impl Tokens {
+    pub fn new(tokens: Vec<String>, notifier: Notifier) -> Self {
+        Self {
+            tokens,
+            budgets: Default::default(),
+            notifier,
+            exhausted_since: Mutex::new(None),
+        }
+    }
+
+    /// Records the budget of a token, e.g. from the `rateLimit` of a GraphQL response
+    pub fn record(&self, token: &str, resource: &str, remaining: u64, reset: u64) {
+        let Some(index) = self.tokens.iter().position(|t| t == token) else {
+            return;
+        };
+        let mut budgets = self.budgets.lock().unwrap();
+        let budgets = budgets
+            .entry(resource.to_string())
+            .or_insert_with(|| vec![None; self.tokens.len()]);
+        budgets[index] = Some(Budget { remaining, reset });
+    }
+
+    /// Marks a token as rate limited, unless its budget already says so
+    fn limited(&self, token: &str, resource: &str, retry_after: Option<u64>) {
+        let index = self.tokens.iter().position(|t| t == token);
+        let known = index.and_then(|index| {
+            let budgets = self.budgets.lock().unwrap();
+            budgets.get(resource)?[index].filter(|b| b.remaining == 0 && b.reset > now())
+        });
+        if known.is_none() {
+            let reset = now() + retry_after.unwrap_or(RATE_LIMIT_BACKOFF);
+            self.record(token, resource, 0, reset);
+        }
+    }
+
+    /// The token with the most budget left for a resource, tokens without a known budget first.
+    /// When all are exhausted, waits until the earliest reset.
+    async fn pick(&self, resource: &str) -> &str {
+        loop {
+            let wait = {
+                let budgets = self.budgets.lock().unwrap();
+                let now = now();
+                let budgets = budgets.get(resource);
+                let left = |index: usize| match budgets.and_then(|b| b[index]) {
+                    Some(budget) if budget.reset > now => budget.remaining,
+                    _ => u64::MAX,
+                };
+
+                let best = (0..self.tokens.len())
+                    .max_by_key(|&index| (left(index), Reverse(index)))
+                    .expect("no tokens");
+                if left(best) > 0 {
+                    METRICS.token_index.store(best as u64, Relaxed);
+                    return &self.tokens[best];
+                }
+
+                let reset = budgets
+                    .into_iter()
+                    .flatten()
+                    .flatten()
+                    .map(|budget| budget.reset)
+                    .min()
+                    .unwrap_or(now);
+                reset.saturating_sub(now) + 1
+            };
+
+            warn!("All tokens are rate limited, sleeping {wait} seconds until the earliest reset");
+            METRICS.rate_limit_sleeps.fetch_add(1, Relaxed);
+            METRICS.rate_limit_sleep_seconds.fetch_add(wait, Relaxed);
+            self.exhausted().await;
+            sleep(Duration::from_secs(wait)).await;
+        }
+    }
+
+    /// Notifies once all tokens have been rate limited for too long
+    async fn exhausted(&self) {
+        let exhausted_for = {
+            let mut exhausted = self.exhausted_since.lock().unwrap();
+            let (since, notified) = exhausted.get_or_insert((Instant::now(), false));
+            if *notified || since.elapsed() < self.notifier.tokens_exhausted_after() {
+                return;
+            }
+            *notified = true;
+            since.elapsed()
+        };
+
+        let message = format!(
+            "All {} tokens have been rate limited for {} minutes",
+            self.tokens.len(),
+            exhausted_for.as_secs() / 60
+        );
+        self.notifier.notify(Event::TokensExhausted, &message).await;
+    }
+
+    /// Sends a request built with the token that has the most budget for `resource` left,
+    /// recording the budget reported by the response
+    pub async fn send(
+        &self,
+        resource: &str,
+        request: impl FnOnce(&str) -> RequestBuilder,
+    ) -> Result<Response, Error> {
+        let token = self.pick(resource).await;
+        let resp = request(token).send().await?;
+
+        let headers = resp.headers();
+        // GitHub prefixes the headers with `x-` and says which resource they are of, GitLab doesn't
+        let header = |name: &str| {
+            headers
+                .get(format!("x-ratelimit-{name}"))
+                .or_else(|| headers.get(format!("ratelimit-{name}")))
+                .and_then(|value| value.to_str().ok())
+        };
+        let resource = header("resource").unwrap_or(resource).to_string();
+        let remaining = header("remaining").and_then(|value| value.parse().ok());
+        let reset = header("reset").and_then(|value| value.parse().ok());
+        if let (Some(remaining), Some(reset)) = (remaining, reset) {
+            self.record(token, &resource, remaining, reset);
+        }
+        let retry_after = headers
+            .get(header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok());
+
+        match handle_response(resp).await {
+            Err(Error::RateLimit(status)) => {
+                // Secondary rate limits don't show in the budget
+                METRICS.rate_limited.fetch_add(1, Relaxed);
+                self.limited(token, &resource, retry_after);
+                Err(Error::RateLimit(status))
+            }
+            res => res,
+        }
+    }
+
+    pub async fn send_json<T: DeserializeOwned>(
+        &self,
+        resource: &str,
+        request: impl FnOnce(&str) -> RequestBuilder,
+    ) -> Result<T, Error> {
+        Ok(self.send(resource, request).await?.json().await?)
+    }
+
+    /// retry an api request, waiting for a token with budget left when rate limited
+    /// On reqwest errors does exponential backoff until 5 mins.
+    pub async fn retry<F, Fu, R>(&self, fun: F) -> Result<R, Error>
+    where
+        F: Fn() -> Fu,
+        Fu: Future<Output = Result<R, Error>>,
+    {
+        let mut backoff = Duration::from_secs(1);
+        loop {
+            match fun().await {
+                ok @ Ok(_) => {
+                    *self.exhausted_since.lock().unwrap() = None;
+                    return ok;
+                }
+                Err(Error::Reqwest(reqwest_error)) => {
+                    warn!("Reqwest encountered error {reqwest_error:?}");
+                    warn!("Backing off for {} seconds", backoff.as_secs());
+                    sleep(backoff).await;
+
+                    backoff = backoff + backoff + Duration::from_millis(123); // Exponential backoff + jitter
+
+                    // After 5 minutes bail
+                    if backoff.as_secs() > 300 {
+                        error!("Failed sending request 5 times");
+                        return Err(Error::Reqwest(reqwest_error));
+                    }
+                }
+                Err(err @ Error::HttpError(_)) => return Err(err),
+                // The next attempt picks another token, or waits for one
+                Err(Error::RateLimit(_)) => {}
+                err @ Err(_) => return err,
+            }
+
+            // Yield
+            yield_now().await
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    message: String,
+}
+
+/// Converts forge responses into the correct error codes (helper for the retry function).
+/// 304 Not Modified answers a conditional request, which the sender handles.
+async fn handle_response(resp: Response) -> Result<Response, Error> {
+    let status = resp.status();
+    if status.is_success() || status == StatusCode::NOT_MODIFIED {
+        Ok(resp)
+    } else if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::UNPROCESSABLE_ENTITY
+    {
+        warn!("Rate limit hit");
+        Err(Error::RateLimit(status))
+    } else if let Ok(error) = resp.json().await {
+        let error: ApiError = error;
+        if error.message.contains("abuse") || error.message.contains("rate limit") {
+            warn!("Rate limit hit ({}): {}", status.as_u16(), error.message);
+            Err(Error::RateLimit(status))
+        } else {
+            warn!("Http Error ({}): {}", status.as_u16(), error.message);
+            Err(Error::HttpError(status))
+        }
+    } else {
+        Err(Error::HttpError(status))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_timestamp;
+
+    #[test]
+    fn parses_utc_timestamps() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z"), Some(0));
+        assert_eq!(parse_timestamp("2024-01-01T12:00:00Z"), Some(1_704_110_400));
+        assert_eq!(parse_timestamp("2000-03-01T00:00:01Z"), Some(951_868_801));
+    }
+
+    #[test]
+    fn parses_leap_days() {
+        assert_eq!(parse_timestamp("2024-02-29T00:00:00Z"), Some(1_709_164_800));
+        assert_eq!(parse_timestamp("2000-02-29T23:59:59Z"), Some(951_868_799));
+    }
+
+    #[test]
+    fn ignores_fractional_seconds() {
+        // GitLab returns milliseconds
+        assert_eq!(
+            parse_timestamp("2024-01-01T12:00:00.123Z"),
+            parse_timestamp("2024-01-01T12:00:00Z")
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_timestamps() {
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("2024-01-01"), None);
+        assert_eq!(parse_timestamp("2024-13-01T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("2024-01-32T00:00:00Z"), None);
+        assert_eq!(parse_timestamp("2024-01-01T24:00:00Z"), None);
+        assert_eq!(parse_timestamp("2024-01-01T00:00Z"), None);
+        assert_eq!(parse_timestamp("1969-12-31T23:59:59Z"), None);
+    }
+}
@@ -1,45 +1,62 @@
-use crate::data::Data;
-use crate::{data, Repo};
-use reqwest::{header, Client, Method, RequestBuilder, Response, StatusCode};
+use crate::data::{Data, ForgeKind};
+use crate::metrics::METRICS;
+use crate::notify::Notifier;
+use crate::scraper::cache::{Entry, HttpCache};
+use crate::scraper::forge::{
+    parse_timestamp, Forge, Head, ListedRepo, LoadedRepo, Node, Tokens, Tree, GRAPHQL, REST,
+};
+use crate::scraper::selection::Selection;
+use crate::Repo;
+use async_trait::async_trait;
+use bytes::Bytes;
+use flate2::read::GzDecoder;
+use reqwest::{header, Client, Method, RequestBuilder, StatusCode};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::borrow::Cow;
-use std::future::Future;
-use std::io;
-use std::ops::Add;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::Duration;
-use thiserror::Error;
-use tokio::task::yield_now;
-use tokio::time::sleep;
-use tracing::{debug, error, warn};
+use std::collections::HashSet;
+use std::io::{self, Read};
+use std::sync::atomic::Ordering::Relaxed;
+use tokio::sync::mpsc;
+use tokio::task::spawn_blocking;
+use tracing::{debug, warn};
+
+pub use crate::scraper::forge::Error;
 
 static USER_AGENT: &str = "rust-repos (https://github.com/rust-ops/rust-repos)";
 
 #[derive(Debug)]
 pub struct Github {
     client: Client,
-    tokens: Vec<String>,
-    current_token_index: AtomicUsize,
+    tokens: Tokens,
     data_dir: Data,
+    /// Only with `--http-cache`, as it keeps a file per downloaded file
+    cache: Option<HttpCache>,
 }
 
 #[derive(Deserialize)]
+#[allow(dead_code)]
 pub struct GitHubError {
     message: String,
     #[serde(rename = "type")]
     type_: Option<String>,
 }
 
-#[derive(Clone, Debug, Deserialize)]
-pub struct Node {
-    pub path: String,
+#[derive(Debug, Serialize, Deserialize)]
+struct GithubTree {
+    tree: Vec<GithubTreeEntry>,
+    /// Set when a recursive listing exceeds GitHub's limits (100k entries or 7 MB)
+    #[serde(default)]
+    truncated: bool,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct GithubTree {
-    pub tree: Vec<Node>,
+#[derive(Debug, Serialize, Deserialize)]
+struct GithubTreeEntry {
+    path: String,
+    #[serde(rename = "type")]
+    type_: String,
+    sha: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -50,7 +67,54 @@ pub struct RestRepository {
     pub fork: bool,
 }
 
+#[derive(Debug, Deserialize)]
+struct GraphHead {
+    repository: Option<GraphHeadRepository>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphHeadRepository {
+    name_with_owner: String,
+    /// Missing for empty repos
+    default_branch_ref: Option<GraphBranch>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphBranch {
+    name: String,
+    target: Option<GraphCommit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphCommit {
+    oid: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestPackage {
+    repository: Option<RestPackageRepository>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestPackageRepository {
+    full_name: String,
+}
+
+/// A GitHub release, by the names of its assets
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    #[serde(default)]
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReleaseAsset {
+    pub name: String,
+}
+
 #[derive(Deserialize)]
+#[allow(dead_code)]
 struct GraphResponse<T> {
     data: Option<T>,
     errors: Option<Vec<GitHubError>>,
@@ -58,8 +122,12 @@ struct GraphResponse<T> {
 }
 
 #[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct GraphRateLimit {
     cost: u16,
+    remaining: u64,
+    /// e.g. `2024-01-01T12:00:00Z`
+    reset_at: String,
 }
 
 #[derive(Deserialize)]
@@ -75,15 +143,62 @@ pub struct GraphRepository {
     pub id: String,
     pub name_with_owner: String,
     pub languages: GraphLanguages,
+    pub license_info: Option<GraphLicense>,
+    pub stargazer_count: u64,
+    pub fork_count: u64,
+    pub created_at: String,
+    /// In KB
+    pub disk_usage: Option<u64>,
+    pub pushed_at: Option<String>,
+    pub is_archived: bool,
+    /// Only queried when selecting by topic
+    #[serde(default)]
+    pub repository_topics: Option<GraphTopics>,
 }
 
 impl GraphRepository {
+    pub fn license(&self) -> Option<String> {
+        self.license_info
+            .as_ref()
+            .and_then(|license| license.spdx_id.clone())
+    }
+
     pub fn to_repo(self) -> Repo {
         Repo {
             id: self.id,
             name: self.name_with_owner,
         }
     }
+
+    fn into_loaded(self) -> LoadedRepo {
+        let languages = self
+            .languages
+            .nodes
+            .iter()
+            .flatten()
+            .map(|language| language.name.clone())
+            .collect();
+        let license = self.license();
+        let topics = self
+            .repository_topics
+            .iter()
+            .flat_map(|topics| topics.nodes.iter().flatten())
+            .map(|node| node.topic.name.clone())
+            .collect();
+        let pushed_at = self.pushed_at.as_deref().and_then(parse_timestamp);
+        LoadedRepo {
+            stars: self.stargazer_count,
+            forks: self.fork_count,
+            created_at: Some(self.created_at.clone()),
+            disk_usage: self.disk_usage,
+            archived: self.is_archived,
+            topics,
+            pushed_at,
+            license,
+            languages,
+            repo: self.to_repo(),
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -97,26 +212,29 @@ pub struct GraphLanguage {
 }
 
 #[derive(Debug, Deserialize)]
-pub struct GraphRef {
-    pub name: String,
+pub struct GraphTopics {
+    pub nodes: Vec<Option<GraphTopicNode>>,
 }
 
-#[derive(Debug, Error)]
-pub enum Error {
-    #[error("reqwest error occurred {0:?}")]
-    Reqwest(#[from] reqwest::Error),
-    #[error("rate limit hit {0}")]
-    RateLimit(StatusCode),
-    #[error("other http error: {0}")]
-    HttpError(StatusCode),
+#[derive(Debug, Deserialize)]
+pub struct GraphTopicNode {
+    pub topic: GraphTopic,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GraphTopic {
+    pub name: String,
+}
 
-    #[error("Data error occurred: {0:?}")]
-    DataError(#[from] data::Error),
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphLicense {
+    pub spdx_id: Option<String>,
+}
 
-    #[error("Response did not contain requested data")]
-    EmptyData,
-    #[error("IO Error {0}")]
-    Io(#[from] io::Error),
+#[derive(Debug, Deserialize)]
+pub struct GraphRef {
+    pub name: String,
 }
 
 const GRAPHQL_QUERY_REPOSITORIES: &str = "
@@ -130,31 +248,174 @@ query($ids: [ID!]!) {
                     name
                 }
             }
+            licenseInfo {
+                spdxId
+            }
+            stargazerCount
+            forkCount
+            createdAt
+            diskUsage
+            pushedAt
+            isArchived
+            # topics
         }
     }
 
     rateLimit {
         cost
+        remaining
+        resetAt
     }
 }
 ";
 
+/// The default branch of a repository and the commit it points to. Renamed repositories are found
+/// by their old name as well.
+const GRAPHQL_QUERY_HEAD: &str = "
+query($owner: String!, $name: String!) {
+    repository(owner: $owner, name: $name) {
+        nameWithOwner
+        defaultBranchRef {
+            name
+            target {
+                oid
+            }
+        }
+    }
+}
+";
+
+/// Replaces the `# topics` comment of [GRAPHQL_QUERY_REPOSITORIES]. Topics are another connection
+/// per repository, which doubles the cost of the query, so they are only queried when needed.
+const GRAPHQL_TOPICS: &str = "repositoryTopics(first: 20) { nodes { topic { name } } }";
+
 impl Github {
-    pub fn new(tokens: Vec<String>, data: Data) -> Self {
+    /// Gets an API url, conditionally if its response was cached before. Responses of urls pinned
+    /// to a sha never change, so they aren't cached.
+    async fn get<T: DeserializeOwned + Serialize>(&self, url: &str) -> Result<T, Error> {
+        let cache = self.cache.as_ref().filter(|_| !is_pinned(url));
+        let cached = match cache {
+            Some(cache) => cache.get(url).await.filter(|entry| entry.body.is_some()),
+            None => None,
+        };
+        let fresh = self
+            .tokens
+            .retry(|| async {
+                let resp = self
+                    .tokens
+                    .send(REST, |token| {
+                        let request = self.build_request(Method::GET, url, token);
+                        match &cached {
+                            Some(entry) => entry.conditional(request),
+                            None => request,
+                        }
+                    })
+                    .await?;
+                if resp.status() == StatusCode::NOT_MODIFIED {
+                    return Ok(None);
+                }
+                let entry = Entry::of(url, &resp);
+                let body: T = resp.json().await?;
+                Ok(Some((entry, body)))
+            })
+            .await?;
+
+        match (fresh, cached) {
+            (Some((entry, body)), _) => {
+                if let Some(cache) =
+                    cache.filter(|_| entry.etag.is_some() || entry.last_modified.is_some())
+                {
+                    // Only the fields that are read, not the whole response
+                    let entry = Entry {
+                        body: Some(serde_json::to_value(&body)?),
+                        ..entry
+                    };
+                    if let Err(e) = cache.put(url, &entry).await {
+                        warn!("Failed caching response of {url}: {e}");
+                    }
+                }
+                Ok(body)
+            }
+            (None, Some(entry)) => {
+                debug!("{url} not modified");
+                METRICS.not_modified.fetch_add(1, Relaxed);
+                Ok(serde_json::from_value(entry.body.unwrap_or_default())?)
+            }
+            (None, None) => Err(Error::HttpError(StatusCode::NOT_MODIFIED)),
+        }
+    }
+
+    /// Lists a tree by its sha, or the sha of the commit for the root
+    async fn get_tree(&self, repo: &Repo, sha: &str, recursive: bool) -> Result<GithubTree, Error> {
+        let url = if recursive {
+            format!("repos/{}/git/trees/{sha}?recursive=1", repo.name)
+        } else {
+            format!("repos/{}/git/trees/{sha}", repo.name)
+        };
+        self.get(&url).await
+    }
+
+    /// Downloads the files at `paths` of a repo at a commit from its tarball, instead of one
+    /// request per file. The tarball is extracted while it's streamed, keeping only the wanted
+    /// files in memory. Returns the amount of files written.
+    pub async fn download_tarball(
+        &self,
+        repo: &Repo,
+        sha: &str,
+        paths: &[String],
+    ) -> Result<usize, Error> {
+        let url = format!("repos/{}/tarball/{sha}", repo.name);
+        let mut resp = self
+            .tokens
+            .retry(|| {
+                self.tokens
+                    .send(REST, |token| self.build_request(Method::GET, &url, token))
+            })
+            .await?;
+
+        let wanted: HashSet<String> = paths.iter().cloned().collect();
+        let (tx, rx) = mpsc::channel(16);
+        let extracted = spawn_blocking(move || {
+            extract_tarball(
+                ChunkReader {
+                    chunks: rx,
+                    current: Bytes::new(),
+                },
+                &wanted,
+            )
+        });
+
+        let streamed = async {
+            while let Some(chunk) = resp.chunk().await? {
+                // The extraction stops early once it found all wanted files
+                if tx.send(chunk).await.is_err() {
+                    break;
+                }
+            }
+            Ok::<_, Error>(())
+        }
+        .await;
+        drop(tx);
+        let files = extracted.await.unwrap();
+        streamed?;
+
+        let files = files?;
+        for (path, bytes) in &files {
+            self.data_dir.write_pom(repo, path, bytes).await?;
+        }
+        Ok(files.len())
+    }
+
+    pub fn new(tokens: Vec<String>, data: Data, notifier: Notifier, http_cache: bool) -> Self {
         Github {
             client: Client::new(),
-            tokens,
-            current_token_index: AtomicUsize::new(0),
+            tokens: Tokens::new(tokens, notifier),
+            cache: http_cache.then(|| HttpCache::new(data.http_cache_dir())),
             data_dir: data,
         }
     }
 
-    #[inline]
-    fn get_token(&self) -> &str {
-        &self.tokens[self.current_token_index.load(Ordering::Relaxed)]
-    }
-
-    async fn build_request(&self, method: Method, url: &str) -> RequestBuilder {
+    fn build_request(&self, method: Method, url: &str, token: &str) -> RequestBuilder {
         let url = if !url.starts_with("https://") {
             Cow::from(format!("https://api.github.com/{}", url))
         } else {
@@ -163,7 +424,7 @@ impl Github {
         debug!("Sending request to {url}");
         self.client
             .request(method, url.as_ref())
-            .header(header::AUTHORIZATION, format!("token {}", self.get_token()))
+            .header(header::AUTHORIZATION, format!("token {token}"))
             .header(header::USER_AGENT, USER_AGENT)
         // .header(header::ACCEPT, "application/vnd.github+json")
     }
@@ -173,29 +434,166 @@ impl Github {
         query: &str,
         variables: V,
     ) -> Result<T, Error> {
-        let resp = self
-            .build_request(Method::POST, "graphql")
-            .await
-            .json(&json!({
-                "query": query,
-                "variables": variables,
-            }))
-            .send()
+        let mut used = String::new();
+        let data: Value = self
+            .tokens
+            .send_json(GRAPHQL, |token| {
+                used = token.to_string();
+                self.build_request(Method::POST, "graphql", token)
+                    .json(&json!({
+                        "query": query,
+                        "variables": variables,
+                    }))
+            })
             .await?;
 
-        let data: GraphResponse<T> = handle_response_json(resp).await?;
+        // Queries asking for their rate limit report the budget of the token in the body as well
+        if let Ok(limit) = GraphRateLimit::deserialize(&data["data"]["rateLimit"]) {
+            if let Some(reset) = parse_timestamp(&limit.reset_at) {
+                self.tokens.record(&used, GRAPHQL, limit.remaining, reset);
+            }
+        }
 
+        let data: GraphResponse<T> = serde_json::from_value(data)?;
         data.data.ok_or_else(|| Error::EmptyData)
     }
 
-    pub async fn load_repositories(
+    /// scrapes all github repos (paginated)
+    pub async fn scrape_repositories(&self, since: usize) -> Result<Vec<RestRepository>, Error> {
+        // Maybe needs to be a Vec<Option<RestRepository>>
+        let output: Vec<RestRepository> = self
+            .tokens
+            .retry(|| async {
+                let url = format!("repositories?since={}", since);
+                self.tokens
+                    .send_json(REST, |token| self.build_request(Method::GET, &url, token))
+                    .await
+            })
+            .await?;
+
+        Ok(output)
+    }
+
+    /// The id of the newest public repository, found by bisecting `repositories?since=` upwards
+    /// of `lower`
+    pub async fn max_repository_id(&self, lower: usize) -> Result<usize, Error> {
+        let mut low = lower;
+        let mut high = lower.max(1) * 2;
+        while !self.scrape_repositories(high).await?.is_empty() {
+            low = high;
+            high *= 2;
+        }
+
+        // A page holds 100 repos, so the last page ends at the newest one
+        while high - low > 100 {
+            let mid = low + (high - low) / 2;
+            if self.scrape_repositories(mid).await?.is_empty() {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+
+        let page = self.scrape_repositories(low).await?;
+        Ok(page.last().map_or(low, |repo| repo.id))
+    }
+
+    /// All GitHub releases of a repo
+    pub async fn releases(&self, repo: &Repo) -> Result<Vec<Release>, Error> {
+        let mut releases = Vec::new();
+        for page in 1.. {
+            let url = format!("repos/{}/releases?per_page=100&page={page}", repo.name);
+            let next: Vec<Release> = self
+                .tokens
+                .retry(|| async {
+                    self.tokens
+                        .send_json(REST, |token| self.build_request(Method::GET, &url, token))
+                        .await
+                })
+                .await?;
+
+            let last = next.len() < 100;
+            releases.extend(next);
+            if last {
+                break;
+            }
+        }
+
+        Ok(releases)
+    }
+
+    /// Names of the repos an owner (org or user) published maven packages from to GitHub Packages
+    pub async fn maven_packages(&self, owner: &str) -> Result<Vec<String>, Error> {
+        match self.maven_packages_of(&format!("orgs/{owner}")).await {
+            Err(Error::HttpError(StatusCode::NOT_FOUND)) => {
+                self.maven_packages_of(&format!("users/{owner}")).await
+            }
+            res => res,
+        }
+    }
+
+    async fn maven_packages_of(&self, owner_path: &str) -> Result<Vec<String>, Error> {
+        let mut repos = Vec::new();
+        for page in 1.. {
+            let url = format!("{owner_path}/packages?package_type=maven&per_page=100&page={page}");
+            let packages: Vec<RestPackage> = self
+                .tokens
+                .retry(|| async {
+                    self.tokens
+                        .send_json(REST, |token| self.build_request(Method::GET, &url, token))
+                        .await
+                })
+                .await?;
+
+            let last = packages.len() < 100;
+            repos.extend(
+                packages
+                    .into_iter()
+                    .filter_map(|package| Some(package.repository?.full_name)),
+            );
+            if last {
+                break;
+            }
+        }
+
+        Ok(repos)
+    }
+}
+
+#[async_trait]
+impl Forge for Github {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::Github
+    }
+
+    /// scrapes all github repos (paginated)
+    async fn list_repositories(&self, since: usize) -> Result<Vec<ListedRepo>, Error> {
+        let repos = self.scrape_repositories(since).await?;
+        Ok(repos
+            .into_iter()
+            .map(|repo| ListedRepo {
+                id: repo.id,
+                node_id: repo.node_id,
+                fork: repo.fork,
+            })
+            .collect())
+    }
+
+    async fn load_repositories(
         &self,
         node_ids: &[String],
-    ) -> Result<Vec<GraphRepository>, Error> {
+        selection: &Selection,
+    ) -> Result<Vec<LoadedRepo>, Error> {
+        let query = if selection.needs_topics() {
+            Cow::from(GRAPHQL_QUERY_REPOSITORIES.replace("# topics", GRAPHQL_TOPICS))
+        } else {
+            Cow::from(GRAPHQL_QUERY_REPOSITORIES)
+        };
         let data: GraphRepositories = self
+            .tokens
             .retry(|| async {
                 self.graphql(
-                    GRAPHQL_QUERY_REPOSITORIES,
+                    &query,
                     json!({
                         "ids": node_ids,
                     }),
@@ -204,169 +602,228 @@ impl Github {
             })
             .await?;
 
+        let max_cost = if selection.needs_topics() { 2 } else { 1 };
         assert!(
-            data.rate_limit.cost <= 1,
+            data.rate_limit.cost <= max_cost,
             "load repositories query too costly"
         );
 
-        Ok(data.nodes.into_iter().flatten().collect())
-    }
-
-    /// gets a file tree of a specific github repo
-    pub async fn tree(&self, repo: &Repo) -> Result<GithubTree, Error> {
-        self.retry(|| async {
-            let resp = self
-                .build_request(
-                    Method::GET,
-                    &format!("repos/{}/git/trees/HEAD?recursive=1", repo.name),
-                )
-                .await
-                .send()
-                .await?;
-
-            handle_response_json(resp).await
-        })
-        .await
+        Ok(data
+            .nodes
+            .into_iter()
+            .flatten()
+            .map(GraphRepository::into_loaded)
+            .collect())
     }
 
-    /// scrapes all github repos (paginated)
-    pub async fn scrape_repositories(&self, since: usize) -> Result<Vec<RestRepository>, Error> {
-        // Maybe needs to be a Vec<Option<RestRepository>>
-        let output: Vec<RestRepository> = self
+    async fn head(&self, repo: &Repo) -> Result<Head, Error> {
+        let (owner, name) = repo
+            .name
+            .split_once('/')
+            .ok_or(Error::HttpError(StatusCode::NOT_FOUND))?;
+        let data: GraphHead = self
+            .tokens
             .retry(|| async {
-                let resp = self
-                    .build_request(Method::GET, &format!("repositories?since={}", since))
+                self.graphql(GRAPHQL_QUERY_HEAD, json!({ "owner": owner, "name": name }))
                     .await
-                    .send()
-                    .await?;
-
-                handle_response_json(resp).await
             })
             .await?;
-
-        Ok(output)
+        let repository = data
+            .repository
+            .ok_or(Error::HttpError(StatusCode::NOT_FOUND))?;
+        // Empty repos have no files, like repos that disappeared
+        let (default_branch, sha) = repository
+            .default_branch_ref
+            .and_then(|branch| Some((branch.name, branch.target?.oid)))
+            .ok_or(Error::HttpError(StatusCode::NOT_FOUND))?;
+
+        let full_name = repository.name_with_owner;
+        Ok(Head {
+            sha,
+            default_branch,
+            renamed_to: (full_name != repo.name).then_some(full_name),
+        })
     }
 
-    /// downloads a file from a github repo
-    ///
-    /// path being the path inside the repo
-    pub async fn download_file(&self, repo: &Repo, path: &str) -> Result<(), Error> {
-        let file = self.data_dir.get_pom_path(repo, path);
-        if file.exists() {
-            return Ok(());
+    /// gets a file tree of a specific github repo, listing it per directory if it's too large
+    async fn tree(&self, repo: &Repo, sha: &str) -> Result<Tree, Error> {
+        let tree = self.get_tree(repo, sha, true).await?;
+        if !tree.truncated {
+            return Ok(Tree {
+                nodes: tree
+                    .tree
+                    .into_iter()
+                    .map(|entry| Node { path: entry.path })
+                    .collect(),
+                truncated: false,
+            });
         }
 
+        warn!(
+            "Tree of {} is truncated, listing it per directory",
+            repo.name
+        );
+        let mut nodes = Vec::new();
+        // Subtrees are listed recursively at once, unless they are too large as well
+        let mut dirs = vec![(String::new(), sha.to_string(), false)];
+        while let Some((prefix, sha, recursive)) = dirs.pop() {
+            let tree = self.get_tree(repo, &sha, recursive).await?;
+            if tree.truncated && recursive {
+                dirs.push((prefix, sha, false));
+                continue;
+            }
+            if tree.truncated {
+                warn!("Directory {prefix:?} of {} is truncated", repo.name);
+            }
+
+            for entry in tree.tree {
+                let path = if prefix.is_empty() {
+                    entry.path
+                } else {
+                    format!("{prefix}/{}", entry.path)
+                };
+                if !recursive && entry.type_ == "tree" {
+                    dirs.push((path.clone(), entry.sha, true));
+                }
+                nodes.push(Node { path });
+            }
+        }
+
+        Ok(Tree {
+            nodes,
+            truncated: true,
+        })
+    }
+
+    /// Files downloaded at another commit are requested conditionally, and left untouched if
+    /// they didn't change. The ETags of raw.githubusercontent.com are derived from the contents of
+    /// the file, so they match across commits.
+    async fn download_file(
+        &self,
+        repo: &Repo,
+        sha: &str,
+        path: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
         let url = format!(
-            "https://raw.githubusercontent.com/{}/HEAD/{}",
+            "https://raw.githubusercontent.com/{}/{sha}/{}",
             repo.name, path
         );
+        let key = format!("{}/{path}", repo.name);
+
+        let cached = match &self.cache {
+            _ if !self.data_dir.get_pom_path(repo, path).exists() => None,
+            Some(cache) => match cache.get(&key).await {
+                // Downloaded at this commit
+                Some(entry) if entry.url == url => return Ok(()),
+                // Downloaded before responses were cached
+                None if !overwrite => return Ok(()),
+                entry => entry,
+            },
+            None if overwrite => None,
+            None => return Ok(()),
+        };
 
-        let bytes = self
+        let fresh = self
+            .tokens
             .retry(|| async {
-                let resp = self.build_request(Method::GET, &url).await.send().await?;
-                let pom = handle_response(resp).await?.bytes().await?;
-                Ok(pom)
+                let resp = self
+                    .tokens
+                    .send(REST, |token| {
+                        let request = self.build_request(Method::GET, &url, token);
+                        match &cached {
+                            Some(entry) => entry.conditional(request),
+                            None => request,
+                        }
+                    })
+                    .await?;
+                if resp.status() == StatusCode::NOT_MODIFIED {
+                    return Ok(None);
+                }
+                let entry = Entry::of(&url, &resp);
+                Ok(Some((entry, resp.bytes().await?)))
             })
             .await?;
 
-        self.data_dir.write_pom(repo, path, &bytes).await?;
+        let entry = match (fresh, cached) {
+            (Some((entry, bytes)), _) => {
+                self.data_dir.write_pom(repo, path, &bytes).await?;
+                entry
+            }
+            (None, Some(entry)) => {
+                debug!("{key} not modified since {}", entry.url);
+                METRICS.not_modified.fetch_add(1, Relaxed);
+                Entry { url, ..entry }
+            }
+            (None, None) => return Err(Error::HttpError(StatusCode::NOT_MODIFIED)),
+        };
+        if let Some(cache) = &self.cache {
+            if let Err(e) = cache.put(&key, &entry).await {
+                warn!("Failed caching response of {}: {e}", entry.url);
+            }
+        }
 
         Ok(())
     }
+}
 
-    pub async fn has_github_releases(&self, repo: &Repo) -> Result<bool, Error> {
-        let releases: Vec<Value> = self
-            .retry(|| async {
-                let resp = self
-                    .build_request(Method::GET, &format!("repos/{}/releases", repo.name))
-                    .await
-                    .send()
-                    .await?;
-                let resp = handle_response_json(resp).await?;
-
-                Ok(resp)
-            })
-            .await?;
+/// Whether a url addresses something by its sha, e.g. a tree or a file at a commit
+fn is_pinned(url: &str) -> bool {
+    let path = url.split('?').next().unwrap_or_default();
+    path.split('/')
+        .any(|segment| segment.len() == 40 && segment.bytes().all(|b| b.is_ascii_hexdigit()))
+}
 
-        Ok(!releases.is_empty())
-    }
+/// Reads the chunks of a response body sent by the task downloading it
+struct ChunkReader {
+    chunks: mpsc::Receiver<Bytes>,
+    current: Bytes,
+}
 
-    /// retry a github api request and rotate tokens to circumvent rate limiting
-    /// On reqwest errors does exponential backoff until 5 mins.
-    async fn retry<F, Fu, R>(&self, fun: F) -> Result<R, Error>
-    where
-        F: Fn() -> Fu,
-        Fu: Future<Output = Result<R, Error>>,
-    {
-        let mut backoff = Duration::from_secs(1);
-        loop {
-            match fun().await {
-                ok @ Ok(_) => return ok,
-                Err(Error::Reqwest(reqwest_error)) => {
-                    warn!("Reqwest encountered error {reqwest_error:?}");
-                    warn!("Backing off for {} seconds", backoff.as_secs());
-                    sleep(backoff).await;
-
-                    backoff = backoff + backoff + Duration::from_millis(123); // Exponential backoff + jitter
-
-                    // After 5 minutes bail
-                    if backoff.as_secs() > 300 {
-                        error!("Failed sending request 5 times");
-                        return Err(Error::Reqwest(reqwest_error));
-                    }
-                }
-                Err(err @ Error::HttpError(_)) => return Err(err),
-                Err(Error::RateLimit(_)) => {
-                    let mut wait = false;
-                    self.current_token_index
-                        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |old| {
-                            if old + 1 >= self.tokens.len() {
-                                wait = true;
-                                Some(0)
-                            } else {
-                                Some(old + 1)
-                            }
-                        })
-                        .unwrap();
-
-                    if wait {
-                        warn!("Tokens wrapped around, sleeping for 1 minute");
-                        sleep(Duration::from_secs(60)).await;
-                    }
-                }
-                err @ Err(_) => return err,
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => self.current = chunk,
+                None => return Ok(0),
             }
-
-            // Yield
-            yield_now().await
         }
+        let n = buf.len().min(self.current.len());
+        buf[..n].copy_from_slice(&self.current.split_to(n));
+        Ok(n)
     }
 }
 
-async fn handle_response_json<T: DeserializeOwned>(resp: Response) -> Result<T, Error> {
-    let res = handle_response(resp).await?.json().await?;
-    Ok(res)
-}
-
-/// Converts github responses into the correct error codes (helper for the retry function)
-async fn handle_response(resp: Response) -> Result<Response, Error> {
-    let status = resp.status();
-    if status.is_success() {
-        Ok(resp)
-    } else if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::UNPROCESSABLE_ENTITY
-    {
-        warn!("Rate limit hit");
-        Err(Error::RateLimit(status))
-    } else if let Ok(error) = resp.json().await {
-        let error: GitHubError = error;
-        if error.message.contains("abuse") || error.message.contains("rate limit") {
-            warn!("Rate limit hit ({}): {}", status.as_u16(), error.message);
-            Err(Error::RateLimit(status))
-        } else {
-            warn!("Http Error ({}): {}", status.as_u16(), error.message);
-            Err(Error::HttpError(status))
+/// The files at the `wanted` paths of a gzipped repo tarball. GitHub puts the files of the repo
+/// in a `{owner}-{repo}-{sha}/` directory, which is stripped from their paths.
+fn extract_tarball(
+    reader: impl Read,
+    wanted: &HashSet<String>,
+) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut files = Vec::new();
+    if wanted.is_empty() {
+        return Ok(files);
+    }
+    let mut archive = tar::Archive::new(GzDecoder::new(reader));
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.to_string_lossy().into_owned();
+        let Some((_, path)) = path.split_once('/') else {
+            continue;
+        };
+        if !wanted.contains(path) {
+            continue;
+        }
+
+        let mut bytes = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut bytes)?;
+        files.push((path.to_string(), bytes));
+        if files.len() == wanted.len() {
+            break;
         }
-    } else {
-        Err(Error::HttpError(status))
     }
+    Ok(files)
 }
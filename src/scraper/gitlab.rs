@@ -0,0 +1,290 @@
+//! Client for the GitLab REST API (v4), of gitlab.com or a self-hosted instance.
+
+use crate::data::{Data, ForgeKind};
+use crate::notify::Notifier;
+use crate::scraper::forge::{
+    parse_timestamp, Error, Forge, Head, ListedRepo, LoadedRepo, Node, Tokens, Tree, REST,
+};
+use crate::scraper::selection::Selection;
+use crate::Repo;
+use async_trait::async_trait;
+use reqwest::{header, Client, Method, RequestBuilder, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use tracing::debug;
+use url::form_urlencoded::byte_serialize;
+
+static USER_AGENT: &str = "maven_github_scraper (https://github.com/NULLx76/maven_github_scraper)";
+
+/// Page size of listings, the maximum GitLab allows
+const PER_PAGE: usize = 100;
+
+#[derive(Debug)]
+pub struct Gitlab {
+    client: Client,
+    /// e.g. `https://gitlab.com/api/v4/`
+    api: String,
+    tokens: Tokens,
+    data_dir: Data,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestProject {
+    id: usize,
+    /// Only present for forks
+    #[serde(default)]
+    forked_from_project: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestLicense {
+    key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestProjectDetails {
+    id: usize,
+    path_with_namespace: String,
+    #[serde(default)]
+    license: Option<RestLicense>,
+    #[serde(default)]
+    star_count: u64,
+    #[serde(default)]
+    forks_count: u64,
+    #[serde(default)]
+    created_at: Option<String>,
+    #[serde(default)]
+    topics: Vec<String>,
+    #[serde(default)]
+    last_activity_at: Option<String>,
+    #[serde(default)]
+    archived: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestProjectBranch {
+    path_with_namespace: String,
+    /// Missing for empty projects
+    #[serde(default)]
+    default_branch: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestBranch {
+    commit: RestCommit,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestCommit {
+    id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RestTreeEntry {
+    path: String,
+    #[serde(rename = "type")]
+    type_: String,
+}
+
+/// Percent encodes a project path or file path as one path segment
+fn encode(path: &str) -> String {
+    byte_serialize(path.as_bytes()).collect()
+}
+
+impl Gitlab {
+    pub fn new(url: &str, tokens: Vec<String>, data: Data, notifier: Notifier) -> Self {
+        Gitlab {
+            client: Client::new(),
+            api: format!("{}/api/v4/", url.trim_end_matches('/')),
+            tokens: Tokens::new(tokens, notifier),
+            data_dir: data,
+        }
+    }
+
+    fn build_request(&self, method: Method, path: &str, token: &str) -> RequestBuilder {
+        let url = format!("{}{path}", self.api);
+        debug!("Sending request to {url}");
+        self.client
+            .request(method, url)
+            .header("PRIVATE-TOKEN", token)
+            .header(header::USER_AGENT, USER_AGENT)
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, Error> {
+        self.tokens
+            .retry(|| async {
+                self.tokens
+                    .send_json(REST, |token| self.build_request(Method::GET, path, token))
+                    .await
+            })
+            .await
+    }
+
+    /// Loads one project, `None` if it was deleted or made private since it was listed
+    async fn load_project(
+        &self,
+        id: &str,
+        selection: &Selection,
+    ) -> Result<Option<LoadedRepo>, Error> {
+        let languages: HashMap<String, f64> =
+            match self.get(&format!("projects/{id}/languages")).await {
+                Ok(languages) => languages,
+                Err(Error::HttpError(StatusCode::NOT_FOUND)) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+        let languages: Vec<String> = languages.into_keys().collect();
+
+        // The license costs another request, which is only worth it for the repos that are kept
+        let project: RestProjectDetails = if selection.matches_languages(&languages) {
+            self.get(&format!("projects/{id}?license=true")).await?
+        } else {
+            self.get(&format!("projects/{id}")).await?
+        };
+
+        Ok(Some(LoadedRepo {
+            repo: Repo {
+                id: project.id.to_string(),
+                name: project.path_with_namespace,
+            },
+            languages,
+            license: project.license.map(|license| license.key),
+            stars: project.star_count,
+            forks: project.forks_count,
+            created_at: project.created_at,
+            disk_usage: None,
+            topics: project.topics,
+            pushed_at: project
+                .last_activity_at
+                .as_deref()
+                .and_then(parse_timestamp),
+            archived: project.archived,
+        }))
+    }
+}
+
+#[async_trait]
+impl Forge for Gitlab {
+    fn kind(&self) -> ForgeKind {
+        ForgeKind::Gitlab
+    }
+
+    async fn list_repositories(&self, since: usize) -> Result<Vec<ListedRepo>, Error> {
+        let projects: Vec<RestProject> = self
+            .get(&format!(
+                "projects?visibility=public&order_by=id&sort=asc&id_after={since}&per_page={PER_PAGE}"
+            ))
+            .await?;
+
+        Ok(projects
+            .into_iter()
+            .map(|project| ListedRepo {
+                id: project.id,
+                node_id: project.id.to_string(),
+                fork: project.forked_from_project.is_some(),
+            })
+            .collect())
+    }
+
+    async fn load_repositories(
+        &self,
+        node_ids: &[String],
+        selection: &Selection,
+    ) -> Result<Vec<LoadedRepo>, Error> {
+        let mut repos = Vec::with_capacity(node_ids.len());
+        for id in node_ids {
+            repos.extend(self.load_project(id, selection).await?);
+        }
+        Ok(repos)
+    }
+
+    async fn head(&self, repo: &Repo) -> Result<Head, Error> {
+        let project: RestProjectBranch = self
+            .get(&format!("projects/{}", encode(&repo.name)))
+            .await?;
+        // Empty projects have no files, like projects that disappeared
+        let default_branch = project
+            .default_branch
+            .ok_or(Error::HttpError(StatusCode::NOT_FOUND))?;
+        let branch: RestBranch = self
+            .get(&format!(
+                "projects/{}/repository/branches/{}",
+                encode(&repo.name),
+                encode(&default_branch)
+            ))
+            .await?;
+
+        // Projects are found by their old path after a rename, until another project takes it
+        let path = project.path_with_namespace;
+        Ok(Head {
+            sha: branch.commit.id,
+            default_branch,
+            renamed_to: (path != repo.name).then_some(path),
+        })
+    }
+
+    /// The project is addressed by its path, as not every caller knows the id
+    async fn tree(&self, repo: &Repo, sha: &str) -> Result<Tree, Error> {
+        let mut nodes = Vec::new();
+        for page in 1.. {
+            let entries: Vec<RestTreeEntry> = self
+                .get(&format!(
+                    "projects/{}/repository/tree?ref={sha}&recursive=true&per_page={PER_PAGE}&page={page}",
+                    encode(&repo.name)
+                ))
+                .await?;
+
+            let last = entries.len() < PER_PAGE;
+            nodes.extend(
+                entries
+                    .into_iter()
+                    .filter(|entry| entry.type_ == "blob")
+                    .map(|entry| Node { path: entry.path }),
+            );
+            if last {
+                break;
+            }
+        }
+
+        // Listings are paginated instead of truncated
+        Ok(Tree {
+            nodes,
+            truncated: false,
+        })
+    }
+
+    async fn download_file(
+        &self,
+        repo: &Repo,
+        sha: &str,
+        path: &str,
+        overwrite: bool,
+    ) -> Result<(), Error> {
+        let file = self.data_dir.get_pom_path(repo, path);
+        if file.exists() && !overwrite {
+            return Ok(());
+        }
+
+        let url = format!(
+            "projects/{}/repository/files/{}/raw?ref={sha}",
+            encode(&repo.name),
+            encode(path)
+        );
+
+        let bytes = self
+            .tokens
+            .retry(|| async {
+                let resp = self
+                    .tokens
+                    .send(REST, |token| self.build_request(Method::GET, &url, token))
+                    .await?;
+                Ok(resp.bytes().await?)
+            })
+            .await?;
+
+        self.data_dir.write_pom(repo, path, &bytes).await?;
+
+        Ok(())
+    }
+}
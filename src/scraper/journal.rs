@@ -0,0 +1,147 @@
+//! Journal of fetch-and-download, so a crashed run resumes exactly where it stopped.
+//!
+//! `last_id` advances as soon as a page of repositories is listed, before its batches are loaded.
+//! The node ids of a page are journaled as pending before that, and removed by compacting the
+//! journal once their batch is stored, so batches that were in flight (or failed) are loaded
+//! again on the next run instead of being skipped, while the journal only ever holds the few pages
+//! in flight. Within a repo, the downloaded files themselves are the progress: they are written
+//! atomically and existing ones aren't downloaded again, so an interrupted repo only downloads the
+//! missing files.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+use tokio::task::spawn_blocking;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO Error: {0:?}")]
+    IO(#[from] io::Error),
+
+    #[error("Invalid journal entry: {0}")]
+    Serde(#[from] serde_json::Error),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum Entry {
+    /// Listed repositories (node ids) that still have to be loaded
+    Pending { nodes: Vec<String> },
+    /// Loaded repositories, only in journals that weren't compacted as batches completed
+    Done { nodes: Vec<String> },
+}
+
+#[derive(Debug)]
+struct Inner {
+    path: PathBuf,
+    file: File,
+    /// Nodes that were listed and aren't loaded yet, in the order they were listed
+    pending: Vec<String>,
+}
+
+impl Inner {
+    /// Rewrites the journal to only the pending nodes
+    fn compact(&mut self) -> Result<(), Error> {
+        let tmp = PathBuf::from(format!("{}.new", self.path.display()));
+        let mut compacted = BufWriter::new(File::create(&tmp)?);
+        if !self.pending.is_empty() {
+            let entry = Entry::Pending {
+                nodes: self.pending.clone(),
+            };
+            serde_json::to_writer(&mut compacted, &entry)?;
+            compacted.write_all(b"\n")?;
+        }
+        compacted.flush()?;
+        drop(compacted);
+        fs::rename(&tmp, &self.path)?;
+
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Journal {
+    inner: Arc<Mutex<Inner>>,
+    /// Nodes pending when the journal was opened, in the order they were listed
+    outstanding: Vec<String>,
+}
+
+impl Journal {
+    /// Reads the journal left by an earlier run and compacts it to what is still outstanding
+    ///
+    /// Warning: this method blocks
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let mut outstanding = Vec::new();
+        let mut done = HashSet::new();
+
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                let line = line?;
+                // The last line of a crashed run can be cut off
+                let Ok(entry) = serde_json::from_str(&line) else {
+                    continue;
+                };
+                match entry {
+                    Entry::Pending { nodes } => outstanding.extend(nodes),
+                    Entry::Done { nodes } => done.extend(nodes),
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        outstanding.retain(|node| !done.contains(node) && seen.insert(node.clone()));
+
+        let mut inner = Inner {
+            path: path.to_path_buf(),
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+            pending: outstanding.clone(),
+        };
+        inner.compact()?;
+        Ok(Self {
+            inner: Arc::new(Mutex::new(inner)),
+            outstanding,
+        })
+    }
+
+    /// Repositories listed by an earlier run that weren't loaded
+    pub fn outstanding(&self) -> &[String] {
+        &self.outstanding
+    }
+
+    /// Journals listed repositories as pending
+    pub async fn pending(&self, nodes: Vec<String>) -> Result<(), Error> {
+        if nodes.is_empty() {
+            return Ok(());
+        }
+        let inner = self.inner.clone();
+        spawn_blocking(move || -> Result<(), Error> {
+            let mut line = serde_json::to_vec(&Entry::Pending {
+                nodes: nodes.clone(),
+            })?;
+            line.push(b'\n');
+            let mut inner = inner.lock().unwrap();
+            inner.file.write_all(&line)?;
+            inner.pending.extend(nodes);
+            Ok(())
+        })
+        .await
+        .unwrap()
+    }
+
+    /// Removes loaded repositories from the journal
+    pub async fn done(&self, nodes: Vec<String>) -> Result<(), Error> {
+        let inner = self.inner.clone();
+        spawn_blocking(move || {
+            let nodes: HashSet<String> = nodes.into_iter().collect();
+            let mut inner = inner.lock().unwrap();
+            inner.pending.retain(|node| !nodes.contains(node));
+            inner.compact()
+        })
+        .await
+        .unwrap()
+    }
+}
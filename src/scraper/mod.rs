@@ -1,37 +1,206 @@
-use crate::data::Data;
+use crate::analyzer::{distribution, gradle};
+use crate::data::{
+    Data, ErrorRecord, ForgeKind, Manifest, PackagesStatus, ProgressPoint, ReleaseStatus,
+    RepoMetadata,
+};
+use crate::metrics::METRICS;
+use crate::notify::{ErrorRate, Event, Notifier};
+use crate::scraper::forge::{Forge, Head, Tree};
 use crate::scraper::github::Github;
-use crate::{data, Repo};
+use crate::scraper::gitlab::Gitlab;
+use crate::scraper::journal::Journal;
+use crate::scraper::patterns::Pattern;
+use crate::scraper::selection::Selection;
+use crate::{data, project_dir, Repo};
 use itertools::Itertools;
-use std::sync::atomic::AtomicBool;
-use std::sync::atomic::Ordering::SeqCst;
-use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::Ordering::{Relaxed, SeqCst};
+use std::sync::atomic::{AtomicBool, AtomicUsize};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 use tokio::signal::ctrl_c;
 use tokio::task::JoinSet;
 use tokio::time::sleep;
 use tracing::{debug, error, info, warn};
 
+pub mod cache;
+pub mod forge;
 pub mod github;
+pub mod gitlab;
+pub mod journal;
+pub mod patterns;
+pub mod progress;
+pub mod selection;
+pub mod watch;
+
+/// Amount of repositories loaded per GraphQL request
+const BATCH_SIZE: usize = 100;
+
+/// Repos per page of [Forge::list_repositories], on GitHub and GitLab
+const PAGE_SIZE: usize = 100;
+
+/// How often fetch-and-download records its progress in the history
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Build and automation configuration files downloaded together with the poms, by their path in the repo
+pub const BUILD_FILES: &[&str] = &[
+    "toolchains.xml",
+    ".mvn/extensions.xml",
+    ".github/dependabot.yml",
+    ".github/dependabot.yaml",
+    "renovate.json",
+    "renovate.json5",
+    ".github/renovate.json",
+    ".github/renovate.json5",
+    ".gitlab/renovate.json",
+    ".gitlab/renovate.json5",
+    ".renovaterc",
+    ".renovaterc.json",
+];
+
+fn is_build_file(path: &str) -> bool {
+    BUILD_FILES
+        .iter()
+        .any(|file| path == *file || path.ends_with(&format!("/{file}")))
+}
+
+/// Build systems whose build files are downloaded by fetch-and-download and download-poms
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BuildSystem {
+    /// pom.xml files
+    Maven,
+    /// build.gradle(.kts) and settings.gradle(.kts) files
+    Gradle,
+}
+
+impl BuildSystem {
+    fn matches(self, path: &str) -> bool {
+        match self {
+            BuildSystem::Maven => path.ends_with("pom.xml"),
+            BuildSystem::Gradle => gradle::is_gradle_file(Path::new(path)),
+        }
+    }
+}
+
+/// How the files of a repo are downloaded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Strategy {
+    /// From the tarball for repos with more files than the tarball threshold, otherwise per file
+    #[default]
+    Auto,
+    /// One request per file
+    Files,
+    /// From the tarball of the repo, only on GitHub
+    Tarball,
+}
 
 #[derive(Debug, Clone)]
 pub struct Scraper {
-    gh: Arc<Github>,
+    forge: Arc<dyn Forge>,
+    /// Set when scraping GitHub, for what only GitHub has
+    gh: Option<Arc<Github>>,
     data: Data,
     finished: Arc<AtomicBool>,
+    /// Repos and repos with poms stored in the csv, kept up to date by fetch-and-download
+    stored: Arc<AtomicUsize>,
+    stored_poms: Arc<AtomicUsize>,
+    notifier: Notifier,
+    error_rate: Arc<Mutex<ErrorRate>>,
+    /// Opened by fetch-and-download
+    journal: Arc<OnceLock<Journal>>,
+    build_systems: Vec<BuildSystem>,
+    /// Other files downloaded together with the build files
+    patterns: Arc<Vec<Pattern>>,
+    strategy: Strategy,
+    /// Files above which [Strategy::Auto] downloads the tarball
+    tarball_threshold: usize,
+    /// Which loaded repos are stored
+    selection: Arc<Selection>,
 }
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("Github API Error")]
-    Github(#[from] github::Error),
+    #[error("Forge API Error: {0}")]
+    Forge(#[from] forge::Error),
+    #[error("Not supported for {0:?}")]
+    Unsupported(ForgeKind),
     #[error("Data store error")]
     Data(#[from] data::Error),
+    #[error("Journal error")]
+    Journal(#[from] journal::Error),
+}
+
+impl Error {
+    /// Label of the error in the metrics
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Forge(e) => e.kind(),
+            Error::Unsupported(_) => "unsupported",
+            Error::Data(_) => "data",
+            Error::Journal(_) => "journal",
+        }
+    }
+
+    /// Whether the failed operation may succeed when tried again, see [forge::Error::retryable]
+    pub fn retryable(&self) -> bool {
+        match self {
+            Error::Forge(e) => e.retryable(),
+            _ => false,
+        }
+    }
+}
+
+/// What `retry-failed` did
+#[derive(Debug, Default)]
+pub struct RetrySummary {
+    /// Repos and stages whose last attempt failed with a retryable error
+    pub retried: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// Failures of repos that are no longer stored or of stages that can't be retried
+    pub skipped: usize,
+}
+
+impl RetrySummary {
+    pub fn print(&self) {
+        println!(
+            "Retried {} failures: {} succeeded, {} failed again, {} skipped",
+            self.retried, self.succeeded, self.failed, self.skipped
+        );
+    }
 }
 
 impl Scraper {
-    pub fn new(gh_tokens: Vec<String>, data: Data) -> Self {
-        let gh = Github::new(gh_tokens, data.clone());
+    /// Scrapes the forge of the data dir, with the GitHub tokens or the ones in the options
+    pub fn new(
+        options: &forge::Options,
+        gh_tokens: Vec<String>,
+        data: Data,
+        notifier: Notifier,
+    ) -> Self {
+        let (forge, gh): (Arc<dyn Forge>, _) = match data.forge() {
+            ForgeKind::Github => {
+                let gh = Arc::new(Github::new(
+                    gh_tokens,
+                    data.clone(),
+                    notifier.clone(),
+                    options.http_cache,
+                ));
+                (gh.clone(), Some(gh))
+            }
+            ForgeKind::Gitlab => {
+                let gitlab = Gitlab::new(
+                    &options.gitlab_url,
+                    options.gitlab_tokens.clone(),
+                    data.clone(),
+                    notifier.clone(),
+                );
+                (Arc::new(gitlab), None)
+            }
+        };
+        let error_rate = ErrorRate::new(notifier.options().error_rate);
         let finished = Arc::new(AtomicBool::new(false));
         let f2 = finished.clone();
 
@@ -42,15 +211,246 @@ impl Scraper {
         });
 
         Self {
-            gh: Arc::new(gh),
+            forge,
+            gh,
             data,
             finished,
+            stored: Default::default(),
+            stored_poms: Default::default(),
+            notifier,
+            error_rate: Arc::new(Mutex::new(error_rate)),
+            journal: Default::default(),
+            build_systems: vec![BuildSystem::Maven],
+            patterns: Default::default(),
+            strategy: Strategy::Files,
+            tarball_threshold: usize::MAX,
+            selection: Default::default(),
         }
     }
 
+    /// Only stores the listed repos matching the selection
+    pub fn with_selection(mut self, selection: Selection) -> Self {
+        self.selection = Arc::new(selection);
+        self
+    }
+
+    /// Downloads the build files of these build systems instead of only poms
+    pub fn with_build_systems(mut self, build_systems: Vec<BuildSystem>) -> Self {
+        self.build_systems = build_systems;
+        self
+    }
+
+    /// Also downloads the files matching these patterns, counting them as build files
+    pub fn with_patterns(mut self, patterns: Vec<Pattern>) -> Self {
+        self.patterns = Arc::new(patterns);
+        self
+    }
+
+    /// Downloads repos with more than `tarball_threshold` files from their tarball with
+    /// [Strategy::Auto]. GitLab repos are always downloaded per file.
+    pub fn with_strategy(mut self, strategy: Strategy, tarball_threshold: usize) -> Self {
+        self.strategy = strategy;
+        self.tarball_threshold = tarball_threshold;
+        self
+    }
+
+    /// Whether a file is a build file of the build systems or matches one of the patterns
+    fn is_selected(&self, path: &str) -> bool {
+        self.build_systems.iter().any(|system| system.matches(path))
+            || self.patterns.iter().any(|pattern| pattern.matches(path))
+    }
+
+    /// Tracks the outcome of scraping a repo (or batch of them) for the error rate notification
+    async fn record_outcome(&self, error: Option<&'static str>) {
+        if let Some(kind) = error {
+            METRICS.error(kind);
+        }
+        let rate = self.error_rate.lock().unwrap().record(error.is_some());
+        if let Some(rate) = rate {
+            let message = format!("{:.0}% of the recent repos failed", rate * 100.0);
+            self.notifier.notify(Event::ErrorRate, &message).await;
+        }
+    }
+
+    /// Appends a failed stage of a repo to the error log, e.g. `download` or `releases`
+    async fn record_error(&self, repo: &Repo, stage: &str, error: &Error) {
+        let status = match error {
+            Error::Forge(e) => e.status().map(|status| status.as_u16()),
+            _ => None,
+        };
+        let record = ErrorRecord {
+            repo_id: Some(repo.id.clone()),
+            kind: Some(error.kind().to_string()),
+            status,
+            retryable: error.retryable(),
+            ..ErrorRecord::new(repo.path(), stage, error.to_string())
+        };
+        let data = self.data.clone();
+        let recorded = tokio::task::spawn_blocking(move || data.record_error(&record))
+            .await
+            .unwrap();
+        if let Err(e) = recorded {
+            error!("Failed recording error for {}: {e}", repo.name);
+        }
+    }
+
+    fn github(&self) -> Result<&Github, Error> {
+        self.gh
+            .as_deref()
+            .ok_or_else(|| Error::Unsupported(self.forge.kind()))
+    }
+
+    /// The id of the newest public GitHub repository
+    pub async fn max_repository_id(&self) -> Result<usize, Error> {
+        Ok(self
+            .github()?
+            .max_repository_id(self.data.get_last_id()?)
+            .await?)
+    }
+
+    async fn record_progress(&self, last_id: usize) -> Result<(), Error> {
+        let point = ProgressPoint {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            last_id,
+            repos: self.stored.load(SeqCst),
+            poms: self.stored_poms.load(SeqCst),
+        };
+        self.data.record_progress(point).await?;
+        Ok(())
+    }
+
     async fn has_github_releases(&self, repo: &Repo) -> Result<bool, Error> {
-        let res = self.gh.has_github_releases(repo).await?;
-        todo!("write to file somewhere")
+        let releases = self.github()?.releases(repo).await?;
+        let status = ReleaseStatus {
+            project: repo.path(),
+            has_releases: !releases.is_empty(),
+            releases: releases.len(),
+            artifacts: releases
+                .into_iter()
+                .flat_map(|release| release.assets)
+                .map(|asset| asset.name)
+                .filter(|name| name.ends_with(".jar") || name.ends_with(".pom"))
+                .collect(),
+        };
+        self.data.record_releases(&status).await?;
+        Ok(status.has_releases)
+    }
+
+    /// Checks whether repositories with poms publish GitHub releases, skipping ones checked before.
+    /// With `distribution_only` only the repos with a distributionManagement in the last analysis
+    /// are checked.
+    pub async fn fetch_all_releases(&self, distribution_only: bool) -> Result<usize, Error> {
+        let data = self.data.clone();
+        let (repos, done, distributing) = tokio::task::spawn_blocking(move || {
+            let distributing = if distribution_only {
+                let report = data.read_report()?;
+                Some(report.has_distro_repos.into_iter().collect::<HashSet<_>>())
+            } else {
+                None
+            };
+            Ok::<_, data::Error>((data.read_repos()?, data.read_releases()?, distributing))
+        })
+        .await
+        .unwrap()?;
+
+        let repos: Vec<Repo> = repos
+            .into_iter()
+            .filter(|repo| repo.has_pom)
+            .map(Repo::from)
+            .filter(|repo| !done.contains_key(&repo.path()))
+            .filter(|repo| {
+                distributing
+                    .as_ref()
+                    .is_none_or(|projects| projects.contains(&repo.path()))
+            })
+            .collect();
+        info!(
+            "Checking releases of {} repos ({} checked before)",
+            repos.len(),
+            done.len()
+        );
+
+        let mut cnt = 0;
+        for repos in repos.chunks(8) {
+            if self.finished.load(SeqCst) {
+                break;
+            }
+
+            let mut js = JoinSet::new();
+            for repo in repos {
+                let repo = repo.clone();
+                let me = self.clone();
+                js.spawn(async move {
+                    let res = me.has_github_releases(&repo).await;
+                    if let Err(e) = &res {
+                        me.record_error(&repo, "releases", e).await;
+                    }
+                    res
+                });
+            }
+
+            while let Some(next) = js.join_next().await {
+                let next = next.unwrap();
+                self.record_outcome(next.as_ref().err().map(|e| e.kind()))
+                    .await;
+                match next {
+                    Ok(true) => cnt += 1,
+                    Err(e) => error!("Error: {e:?}"),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(cnt)
+    }
+
+    /// Fetches the GitHub Packages maven packages of the owners of projects that configure
+    /// GitHub Packages in the last analysis, skipping owners fetched before
+    pub async fn fetch_all_packages(&self) -> Result<usize, Error> {
+        let gh = self.github()?;
+        let data = self.data.clone();
+        let (projects, done) = tokio::task::spawn_blocking(move || {
+            Ok::<_, data::Error>((data.read_projects()?, data.read_packages()?))
+        })
+        .await
+        .unwrap()?;
+
+        let owners: BTreeSet<String> = projects
+            .iter()
+            .filter(|project| {
+                distribution::configured_in_pom(project) || project.github_packages_workflow
+            })
+            .map(|project| project.owner().to_string())
+            .filter(|owner| !done.contains_key(owner))
+            .collect();
+        info!("Fetching packages of {} owners", owners.len());
+
+        let mut cnt = 0;
+        for owner in owners {
+            if self.finished.load(SeqCst) {
+                break;
+            }
+
+            let packages = gh.maven_packages(&owner).await;
+            self.record_outcome(packages.as_ref().err().map(|e| e.kind()))
+                .await;
+            match packages {
+                Ok(repos) => {
+                    let status = PackagesStatus {
+                        repos: repos.iter().map(|repo| project_dir(repo)).collect(),
+                        owner,
+                    };
+                    self.data.record_packages(&status).await?;
+                    cnt += usize::from(!status.repos.is_empty());
+                }
+                Err(e) => error!("Error fetching packages of {owner}: {e:?}"),
+            }
+        }
+
+        Ok(cnt)
     }
 
     pub async fn download_all_workflows(&self) -> Result<usize, Error> {
@@ -74,7 +474,10 @@ impl Scraper {
             }
 
             while let Some(next) = js.join_next().await {
-                match next.unwrap() {
+                let next = next.unwrap();
+                self.record_outcome(next.as_ref().err().map(|e| e.kind()))
+                    .await;
+                match next {
                     Ok(true) => cnt += 1,
                     Err(e) => error!("Error: {e:?}"),
                     _ => {}
@@ -85,176 +488,656 @@ impl Scraper {
         Ok(cnt)
     }
 
+    /// Resolves the current commit of a repo and lists its files at it, under its new name if
+    /// the repo was renamed
+    async fn head_and_tree(&self, repo: &Repo) -> Result<(Repo, Head, Tree), Error> {
+        let head = self.forge.head(repo).await?;
+        let repo = self.follow_rename(repo, &head).await?;
+        let tree = self.forge.tree(&repo, &head.sha).await?;
+        Ok((repo, head, tree))
+    }
+
+    /// Stores a repo renamed on the forge under its new name, see [data::Data::rename_repo]
+    async fn follow_rename(&self, repo: &Repo, head: &Head) -> Result<Repo, Error> {
+        let Some(name) = &head.renamed_to else {
+            return Ok(repo.clone());
+        };
+        info!("{} was renamed to {name}", repo.name);
+
+        let (data, from, to) = (self.data.clone(), repo.clone(), name.clone());
+        tokio::task::spawn_blocking(move || data.rename_repo(&from, &to))
+            .await
+            .unwrap()?;
+        Ok(Repo {
+            id: repo.id.clone(),
+            name: name.clone(),
+        })
+    }
+
     async fn fetch_workflow_files(&self, repo: &Repo) -> Result<bool, Error> {
-        let tree = self.gh.tree(repo).await?;
+        let (repo, head, tree) = self.head_and_tree(repo).await?;
         let mut js = JoinSet::new();
 
         let mut has_file = false;
 
-        for f in tree.tree.into_iter().filter(|node| {
+        for f in tree.nodes.into_iter().filter(|node| {
             node.path.starts_with(".github/workflows")
                 && (node.path.ends_with(".yml") || node.path.ends_with(".yaml"))
         }) {
             has_file = true;
-            let gh = self.gh.clone();
+            let forge = self.forge.clone();
             let repo = repo.clone();
+            let sha = head.sha.clone();
 
             info!("Downloading {:?}, {}", &repo, &f.path);
-            js.spawn(async move { gh.download_file(&repo, &f.path).await });
+            js.spawn(async move { forge.download_file(&repo, &sha, &f.path, false).await });
         }
 
         while let Some(res) = js.join_next().await {
             res.unwrap()?;
         }
 
-        self.data.mark_fetched(repo).await?;
+        self.data.mark_fetched(&repo).await?;
         info!("Fetched files for {}", &repo.name);
 
         Ok(has_file)
     }
 
-    async fn fetch_all_files_for(&self, repo: &Repo, file: String) -> Result<bool, Error> {
-        debug!("Fetching files for {}", repo.name);
-        let tree = match self.gh.tree(repo).await {
-            Ok(el) => el,
-            Err(github::Error::HttpError(code)) => {
-                self.data.mark_fetched(repo).await?;
-                warn!(
-                    "HTTP Error occurred {code} while getting tree for {}",
-                    repo.name
-                );
-                return Ok(false);
+    /// Downloads the [BUILD_FILES] of repositories fetched before they were downloaded as well
+    pub async fn download_all_build_files(&self) -> Result<usize, Error> {
+        let data = self.data.clone();
+        let repos = tokio::task::spawn_blocking(move || data.read_repos())
+            .await
+            .unwrap()?;
+
+        let mut cnt = 0;
+        for repos in repos.chunks(8) {
+            if self.finished.load(SeqCst) {
+                break;
             }
-            e @ Err(_) => e?,
-        };
-        let mut js = JoinSet::new();
 
-        let mut has_file = false;
+            let mut js = JoinSet::new();
+            for repo in repos.iter().filter(|repo| repo.has_pom) {
+                let repo: Repo = repo.clone().into();
+                let me = self.clone();
+                js.spawn(async move { me.fetch_build_files(&repo).await });
+            }
 
-        for f in tree
-            .tree
-            .into_iter()
-            .filter(|node| node.path.ends_with(&file))
-        {
+            while let Some(next) = js.join_next().await {
+                let next = next.unwrap();
+                self.record_outcome(next.as_ref().err().map(|e| e.kind()))
+                    .await;
+                match next {
+                    Ok(true) => cnt += 1,
+                    Err(e) => error!("Error: {e:?}"),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(cnt)
+    }
+
+    async fn fetch_build_files(&self, repo: &Repo) -> Result<bool, Error> {
+        let (repo, head, tree) = self.head_and_tree(repo).await?;
+
+        let mut has_file = false;
+        for f in tree.nodes.iter().filter(|node| is_build_file(&node.path)) {
             has_file = true;
-            let gh = self.gh.clone();
+            info!("Downloading {:?}, {}", &repo, &f.path);
+            self.forge
+                .download_file(&repo, &head.sha, &f.path, false)
+                .await?;
+        }
+
+        Ok(has_file)
+    }
+
+    /// Downloads the build files of a repo, returning whether it has any. The files to download
+    /// are listed in a manifest first, so an interrupted download resumes without listing again.
+    /// On a `retry`, failing to list the files is an error instead of a repo without files.
+    async fn fetch_all_files_for(&self, repo: &Repo, retry: bool) -> Result<bool, Error> {
+        let (repo, manifest) = match self.data.read_manifest(repo).await? {
+            Some(manifest) => {
+                debug!("Resuming files for {}", repo.name);
+                (repo.clone(), manifest)
+            }
+            None => {
+                debug!("Fetching files for {}", repo.name);
+                let Some((repo, manifest)) = self.list_files(repo, retry).await? else {
+                    return Ok(false);
+                };
+                if !manifest.paths.is_empty() {
+                    self.data.write_manifest(&repo, &manifest).await?;
+                }
+                (repo, manifest)
+            }
+        };
+        let has_file = manifest.paths.iter().any(|path| self.is_selected(path));
+        let downloaded = self.download_manifest(&repo, &manifest, false).await?;
+
+        // Only repos with files have a project dir to store it in
+        if downloaded {
+            self.data.write_metadata(&repo, &manifest.metadata).await?;
+        }
+
+        self.data.mark_fetched(&repo).await?;
+        self.data.remove_manifest(&repo).await?;
+        info!("Fetched files for {}", &repo.name);
+
+        Ok(has_file)
+    }
+
+    /// Downloads the files of a manifest with the [Strategy] of the scraper, returning whether any
+    /// was. Files downloaded before are only downloaded again to `overwrite` them.
+    async fn download_manifest(
+        &self,
+        repo: &Repo,
+        manifest: &Manifest,
+        overwrite: bool,
+    ) -> Result<bool, Error> {
+        let sha = manifest.metadata.sha.clone().unwrap_or_default();
+        // Tarballs always overwrite the files they contain
+        match (&self.gh, self.strategy) {
+            (Some(gh), Strategy::Tarball) => self.download_tarball(gh, repo, &sha, manifest).await,
+            (Some(gh), Strategy::Auto) if manifest.paths.len() > self.tarball_threshold => {
+                self.download_tarball(gh, repo, &sha, manifest).await
+            }
+            _ => self.download_each(repo, &sha, manifest, overwrite).await,
+        }
+    }
+
+    /// Downloads the files of a manifest with a request per file, returning whether any was
+    async fn download_each(
+        &self,
+        repo: &Repo,
+        sha: &str,
+        manifest: &Manifest,
+        overwrite: bool,
+    ) -> Result<bool, Error> {
+        let mut js = JoinSet::new();
+        for path in &manifest.paths {
+            let forge = self.forge.clone();
+            let path = path.clone();
             let repo = repo.clone();
+            let sha = sha.to_string();
 
-            js.spawn(async move { gh.download_file(&repo, &f.path).await });
+            js.spawn(async move { forge.download_file(&repo, &sha, &path, overwrite).await });
         }
 
+        let mut downloaded = false;
         while let Some(res) = js.join_next().await {
-            if let Err(e) = res.unwrap() {
-                match e {
-                    github::Error::HttpError(code) => {
-                        warn!(
-                            "HTTP {} occurred while fetching files for {}",
-                            code.as_u16(),
-                            repo.name
-                        )
-                    }
-                    e => return Err(e.into()),
+            match res.unwrap() {
+                Ok(()) => downloaded = true,
+                Err(forge::Error::HttpError(code)) => {
+                    warn!(
+                        "HTTP {} occurred while fetching files for {}",
+                        code.as_u16(),
+                        repo.name
+                    )
                 }
+                Err(e) => return Err(e.into()),
             }
         }
 
-        self.data.mark_fetched(repo).await?;
-        info!("Fetched files for {}", &repo.name);
+        Ok(downloaded)
+    }
 
-        Ok(has_file)
+    /// Downloads the files of a manifest from the tarball of the repo, returning whether any was
+    async fn download_tarball(
+        &self,
+        gh: &Github,
+        repo: &Repo,
+        sha: &str,
+        manifest: &Manifest,
+    ) -> Result<bool, Error> {
+        // Repos without build files have nothing to extract, like download_each
+        if manifest.paths.is_empty() {
+            return Ok(false);
+        }
+        debug!(
+            "Downloading {} files of {} from its tarball",
+            manifest.paths.len(),
+            repo.name
+        );
+        match gh.download_tarball(repo, sha, &manifest.paths).await {
+            Ok(extracted) => {
+                if extracted < manifest.paths.len() {
+                    warn!(
+                        "Tarball of {} had {extracted} of its {} files",
+                        repo.name,
+                        manifest.paths.len()
+                    );
+                }
+                Ok(extracted > 0)
+            }
+            Err(forge::Error::HttpError(code)) => {
+                warn!(
+                    "HTTP {} occurred while fetching the tarball of {}",
+                    code.as_u16(),
+                    repo.name
+                );
+                Ok(false)
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 
+    /// The build files of a repo at its current commit with the repo under its current name,
+    /// `None` (and marked as fetched) if its tree can't be listed
+    async fn list_files(
+        &self,
+        repo: &Repo,
+        retry: bool,
+    ) -> Result<Option<(Repo, Manifest)>, Error> {
+        let fetched_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let (repo, head, tree) = match self.head_and_tree(repo).await {
+            Ok(el) => el,
+            Err(e @ Error::Forge(forge::Error::HttpError(_))) if !retry => {
+                self.data.mark_fetched(repo).await?;
+                warn!("{e} while getting tree for {}", repo.name);
+                self.record_error(repo, "download", &e).await;
+                return Ok(None);
+            }
+            Err(e) => return Err(e),
+        };
+
+        let paths = tree
+            .nodes
+            .into_iter()
+            .map(|node| node.path)
+            .filter(|path| self.is_selected(path) || is_build_file(path))
+            .collect();
+
+        Ok(Some((
+            repo,
+            Manifest {
+                metadata: RepoMetadata {
+                    sha: Some(head.sha),
+                    default_branch: Some(head.default_branch),
+                    fetched_at,
+                    truncated: tree.truncated,
+                    checked_at: None,
+                },
+                paths,
+            },
+        )))
+    }
+
+    /// Loads a batch of repositories, journaling it as done once all of them are stored
     async fn load_repositories(&self, repos: Vec<String>) -> Result<(), Error> {
+        let nodes = repos.clone();
+        self.load_new_repositories(repos).await?;
+        if let Some(journal) = self.journal.get() {
+            journal.done(nodes).await?;
+        }
+        Ok(())
+    }
+
+    async fn load_new_repositories(&self, mut repos: Vec<String>) -> Result<(), Error> {
+        // Ranges can be scraped again after restarting with an older last_id
+        let mut known = Vec::new();
+        for id in &repos {
+            if self.data.is_known_repo(id).await? {
+                known.push(id.clone());
+            }
+        }
+        if !known.is_empty() {
+            debug!("Skipping {} already stored repos", known.len());
+            repos.retain(|id| !known.contains(id));
+        }
+        if repos.is_empty() {
+            return Ok(());
+        }
+
         info!("Loading {} repos", repos.len());
 
-        let mut graph_repos = self.gh.load_repositories(&repos).await?;
-        for repo in graph_repos.drain(..) {
-            if repo
-                .languages
-                .nodes
-                .iter()
-                .filter_map(Option::as_ref)
-                .any(|el| el.name == "Java")
-            {
-                let repo = repo.to_repo();
-                let has_files = self
-                    .fetch_all_files_for(&repo, String::from("pom.xml"))
-                    .await?;
+        let loaded = self
+            .forge
+            .load_repositories(&repos, &self.selection)
+            .await?;
+        for repo in loaded {
+            if self.selection.matches(&repo) {
+                let has_files = match self.fetch_all_files_for(&repo.repo, false).await {
+                    Ok(has_files) => has_files,
+                    Err(e) => {
+                        self.record_error(&repo.repo, "download", &e).await;
+                        return Err(e);
+                    }
+                };
 
-                self.data.store_repo(repo.to_csv_repo(has_files)).await?;
+                let mut stored = repo.repo.clone().to_csv_repo(has_files);
+                repo.enrich(&mut stored);
+                self.data.store_repo(stored).await?;
+                self.stored.fetch_add(1, SeqCst);
+                METRICS.repos_stored.fetch_add(1, Relaxed);
+                self.stored_poms.fetch_add(usize::from(has_files), SeqCst);
             }
         }
 
         Ok(())
     }
 
-    pub async fn download_files(&self) -> Result<(), Error> {
-        let repos = self.data.get_non_fetched_repos().await?;
+    /// Loads the license, stars, forks, creation date and disk usage of the stored repos stored
+    /// before those were recorded, and rewrites the repo store with them. Returns the amount of
+    /// repos backfilled and of repos left without, e.g. because they no longer exist.
+    pub async fn backfill_metadata(&self) -> Result<(usize, usize), Error> {
+        let data = self.data.clone();
+        let mut repos = tokio::task::spawn_blocking(move || data.read_repos())
+            .await
+            .unwrap()?;
+        let missing: Vec<String> = repos
+            .iter()
+            .filter(|repo| repo.stars.is_none())
+            .map(|repo| repo.id.clone())
+            .collect();
+        info!(
+            "Backfilling the metadata of {} of {} repos",
+            missing.len(),
+            repos.len()
+        );
 
-        for repo in repos {
+        let mut loaded = HashMap::new();
+        let selection = Selection::default();
+        for batch in missing.chunks(BATCH_SIZE) {
             if self.finished.load(SeqCst) {
                 break;
             }
-            self.fetch_all_files_for(&repo.into(), String::from("pom.xml"))
-                .await?;
+
+            let res = self.forge.load_repositories(batch, &selection).await;
+            self.record_outcome(res.as_ref().err().map(|e| e.kind()))
+                .await;
+            match res {
+                Ok(batch) => {
+                    loaded.extend(batch.into_iter().map(|repo| (repo.repo.id.clone(), repo)))
+                }
+                Err(e) => error!("Error: {e:?}"),
+            }
         }
 
-        Ok(())
+        for repo in &mut repos {
+            if let Some(loaded) = loaded.get(&repo.id) {
+                loaded.enrich(repo);
+            }
+        }
+        let data = self.data.clone();
+        tokio::task::spawn_blocking(move || data.write_repos(&repos))
+            .await
+            .unwrap()?;
+
+        Ok((loaded.len(), missing.len() - loaded.len()))
+    }
+
+    /// Downloads the files of the repos that weren't fetched yet, `concurrency` repos at a time.
+    /// After Ctrl+C or the first failed repo no new repos are started, the ones in flight finish.
+    pub async fn download_files(&self, concurrency: usize) -> Result<(), Error> {
+        let mut repos = self.data.get_non_fetched_repos().await?.into_iter();
+        let mut js = JoinSet::new();
+        let mut failed = None;
+
+        loop {
+            while js.len() < concurrency.max(1) && failed.is_none() && !self.finished.load(SeqCst) {
+                let Some(repo) = repos.next() else {
+                    break;
+                };
+                let me = self.clone();
+                js.spawn(async move {
+                    let repo: Repo = repo.into();
+                    let res = me.fetch_all_files_for(&repo, false).await;
+                    if let Err(e) = &res {
+                        me.record_error(&repo, "download", e).await;
+                    }
+                    res
+                });
+            }
+            METRICS.downloads_in_flight.store(js.len() as u64, Relaxed);
+
+            if self.finished.load(SeqCst) && !js.is_empty() {
+                info!("Waiting for {} repos in flight", js.len());
+            }
+            let Some(res) = js.join_next().await else {
+                break;
+            };
+            METRICS.downloads_in_flight.store(js.len() as u64, Relaxed);
+            let res = res.unwrap();
+            self.record_outcome(res.as_ref().err().map(|e| e.kind()))
+                .await;
+            if let Err(e) = res {
+                if !js.is_empty() {
+                    error!("Error: {e:?}, waiting for {} repos in flight", js.len());
+                }
+                failed.get_or_insert(e);
+            }
+        }
+
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    /// Tries the repos again whose last attempt at a stage failed with a retryable error, as
+    /// recorded in the error log, `concurrency` repos at a time. The errors of repos that succeed
+    /// are dropped from the log, the ones that fail again are recorded again.
+    pub async fn retry_failed(&self, concurrency: usize) -> Result<RetrySummary, Error> {
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let data = self.data.clone();
+        let (records, repos) = tokio::task::spawn_blocking(move || {
+            Ok::<_, data::Error>((data.read_errors()?, data.read_repos()?))
+        })
+        .await
+        .unwrap()?;
+
+        // The log is in order, so the last record of a repo and stage is its last attempt
+        let mut last = HashMap::new();
+        for record in records {
+            if let Some(id) = record.repo_id.clone() {
+                last.insert((id, record.stage.clone()), record);
+            }
+        }
+        let repos: HashMap<String, Repo> = repos
+            .into_iter()
+            .map(|repo| (repo.id.clone(), repo.into()))
+            .collect();
+
+        let mut summary = RetrySummary::default();
+        let mut failures = Vec::new();
+        for ((id, stage), record) in last {
+            if !record.retryable {
+                continue;
+            }
+            match repos.get(&id) {
+                Some(repo) if matches!(stage.as_str(), "download" | "releases") => {
+                    failures.push((repo.clone(), stage))
+                }
+                _ => summary.skipped += 1,
+            }
+        }
+        failures.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+        summary.retried = failures.len();
+        info!("Retrying {} failures", failures.len());
+
+        let mut failures = failures.into_iter();
+        let mut succeeded: HashMap<String, HashSet<String>> = HashMap::new();
+        let mut js = JoinSet::new();
+        loop {
+            while js.len() < concurrency.max(1) && !self.finished.load(SeqCst) {
+                let Some((repo, stage)) = failures.next() else {
+                    break;
+                };
+                let me = self.clone();
+                js.spawn(async move {
+                    let res = match stage.as_str() {
+                        "download" => me.fetch_all_files_for(&repo, true).await.map(|_| ()),
+                        _ => me.has_github_releases(&repo).await.map(|_| ()),
+                    };
+                    if let Err(e) = &res {
+                        me.record_error(&repo, &stage, e).await;
+                    }
+                    (repo, stage, res)
+                });
+            }
+
+            let Some(next) = js.join_next().await else {
+                break;
+            };
+            let (repo, stage, res) = next.unwrap();
+            self.record_outcome(res.as_ref().err().map(|e| e.kind()))
+                .await;
+            match res {
+                Ok(()) => {
+                    summary.succeeded += 1;
+                    succeeded.entry(stage).or_default().insert(repo.id);
+                }
+                Err(e) => {
+                    warn!("Retrying {stage} of {} failed: {e}", repo.name);
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        let data = self.data.clone();
+        tokio::task::spawn_blocking(move || {
+            for (stage, ids) in succeeded {
+                data.clear_errors(&stage, &ids, started)?;
+            }
+            Ok::<_, data::Error>(())
+        })
+        .await
+        .unwrap()?;
+
+        Ok(summary)
     }
 
     pub async fn fetch_and_download(&self) -> Result<(), Error> {
         let start = Instant::now();
+        self.scrape_new(false).await?;
+        info!("Took {} seconds", start.elapsed().as_secs());
+
+        Ok(())
+    }
 
-        let mut to_load = Vec::with_capacity(100);
+    /// Opens the journal and loads the repos an earlier run left pending, once
+    async fn open_journal(&self) -> Result<&Journal, Error> {
+        if let Some(journal) = self.journal.get() {
+            return Ok(journal);
+        }
+
+        let data = self.data.clone();
+        let repos = tokio::task::spawn_blocking(move || data.read_repos())
+            .await
+            .unwrap()?;
+        self.stored.store(repos.len(), SeqCst);
+        self.stored_poms
+            .store(repos.iter().filter(|repo| repo.has_pom).count(), SeqCst);
+        drop(repos);
+
+        let path = self.data.output_path("journal", "jsonl", None);
+        let journal = tokio::task::spawn_blocking(move || Journal::open(&path))
+            .await
+            .unwrap()?;
+        let outstanding = journal.outstanding().to_vec();
+        let journal = self.journal.get_or_init(|| journal);
+        if !outstanding.is_empty() {
+            info!(
+                "Resuming {} repos left pending by an earlier run",
+                outstanding.len()
+            );
+            for batch in outstanding.chunks(BATCH_SIZE) {
+                let res = self.load_repositories(batch.to_vec()).await;
+                self.record_outcome(res.as_ref().err().map(|e| e.kind()))
+                    .await;
+                if let Err(e) = res {
+                    warn!("Failed scraping repo: {:?}", e);
+                }
+            }
+        }
+
+        Ok(journal)
+    }
+
+    /// Lists and loads the repos after the last id until Ctrl+C, or with `until_caught_up` until
+    /// a page isn't full, meaning the newest repos were listed
+    async fn scrape_new(&self, until_caught_up: bool) -> Result<(), Error> {
+        let journal = self.open_journal().await?;
+        let mut to_load = Vec::with_capacity(BATCH_SIZE);
 
         let mut last_id = self.data.get_last_id()?;
+        self.record_progress(last_id).await?;
+        let mut last_progress = Instant::now();
         loop {
             let start_loop = Instant::now();
             // TODO: Check timeout
-            let mut repos = self.gh.scrape_repositories(last_id).await?;
-            let finished = self.finished.load(SeqCst);
-            let mut js = JoinSet::new();
+            let mut repos = self.forge.list_repositories(last_id).await?;
+            let caught_up = until_caught_up && repos.len() < PAGE_SIZE;
+            let finished = self.finished.load(SeqCst) || caught_up;
+            let mut listed = Vec::new();
+            METRICS.repos_listed.fetch_add(repos.len() as u64, Relaxed);
 
             for repo in repos.drain(..) {
                 last_id = repo.id;
-                if repo.fork {
+                if repo.fork && !self.selection.include_forks {
                     continue;
                 }
+                listed.push(repo.node_id);
+            }
+
+            // Journaled before any of them is loaded and last_id moves past them, so they are
+            // loaded again after a crash and a finished batch can't be journaled after it's done
+            journal.pending(listed.clone()).await?;
 
-                to_load.push(repo.node_id);
+            let mut js = JoinSet::new();
+            for node_id in listed {
+                to_load.push(node_id);
 
-                if to_load.len() == 100 {
+                if to_load.len() == BATCH_SIZE {
                     let to_load_now = to_load.clone();
                     let me = self.clone();
                     js.spawn(async move { me.load_repositories(to_load_now).await });
                     to_load.clear();
                 };
             }
-
             self.data.set_last_id(last_id).await.unwrap();
+            METRICS.last_id.store(last_id as u64, Relaxed);
+            METRICS
+                .repos_queued
+                .store((to_load.len() + js.len() * BATCH_SIZE) as u64, Relaxed);
 
             while let Some(res) = js.join_next().await {
                 let res = res.unwrap();
+                self.record_outcome(res.as_ref().err().map(|e| e.kind()))
+                    .await;
                 if let Err(e) = res {
                     warn!("Failed scraping repo: {:?}", e);
                 }
             }
+            METRICS.repos_queued.store(to_load.len() as u64, Relaxed);
 
             if finished {
                 if !to_load.is_empty() {
                     let to_load_now = to_load.clone();
                     self.load_repositories(to_load_now).await?;
                 }
+                self.record_progress(last_id).await?;
                 break;
             }
 
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                self.record_progress(last_id).await?;
+                last_progress = Instant::now();
+            }
+
             if let Some(time) = Duration::from_millis(250).checked_sub(start_loop.elapsed()) {
                 sleep(time).await;
             }
         }
 
-        info!("Took {} seconds", start.elapsed().as_secs());
-
         Ok(())
     }
 }
@@ -0,0 +1,146 @@
+//! Glob patterns of files to download besides the build files of the [BuildSystem]s, to collect
+//! other files of the repos, like `gradle.properties` or `.mvn/wrapper/maven-wrapper.properties`.
+//!
+//! Like the [BUILD_FILES], a pattern matches files at any depth: it is matched against the path
+//! and every suffix of it starting after a `/`. `*` and `?` don't match a `/`, `**` does.
+//!
+//! [BuildSystem]: crate::scraper::BuildSystem
+//! [BUILD_FILES]: crate::scraper::BUILD_FILES
+
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern(String);
+
+impl Pattern {
+    pub fn matches(&self, path: &str) -> bool {
+        let mut suffix = path;
+        loop {
+            if glob(self.0.as_bytes(), suffix.as_bytes()) {
+                return true;
+            }
+            match suffix.split_once('/') {
+                Some((_, rest)) => suffix = rest,
+                None => return false,
+            }
+        }
+    }
+}
+
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    // Memoized by position in both, as every `*` would otherwise try all remaining splits again
+    let mut memo = vec![None; (pattern.len() + 1) * (text.len() + 1)];
+    glob_at(pattern, text, 0, 0, &mut memo)
+}
+
+/// Whether `pattern[p..]` matches `text[t..]`
+fn glob_at(pattern: &[u8], text: &[u8], p: usize, t: usize, memo: &mut [Option<bool>]) -> bool {
+    let key = p * (text.len() + 1) + t;
+    if let Some(matched) = memo[key] {
+        return matched;
+    }
+
+    let matched = match &pattern[p..] {
+        [] => t == text.len(),
+        // `**/` also matches no directories at all
+        [b'*', b'*', b'/', ..] => (t..=text.len())
+            .filter(|&i| i == t || text[i - 1] == b'/')
+            .any(|i| glob_at(pattern, text, p + 3, i, memo)),
+        [b'*', b'*', ..] => (t..=text.len()).any(|i| glob_at(pattern, text, p + 2, i, memo)),
+        [b'*', ..] => (t..=text.len())
+            .take_while(|&i| i == t || text[i - 1] != b'/')
+            .any(|i| glob_at(pattern, text, p + 1, i, memo)),
+        [b'?', ..] => text
+            .get(t)
+            .is_some_and(|&c| c != b'/' && glob_at(pattern, text, p + 1, t + 1, memo)),
+        [c, ..] => text
+            .get(t)
+            .is_some_and(|t_c| t_c == c && glob_at(pattern, text, p + 1, t + 1, memo)),
+    };
+    memo[key] = Some(matched);
+    matched
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let pattern = s.trim().trim_start_matches('/');
+        if pattern.is_empty() || pattern.ends_with('/') {
+            return Err(format!("invalid file pattern {s:?}"));
+        }
+        Ok(Pattern(pattern.to_string()))
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+
+    fn matches(pattern: &str, path: &str) -> bool {
+        pattern.parse::<Pattern>().unwrap().matches(path)
+    }
+
+    #[test]
+    fn matches_at_any_depth() {
+        assert!(matches("gradle.properties", "gradle.properties"));
+        assert!(matches("gradle.properties", "sub/module/gradle.properties"));
+        assert!(matches(
+            ".mvn/wrapper/maven-wrapper.properties",
+            "sub/.mvn/wrapper/maven-wrapper.properties"
+        ));
+        assert!(!matches("gradle.properties", "my-gradle.properties"));
+        assert!(!matches(
+            ".mvn/wrapper/maven-wrapper.properties",
+            "maven-wrapper.properties"
+        ));
+    }
+
+    #[test]
+    fn star_stays_within_a_directory() {
+        assert!(matches("*.gradle", "build.gradle"));
+        assert!(matches("*.gradle", "sub/build.gradle"));
+        assert!(matches("sub/*.gradle", "sub/build.gradle"));
+        assert!(!matches("sub/*.gradle", "sub/nested/build.gradle"));
+        assert!(!matches("*.gradle", "build.gradle.kts"));
+    }
+
+    #[test]
+    fn question_mark_matches_one_character() {
+        assert!(matches("pom.?ml", "pom.xml"));
+        assert!(!matches("pom.?ml", "pom.ml"));
+        assert!(!matches("a?b", "a/b"));
+    }
+
+    #[test]
+    fn double_star_crosses_directories() {
+        assert!(matches("config/**/settings.xml", "config/settings.xml"));
+        assert!(matches("config/**/settings.xml", "config/a/b/settings.xml"));
+        assert!(!matches(
+            "config/**/settings.xml",
+            "config/a/b/my-settings.xml"
+        ));
+        assert!(matches("docs/**", "docs/a/b.md"));
+    }
+
+    #[test]
+    fn doesnt_backtrack_exponentially() {
+        let path = "a".repeat(200);
+        assert!(!matches("*a*a*a*a*a*a*a*a*a*a*b", &path));
+        assert!(!matches("**a**a**a**a**a**a**a**a**b", &path));
+        assert!(matches("*a*a*a*a*a*a*a*a*a*a", &path));
+    }
+
+    #[test]
+    fn rejects_directories() {
+        assert!("".parse::<Pattern>().is_err());
+        assert!("docs/".parse::<Pattern>().is_err());
+    }
+}
@@ -0,0 +1,78 @@
+//! Coverage of the GitHub id space and projected completion, from the history in `state.json`
+
+use crate::data::ProgressPoint;
+
+/// Window of the history the projected completion is based on
+const RATE_WINDOW: u64 = 24 * 60 * 60;
+/// Maximum amount of history rows printed
+const ROWS: usize = 20;
+
+fn format_duration(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 * 60 => format!("{:.0} minutes", s as f64 / 60.0),
+        s if s < 2 * 24 * 60 * 60 => format!("{:.1} hours", s as f64 / (60.0 * 60.0)),
+        s => format!("{:.1} days", s as f64 / (24.0 * 60.0 * 60.0)),
+    }
+}
+
+fn bar(fraction: f64) -> String {
+    let filled = (fraction.clamp(0.0, 1.0) * 30.0).round() as usize;
+    format!("[{}{}]", "#".repeat(filled), " ".repeat(30 - filled))
+}
+
+pub fn print(history: &[ProgressPoint], max_id: usize, now: u64) {
+    let (Some(first), Some(last)) = (history.first(), history.last()) else {
+        println!("No progress recorded yet, run fetch-and-download first");
+        return;
+    };
+
+    let coverage = last.last_id as f64 / max_id.max(1) as f64;
+    println!(
+        "Scraped up to id {} of ~{max_id} ({:.2}%), {} ago",
+        last.last_id,
+        coverage * 100.0,
+        format_duration(now.saturating_sub(last.timestamp))
+    );
+    println!("Repos stored: {}, with poms: {}", last.repos, last.poms);
+
+    let start = history
+        .iter()
+        .find(|point| point.timestamp + RATE_WINDOW >= last.timestamp)
+        .unwrap_or(first);
+    let elapsed = last.timestamp.saturating_sub(start.timestamp);
+    if elapsed > 0 && last.last_id > start.last_id {
+        let hours = elapsed as f64 / (60.0 * 60.0);
+        let ids_per_hour = (last.last_id - start.last_id) as f64 / hours;
+        println!(
+            "Rate over the last {}: {ids_per_hour:.0} ids/hour, {:.0} repos/hour, {:.0} with poms/hour",
+            format_duration(elapsed),
+            last.repos.saturating_sub(start.repos) as f64 / hours,
+            last.poms.saturating_sub(start.poms) as f64 / hours,
+        );
+
+        let remaining = max_id.saturating_sub(last.last_id) as f64 / ids_per_hour;
+        println!(
+            "Projected completion in {} (not counting repos created in the meantime)",
+            format_duration((remaining * 60.0 * 60.0) as u64)
+        );
+    }
+
+    println!("History:");
+    let step = history.len().div_ceil(ROWS);
+    let rows = history
+        .iter()
+        .step_by(step)
+        .chain((!(history.len() - 1).is_multiple_of(step)).then_some(last));
+    for point in rows {
+        let fraction = point.last_id as f64 / max_id.max(1) as f64;
+        println!(
+            "  {:>14} ago  {} {:>6.2}%  id {:>10}  repos {:>8}  poms {:>8}",
+            format_duration(now.saturating_sub(point.timestamp)),
+            bar(fraction),
+            fraction * 100.0,
+            point.last_id,
+            point.repos,
+            point.poms
+        );
+    }
+}
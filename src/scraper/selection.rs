@@ -0,0 +1,84 @@
+//! Which of the loaded repositories fetch-and-download stores and downloads the files of.
+
+use crate::scraper::forge::{parse_timestamp, LoadedRepo};
+
+/// Selection criteria of fetch-and-download, by default all Java repos that aren't forks
+#[derive(Debug, Clone, clap::Args)]
+pub struct Selection {
+    /// Only select repos using any of these languages (compared case-insensitively)
+    #[arg(long, value_delimiter = ',', default_value = "Java")]
+    pub languages: Vec<String>,
+
+    /// Only select repos with at least this many stars
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    pub min_stars: u64,
+
+    /// Only select repos pushed to after this date, e.g. `2023-01-01`
+    #[arg(long, value_name = "DATE", value_parser = parse_date)]
+    pub pushed_after: Option<u64>,
+
+    /// Only select repos with any of these topics
+    #[arg(long, value_delimiter = ',')]
+    pub topics: Vec<String>,
+
+    /// Skip archived repos
+    #[arg(long)]
+    pub exclude_archived: bool,
+
+    /// Also select forks, which are skipped by default
+    #[arg(long)]
+    pub include_forks: bool,
+}
+
+impl Default for Selection {
+    fn default() -> Self {
+        Self {
+            languages: vec!["Java".to_string()],
+            min_stars: 0,
+            pushed_after: None,
+            topics: Vec::new(),
+            exclude_archived: false,
+            include_forks: false,
+        }
+    }
+}
+
+/// Seconds since the unix epoch of a date (`2023-01-01`) or UTC timestamp (`2023-01-01T12:00:00Z`)
+fn parse_date(date: &str) -> Result<u64, String> {
+    let timestamp = if date.contains('T') {
+        date.to_string()
+    } else {
+        format!("{date}T00:00:00Z")
+    };
+    parse_timestamp(&timestamp).ok_or_else(|| format!("invalid date {date:?}, expected YYYY-MM-DD"))
+}
+
+impl Selection {
+    /// Whether the topics of repos have to be loaded, which GitHub charges extra for
+    pub fn needs_topics(&self) -> bool {
+        !self.topics.is_empty()
+    }
+
+    pub fn matches_languages(&self, languages: &[String]) -> bool {
+        languages.iter().any(|language| {
+            self.languages
+                .iter()
+                .any(|wanted| wanted.eq_ignore_ascii_case(language))
+        })
+    }
+
+    pub fn matches(&self, repo: &LoadedRepo) -> bool {
+        self.matches_languages(&repo.languages)
+            && repo.stars >= self.min_stars
+            && self
+                .pushed_after
+                .is_none_or(|after| repo.pushed_at.is_some_and(|at| at > after))
+            && (self.topics.is_empty()
+                || repo.topics.iter().any(|topic| {
+                    self.topics
+                        .iter()
+                        .any(|wanted| wanted.eq_ignore_ascii_case(topic))
+                }))
+            && !(self.exclude_archived && repo.archived)
+    }
+}
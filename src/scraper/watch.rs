@@ -0,0 +1,204 @@
+//! `rp watch`: keeps a data dir up to date by scraping in rounds until Ctrl+C. Every round loads
+//! the repos created since the last id, then checks the fetched repos that weren't checked
+//! recently for a moved default branch.
+//!
+//! Checking a repo costs a single request for its head. A repo that changed is listed into a
+//! manifest again and its files are downloaded like an interrupted download: files are written
+//! atomically, unchanged ones aren't written again and `metadata.json` only moves to the new
+//! commit once all are.
+//! Build files that were removed from the repo are removed last.
+
+use crate::data;
+use crate::scraper::{is_build_file, Error, Scraper};
+use crate::Repo;
+use std::collections::HashSet;
+use std::fs;
+use std::sync::atomic::Ordering::SeqCst;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::{spawn_blocking, JoinSet};
+use tokio::time::sleep;
+use tracing::{debug, error, info, warn};
+use walkdir::WalkDir;
+
+/// Amount of repos checked at the same time
+const CHECK_CONCURRENCY: usize = 8;
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Time from the start of a round to the start of the next
+    pub interval: Duration,
+    /// Time after which a fetched repo is checked again
+    pub check_after: Duration,
+}
+
+/// What checking the fetched repos did in a round
+#[derive(Debug, Default)]
+pub struct CheckSummary {
+    pub checked: usize,
+    /// Repos whose default branch moved, whose files were downloaded again
+    pub changed: usize,
+    pub failed: usize,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+impl Scraper {
+    /// Scrapes in rounds until Ctrl+C, see [the module](self)
+    pub async fn watch(&self, options: &Options) -> Result<(), Error> {
+        let mut round = 0;
+        while !self.finished.load(SeqCst) {
+            round += 1;
+            let start = Instant::now();
+
+            if let Err(e) = self.scrape_new(true).await {
+                error!("Failed scraping new repos: {e:?}");
+            }
+            let summary = self.check_all(options.check_after).await?;
+            info!(
+                "Round {round} took {} seconds: {} repos stored ({} with poms), checked {} repos, {} changed, {} failed",
+                start.elapsed().as_secs(),
+                self.stored.load(SeqCst),
+                self.stored_poms.load(SeqCst),
+                summary.checked,
+                summary.changed,
+                summary.failed
+            );
+
+            // Sleeps in steps, so Ctrl+C stops it without waiting for the next round
+            while !self.finished.load(SeqCst) {
+                let Some(left) = options.interval.checked_sub(start.elapsed()) else {
+                    break;
+                };
+                sleep(left.min(Duration::from_secs(1))).await;
+            }
+        }
+
+        info!("Stopped watching after {round} rounds");
+        Ok(())
+    }
+
+    /// Checks the fetched repos that weren't checked for `check_after`
+    async fn check_all(&self, check_after: Duration) -> Result<CheckSummary, Error> {
+        let data = self.data.clone();
+        let repos = spawn_blocking(move || data.read_repos()).await.unwrap()?;
+        let repos: Vec<Repo> = repos
+            .into_iter()
+            .filter(|repo| repo.has_pom)
+            .map(Repo::from)
+            .collect();
+        let due = now().saturating_sub(check_after.as_secs());
+
+        let mut summary = CheckSummary::default();
+        for repos in repos.chunks(CHECK_CONCURRENCY) {
+            if self.finished.load(SeqCst) {
+                break;
+            }
+
+            let mut js = JoinSet::new();
+            for repo in repos {
+                let repo = repo.clone();
+                let me = self.clone();
+                js.spawn(async move {
+                    let res = me.check_repo(&repo, due).await;
+                    if let Err(e) = &res {
+                        me.record_error(&repo, "watch", e).await;
+                    }
+                    res
+                });
+            }
+
+            while let Some(next) = js.join_next().await {
+                let next = next.unwrap();
+                self.record_outcome(next.as_ref().err().map(|e| e.kind()))
+                    .await;
+                match next {
+                    Ok(Some(changed)) => {
+                        summary.checked += 1;
+                        summary.changed += usize::from(changed);
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        warn!("Failed checking repo: {e:?}");
+                        summary.failed += 1;
+                    }
+                }
+            }
+        }
+
+        Ok(summary)
+    }
+
+    /// Downloads the files of a fetched repo again if its default branch moved, returning whether
+    /// it did. `None` if the repo has no downloaded files or was checked after `due`.
+    async fn check_repo(&self, repo: &Repo, due: u64) -> Result<Option<bool>, Error> {
+        let Some(mut metadata) = self.data.read_metadata(repo).await? else {
+            return Ok(None);
+        };
+        if metadata.checked_at.unwrap_or(0).max(metadata.fetched_at) > due {
+            return Ok(None);
+        }
+
+        let head = self.forge.head(repo).await?;
+        let repo = self.follow_rename(repo, &head).await?;
+        if metadata.sha.as_deref() == Some(head.sha.as_str()) {
+            debug!("{} didn't change", repo.name);
+            metadata.checked_at = Some(now());
+            self.data.write_metadata(&repo, &metadata).await?;
+            return Ok(Some(false));
+        }
+
+        info!("{} changed, downloading its files again", repo.name);
+        let Some((repo, manifest)) = self.list_files(&repo, false).await? else {
+            return Ok(Some(false));
+        };
+        // Files of the old commit are on disk, so they are overwritten instead of skipped
+        self.download_manifest(&repo, &manifest, true).await?;
+
+        let removed = self
+            .remove_stale_files(&repo, manifest.paths.iter().cloned().collect())
+            .await?;
+        if removed > 0 {
+            info!("Removed {removed} files no longer in {}", repo.name);
+        }
+        self.data.write_metadata(&repo, &manifest.metadata).await?;
+
+        Ok(Some(true))
+    }
+
+    /// Removes the downloaded build files of a repo that aren't in `paths`, returning how many
+    async fn remove_stale_files(
+        &self,
+        repo: &Repo,
+        paths: HashSet<String>,
+    ) -> Result<usize, Error> {
+        let dir = self.data.get_project_dir(repo);
+        let me = self.clone();
+        let removed = spawn_blocking(move || -> Result<usize, data::Error> {
+            let mut removed = 0;
+            for entry in WalkDir::new(&dir).into_iter().filter_map(|e| e.ok()) {
+                if !entry.file_type().is_file() {
+                    continue;
+                }
+                let Ok(relative) = entry.path().strip_prefix(&dir) else {
+                    continue;
+                };
+                let path = relative.to_string_lossy();
+                if (me.is_selected(&path) || is_build_file(&path)) && !paths.contains(path.as_ref())
+                {
+                    fs::remove_file(entry.path())?;
+                    removed += 1;
+                }
+            }
+            Ok(removed)
+        })
+        .await
+        .unwrap()?;
+
+        Ok(removed)
+    }
+}
@@ -0,0 +1,222 @@
+//! A dashboard and JSON API of a data dir, served by `rp serve` to follow long runs on a remote
+//! machine. Requests read the data dir again, so they show what another process scraping or
+//! analyzing into it wrote last. The repo counts and projects, which are expensive to read, are
+//! kept until their files are modified.
+
+use crate::analyzer::{biggest_n, hostname_counts, Project, Report};
+use crate::data::{self, Data, ProgressPoint, StoreMetadata};
+use crate::project_dir;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio::task::spawn_blocking;
+use tracing::{info, warn};
+
+const INDEX: &str = include_str!("serve.html");
+
+#[derive(Debug, Error)]
+enum ApiError {
+    #[error("Not found")]
+    NotFound,
+
+    #[error("Data error: {0}")]
+    Data(#[from] data::Error),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match &self {
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::Data(data::Error::IO(e)) if e.kind() == io::ErrorKind::NotFound => {
+                StatusCode::NOT_FOUND
+            }
+            ApiError::Data(e) => {
+                warn!("Failed serving request: {e}");
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+/// Runs a blocking read of the data dir off the async runtime
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> Result<T, ApiError> + Send + 'static,
+) -> Result<T, ApiError> {
+    spawn_blocking(f).await.unwrap()
+}
+
+/// Modification times and sizes of files, `None` for missing ones
+type Modified = Vec<Option<(SystemTime, u64)>>;
+
+/// A value read from files, read again once any of them is modified
+struct Cached<T> {
+    /// The value with its files when it was read
+    value: Mutex<Option<(Modified, Arc<T>)>>,
+}
+
+impl<T> Default for Cached<T> {
+    fn default() -> Self {
+        Self {
+            value: Mutex::new(None),
+        }
+    }
+}
+
+impl<T> Cached<T> {
+    fn get(
+        &self,
+        files: &[PathBuf],
+        read: impl FnOnce() -> Result<T, ApiError>,
+    ) -> Result<Arc<T>, ApiError> {
+        let modified: Modified = files
+            .iter()
+            .map(|file| {
+                let meta = fs::metadata(file).ok()?;
+                Some((meta.modified().ok()?, meta.len()))
+            })
+            .collect();
+
+        // Held while reading, so concurrent requests don't read the same files twice
+        let mut value = self.value.lock().unwrap();
+        if let Some((read_at, value)) = &*value {
+            if *read_at == modified {
+                return Ok(value.clone());
+            }
+        }
+        let read = Arc::new(read()?);
+        *value = Some((modified, read.clone()));
+        Ok(read)
+    }
+}
+
+#[derive(Clone)]
+struct AppState {
+    data: Data,
+    store: Arc<Cached<StoreMetadata>>,
+    /// Projects by name
+    projects: Arc<Cached<HashMap<String, Project>>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Progress {
+    last_id: usize,
+    /// Repos in the repo store
+    repos: usize,
+    /// Repos whose files were downloaded
+    fetched: usize,
+    history: Vec<ProgressPoint>,
+}
+
+async fn progress(State(state): State<AppState>) -> Result<Json<Progress>, ApiError> {
+    blocking(move || {
+        let data = &state.data;
+        let (last_id, history) = data.read_progress()?;
+        let store = state
+            .store
+            .get(&data.store_files(), || Ok(data.store_metadata()?))?;
+        Ok(Json(Progress {
+            last_id,
+            repos: store.rows,
+            fetched: store.fetched,
+            history,
+        }))
+    })
+    .await
+}
+
+async fn report(State(state): State<AppState>) -> Result<Json<Report>, ApiError> {
+    blocking(move || Ok(Json(state.data.read_report()?))).await
+}
+
+async fn project(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Project>, ApiError> {
+    blocking(move || {
+        let data = &state.data;
+        let projects = state
+            .projects
+            .get(&[data.output_path("projects", "json", None)], || {
+                Ok(data
+                    .read_projects()?
+                    .into_iter()
+                    .map(|project| (project.name.clone(), project))
+                    .collect())
+            })?;
+        // Accepts the repo name (`owner/repo`) as well as the project dir name
+        projects
+            .get(&project_dir(&name))
+            .cloned()
+            .map(Json)
+            .ok_or(ApiError::NotFound)
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+struct TopN {
+    #[serde(default = "default_top")]
+    n: usize,
+}
+
+fn default_top() -> usize {
+    25
+}
+
+#[derive(Debug, Serialize)]
+struct Hostnames {
+    total: usize,
+    external: Vec<(String, usize)>,
+    distribution: Vec<(String, usize)>,
+}
+
+async fn hostnames(
+    State(state): State<AppState>,
+    Query(TopN { n }): Query<TopN>,
+) -> Result<Json<Hostnames>, ApiError> {
+    blocking(move || {
+        let report = state.data.read_report()?;
+        Ok(Json(Hostnames {
+            total: report.total,
+            external: biggest_n(hostname_counts(&report.external_repos), n),
+            distribution: biggest_n(hostname_counts(&report.distros), n),
+        }))
+    })
+    .await
+}
+
+/// Serves the dashboard of a data dir on `addr` until Ctrl+C
+pub async fn serve(data: Data, addr: SocketAddr) -> io::Result<()> {
+    let app = Router::new()
+        .route("/", get(|| async { Html(INDEX) }))
+        .route("/api/progress", get(progress))
+        .route("/api/report", get(report))
+        .route("/api/projects/*name", get(project))
+        .route("/api/hostnames", get(hostnames))
+        .with_state(AppState {
+            data,
+            store: Default::default(),
+            projects: Default::default(),
+        });
+
+    let listener = TcpListener::bind(addr).await?;
+    info!("Serving the dashboard on http://{addr}/");
+    axum::serve(listener, app)
+        .with_graceful_shutdown(async {
+            let _ = tokio::signal::ctrl_c().await;
+        })
+        .await
+}